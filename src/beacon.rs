@@ -0,0 +1,201 @@
+//! Periodic beacon transmitter.
+//!
+//! [`Beacon`] sends the same payload at a fixed interval, with random jitter so that beacons
+//! of devices started together drift apart, and keeps track of the time spent on air to stay
+//! within a regulatory duty cycle (e.g. 1% per hour in most EN 300 220 sub-bands).
+
+use crate::{Cc1101, Error, RadioMode};
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+/// Beacon timing and duty cycle limits.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BeaconConfig {
+    /// Nominal time between beacons (in microseconds).
+    pub interval_us: u32,
+    /// Maximum random time added to or removed from each interval (in microseconds).
+    pub jitter_us: u32,
+    /// Time on air of one beacon (in microseconds).
+    pub airtime_us: u32,
+    /// Maximum fraction of `window_us` spent transmitting (in permille).
+    pub duty_cycle_permille: u32,
+    /// Duty cycle observation window (in microseconds).
+    pub window_us: u32,
+}
+
+/// Time on air accounting over a fixed window.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct DutyCycle {
+    limit_us: u32,
+    window_us: u32,
+    elapsed_us: u32,
+    on_air_us: u32,
+}
+
+impl DutyCycle {
+    pub fn new(permille: u32, window_us: u32) -> Self {
+        DutyCycle {
+            limit_us: (window_us as u64 * permille as u64 / 1000) as u32,
+            window_us,
+            elapsed_us: 0,
+            on_air_us: 0,
+        }
+    }
+
+    /// Let time pass, starting a new window once the current one is over.
+    pub fn advance(&mut self, us: u32) {
+        self.elapsed_us = self.elapsed_us.saturating_add(us);
+        if self.elapsed_us >= self.window_us {
+            self.elapsed_us = 0;
+            self.on_air_us = 0;
+        }
+    }
+
+    /// Whether a transmission of `airtime_us` fits in what's left of the current window.
+    pub fn allows(&self, airtime_us: u32) -> bool {
+        self.on_air_us.saturating_add(airtime_us) <= self.limit_us
+    }
+
+    /// Account for a transmission of `airtime_us`.
+    pub fn record(&mut self, airtime_us: u32) {
+        self.on_air_us = self.on_air_us.saturating_add(airtime_us);
+        self.advance(airtime_us);
+    }
+
+    /// Time spent on air in the current window (in microseconds).
+    pub fn on_air_us(&self) -> u32 {
+        self.on_air_us
+    }
+}
+
+/// Periodic beacon transmitter, see the module documentation.
+pub struct Beacon<'a> {
+    payload: &'a [u8],
+    config: BeaconConfig,
+    duty_cycle: DutyCycle,
+    rng: u32,
+    sent: u32,
+    skipped: u32,
+}
+
+impl<'a> Beacon<'a> {
+    /// `seed` initializes the jitter generator, use something unique to the device (e.g. its
+    /// address or serial number).
+    pub fn new(payload: &'a [u8], config: BeaconConfig, seed: u32) -> Self {
+        Beacon {
+            payload,
+            config,
+            duty_cycle: DutyCycle::new(config.duty_cycle_permille, config.window_us),
+            // xorshift gets stuck at zero
+            rng: seed.max(1),
+            sent: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Time until the next beacon (in microseconds), the interval with a new random jitter.
+    pub fn next_delay_us(&mut self) -> u32 {
+        // xorshift32
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        let span = 2 * self.config.jitter_us as u64 + 1;
+        let offset = (self.rng as u64 % span) as i64 - self.config.jitter_us as i64;
+        (self.config.interval_us as i64 + offset).max(0) as u32
+    }
+
+    /// Wait for the next beacon time, then transmit the beacon if the duty cycle allows it.
+    ///
+    /// The radio is taken to IDLE for the transmission, and put back in RX afterwards if that's
+    /// where it was. Returns whether the beacon was sent.
+    pub fn run_once<SPI, SpiE, D>(
+        &mut self,
+        cc1101: &mut Cc1101<SPI>,
+        delay: &mut D,
+    ) -> Result<bool, Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+        D: DelayNs,
+    {
+        let wait_us = self.next_delay_us();
+        delay.delay_us(wait_us);
+        self.duty_cycle.advance(wait_us);
+
+        if !self.duty_cycle.allows(self.config.airtime_us) {
+            self.skipped += 1;
+            return Ok(false);
+        }
+        let resume_rx = cc1101.is_in(RadioMode::Receive)?;
+        if resume_rx {
+            cc1101.abort_receive()?;
+        }
+        cc1101.transmit_parts(&[self.payload])?;
+        self.duty_cycle.record(self.config.airtime_us);
+        self.sent += 1;
+        if resume_rx {
+            cc1101.set_radio_mode(RadioMode::Receive)?;
+        }
+        Ok(true)
+    }
+
+    /// Run forever, only returning on error.
+    pub fn run<SPI, SpiE, D>(&mut self, cc1101: &mut Cc1101<SPI>, delay: &mut D) -> Error<SpiE>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+        D: DelayNs,
+    {
+        loop {
+            if let Err(e) = self.run_once(cc1101, delay) {
+                return e;
+            }
+        }
+    }
+
+    /// Duty cycle accounting.
+    pub fn duty_cycle(&self) -> &DutyCycle {
+        &self.duty_cycle
+    }
+
+    /// Number of beacons sent.
+    pub fn sent(&self) -> u32 {
+        self.sent
+    }
+
+    /// Number of beacons skipped to respect the duty cycle.
+    pub fn skipped(&self) -> u32 {
+        self.skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beacon::*;
+
+    #[test]
+    fn test_duty_cycle() {
+        let mut duty_cycle = DutyCycle::new(10, 1_000_000);
+        assert!(duty_cycle.allows(10_000));
+        duty_cycle.record(6_000);
+        assert!(!duty_cycle.allows(6_000));
+        duty_cycle.advance(500_000);
+        assert_eq!(duty_cycle.on_air_us(), 6_000);
+        duty_cycle.advance(500_000);
+        assert_eq!(duty_cycle.on_air_us(), 0);
+        assert!(duty_cycle.allows(6_000));
+    }
+
+    #[test]
+    fn test_jitter() {
+        let config = BeaconConfig {
+            interval_us: 1_000_000,
+            jitter_us: 50_000,
+            airtime_us: 2_000,
+            duty_cycle_permille: 10,
+            window_us: 3_600_000_000,
+        };
+        let mut beacon = Beacon::new(&[], config, 0);
+        let delays: [u32; 64] = core::array::from_fn(|_| beacon.next_delay_us());
+        assert!(delays.iter().all(|&d| (950_000..=1_050_000).contains(&d)));
+        assert!(delays.iter().any(|&d| d != delays[0]));
+    }
+}
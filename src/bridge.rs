@@ -0,0 +1,221 @@
+//! Transparent serial link over the radio, like HC-12 style modules.
+//!
+//! Bytes read from the serial port are sent once the frame is full or the port has been quiet
+//! for a while, received frames are written to the serial port. When the serial output can't
+//! keep up, the bridge asks the peer to pause until its buffer has drained.
+//!
+//! The link is half-duplex and unreliable, frames lost on air are lost, as with the modules it
+//! replicates. Frames are variable length packets (with CRC), the first byte of the payload
+//! being the frame kind.
+
+use crate::lowlevel::registers::*;
+use crate::lowlevel::types::*;
+use crate::{Cc1101, Error, PacketLength, RadioMode};
+use embedded_io::{Read, ReadReady, Write, WriteReady};
+use hal::spi::SpiDevice;
+use heapless::Deque;
+
+const KIND_DATA: u8 = 0x00;
+const KIND_PAUSE: u8 = 0x01;
+const KIND_RESUME: u8 = 0x02;
+
+/// Largest packet payload, so that a packet and its status bytes fit in the RX FIFO.
+const MAX_PAYLOAD: usize = 61;
+/// Largest amount of serial data in a frame.
+pub const MAX_DATA: usize = MAX_PAYLOAD - 1;
+/// Size of the serial output buffer.
+pub const OUTPUT_SIZE: usize = 256;
+
+/// Bridge errors.
+#[derive(Debug)]
+pub enum BridgeError<SpiE, UartE> {
+    /// Radio error.
+    Radio(Error<SpiE>),
+    /// Serial port error.
+    Uart(UartE),
+}
+
+impl<SpiE, UartE> From<Error<SpiE>> for BridgeError<SpiE, UartE> {
+    fn from(e: Error<SpiE>) -> Self {
+        BridgeError::Radio(e)
+    }
+}
+
+/// Link statistics.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct BridgeStats {
+    /// Frames sent.
+    pub sent: u32,
+    /// Frames received.
+    pub received: u32,
+    /// Received frames cut short for lack of room in the output buffer.
+    pub dropped: u32,
+}
+
+/// Serial to radio bridge.
+pub struct SerialBridge<U> {
+    uart: U,
+    gap_us: u32,
+    idle_us: u32,
+    input: [u8; MAX_DATA],
+    input_len: usize,
+    output: Deque<u8, OUTPUT_SIZE>,
+    peer_paused: bool,
+    paused: bool,
+    stats: BridgeStats,
+}
+
+impl<U> SerialBridge<U>
+where
+    U: Read + Write + ReadReady + WriteReady,
+{
+    /// Bridge `uart`, sending partial frames after `gap_us` without input.
+    ///
+    /// A gap of a few characters at the serial baud rate keeps messages written in one go
+    /// in the same frame.
+    pub fn new(uart: U, gap_us: u32) -> Self {
+        SerialBridge {
+            uart,
+            gap_us,
+            idle_us: 0,
+            input: [0; MAX_DATA],
+            input_len: 0,
+            output: Deque::new(),
+            peer_paused: false,
+            paused: false,
+            stats: BridgeStats::default(),
+        }
+    }
+
+    pub fn release(self) -> U {
+        self.uart
+    }
+
+    pub fn stats(&self) -> BridgeStats {
+        self.stats
+    }
+
+    /// Configure the packet format used by the bridge, on top of the modulation settings.
+    ///
+    /// Variable length packets with CRC, bad packets flushed, and IDLE after each packet so
+    /// that a received packet can be told apart from one being received.
+    pub fn configure<SPI, SpiE>(cc1101: &mut Cc1101<SPI>) -> Result<(), Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        cc1101.set_packet_length(PacketLength::Variable(MAX_PAYLOAD as u8))?;
+        cc1101.0.modify_config_burst(Config::PKTCTRL1, |[pktctrl1, pktctrl0]: &mut [u8; 2]| {
+            *pktctrl1 = PKTCTRL1(*pktctrl1).modify().crc_autoflush(1).append_status(1).bits();
+            *pktctrl0 = PKTCTRL0(*pktctrl0).modify().crc_en(1).bits();
+        })?;
+        cc1101.0.modify_register(Config::MCSM1, |r| {
+            MCSM1(r).modify().rxoff_mode(0).txoff_mode(0).bits()
+        })?;
+        Ok(())
+    }
+
+    /// Move data between the serial port and the radio, to be called regularly.
+    ///
+    /// `elapsed_us` is the time since the previous call. The radio is left in RX.
+    pub fn poll<SPI, SpiE>(
+        &mut self,
+        cc1101: &mut Cc1101<SPI>,
+        elapsed_us: u32,
+    ) -> Result<(), BridgeError<SpiE, U::Error>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        // Radio to serial
+        if cc1101.get_marc_state()? == MachineState::IDLE.value() {
+            self.receive(cc1101)?;
+            cc1101.set_radio_mode(RadioMode::Receive)?;
+        }
+        while !self.output.is_empty() && self.uart.write_ready().map_err(BridgeError::Uart)? {
+            let (pending, _) = self.output.as_slices();
+            let n = self.uart.write(pending).map_err(BridgeError::Uart)?;
+            for _ in 0..n {
+                self.output.pop_front();
+            }
+        }
+
+        // Flow control, pause while there's no room for two more frames
+        let free = OUTPUT_SIZE - self.output.len();
+        if !self.paused && free < 2 * MAX_DATA {
+            self.paused = true;
+            self.send(cc1101, KIND_PAUSE, 0)?;
+        } else if self.paused && free > OUTPUT_SIZE - MAX_DATA {
+            self.paused = false;
+            self.send(cc1101, KIND_RESUME, 0)?;
+        }
+
+        // Serial to radio
+        self.idle_us = self.idle_us.saturating_add(elapsed_us);
+        while self.input_len < MAX_DATA && self.uart.read_ready().map_err(BridgeError::Uart)? {
+            let n = self.uart.read(&mut self.input[self.input_len..]).map_err(BridgeError::Uart)?;
+            self.input_len += n;
+            self.idle_us = 0;
+        }
+        let flush = self.input_len == MAX_DATA || self.idle_us >= self.gap_us;
+        if self.input_len > 0 && flush && !self.peer_paused {
+            self.send(cc1101, KIND_DATA, self.input_len)?;
+            self.input_len = 0;
+        }
+        Ok(())
+    }
+
+    /// Read the packet received, if any. The RX FIFO is flushed if it doesn't hold exactly one
+    /// packet, e.g. one cut short by leaving RX.
+    fn receive<SPI, SpiE>(&mut self, cc1101: &mut Cc1101<SPI>) -> Result<(), Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        let rxbytes = RXBYTES(cc1101.0.read_register(Status::RXBYTES)?);
+        if rxbytes.num_rxbytes() == 0 {
+            return Ok(());
+        }
+        let mut len = [0u8];
+        cc1101.0.read_fifo(&mut len)?;
+        let len = len[0] as usize;
+        // Payload and status bytes
+        if len == 0 || len > MAX_PAYLOAD || rxbytes.num_rxbytes() as usize != len + 3 {
+            return cc1101.flush_rx();
+        }
+        let mut packet = [0u8; MAX_PAYLOAD + 2];
+        cc1101.0.read_fifo(&mut packet[..len + 2])?;
+        cc1101.notify_packet(Some([packet[len], packet[len + 1]]))?;
+        self.stats.received += 1;
+        match packet[0] {
+            KIND_DATA => {
+                for &b in &packet[1..len] {
+                    if self.output.push_back(b).is_err() {
+                        self.stats.dropped += 1;
+                        break;
+                    }
+                }
+            }
+            KIND_PAUSE => self.peer_paused = true,
+            KIND_RESUME => self.peer_paused = false,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Send a frame, with the first `len` bytes of input data.
+    fn send<SPI, SpiE>(
+        &mut self,
+        cc1101: &mut Cc1101<SPI>,
+        kind: u8,
+        len: usize,
+    ) -> Result<(), Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        cc1101.set_radio_mode(RadioMode::Idle)?;
+        // A packet may have come in since `poll` checked, only a partial one is dropped
+        self.receive(cc1101)?;
+        cc1101.transmit_parts(&[&[len as u8 + 1, kind], &self.input[..len]])?;
+        cc1101.set_radio_mode(RadioMode::Receive)?;
+        self.stats.sent += 1;
+        Ok(())
+    }
+}
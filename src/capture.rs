@@ -0,0 +1,95 @@
+//! Raw demodulated bitstream capture, e.g. to reverse engineer an unknown protocol.
+//!
+//! Packet handling is disabled (no sync word, infinite length, no CRC or whitening) so the
+//! demodulator output goes to the RX FIFO as is, byte aligned on nothing in particular. The
+//! bytes are handed to a sink along with the time they were read, to be forwarded to a host
+//! for analysis. Modulation, frequency, data rate and bandwidth are left as configured.
+
+use crate::lowlevel::registers::*;
+use crate::lowlevel::types::*;
+use crate::{Cc1101, Error, RadioMode};
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+/// Capture statistics.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct CaptureStats {
+    /// Bytes handed to the sink.
+    pub bytes: u32,
+    /// RX FIFO overflows, each one is a gap in the captured stream.
+    pub overflows: u32,
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Capture the raw demodulated bitstream, until `sink` returns `false`.
+    ///
+    /// The RX FIFO is polled every `poll_us`, which must be short enough for the FIFO not to
+    /// fill up in between (64 bytes, i.e. 512 bits at the configured data rate). `sink` gets
+    /// the time of the read (in microseconds since the start of the capture, counting poll
+    /// delays only) and the bytes read, MSB first. With `carrier_sense` only data received
+    /// while the RSSI is above the carrier sense threshold is captured.
+    ///
+    /// Packet handling registers are restored, and the radio left in IDLE, afterwards.
+    pub fn capture<D, F>(
+        &mut self,
+        delay: &mut D,
+        poll_us: u32,
+        carrier_sense: bool,
+        mut sink: F,
+    ) -> Result<CaptureStats, Error<SpiE>>
+    where
+        D: DelayNs,
+        F: FnMut(u32, &[u8]) -> bool,
+    {
+        let sync_mode = match carrier_sense {
+            true => SyncCheck::CHECK_0_0_CS,
+            false => SyncCheck::DISABLED,
+        };
+        let mdmcfg2 = self.0.read_register(Config::MDMCFG2)?;
+        let config = [
+            (Config::PKTCTRL1, PKTCTRL1::default().pqt(0).append_status(0).adr_chk(0).bits()),
+            (
+                Config::PKTCTRL0,
+                PKTCTRL0::default()
+                    .white_data(0)
+                    .crc_en(0)
+                    .length_config(LengthConfig::INFINITE.value())
+                    .bits(),
+            ),
+            (Config::MDMCFG2, MDMCFG2(mdmcfg2).modify().sync_mode(sync_mode.value()).bits()),
+        ];
+        self.with_config(&config, |cc1101| {
+            let mut stats = CaptureStats::default();
+            let mut now_us = 0u32;
+            let mut buf = [0u8; crate::lowlevel::FIFO_SIZE];
+            cc1101.set_radio_mode(RadioMode::Receive)?;
+            loop {
+                delay.delay_us(poll_us);
+                now_us = now_us.wrapping_add(poll_us);
+                let rxbytes = cc1101.rx_bytes_available()?;
+                if rxbytes.error {
+                    stats.overflows += 1;
+                    cc1101.abort_receive()?;
+                    cc1101.set_radio_mode(RadioMode::Receive)?;
+                    continue;
+                }
+                // Leave a byte in the FIFO while receiving (errata: reading the last byte
+                // while it's being written corrupts it)
+                let n = (rxbytes.bytes as usize).saturating_sub(1);
+                if n == 0 {
+                    continue;
+                }
+                cc1101.0.read_fifo(&mut buf[..n])?;
+                stats.bytes += n as u32;
+                if !sink(now_us, &buf[..n]) {
+                    break;
+                }
+            }
+            cc1101.abort_receive()?;
+            Ok(stats)
+        })
+    }
+}
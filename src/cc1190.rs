@@ -0,0 +1,149 @@
+//! Support for CC1190-style range extenders (external PA/LNA front ends).
+//!
+//! The front end is controlled by three lines: PA_EN, LNA_EN and HGM (high gain mode). They
+//! can either be driven from GPIOs, using [`Cc1190`], or by the CC1101 itself through the GDO
+//! pins, using [`route_to_gdo`].
+
+use hal::digital::OutputPin;
+use hal::spi::SpiDevice;
+
+use crate::lowlevel::registers::*;
+use crate::lowlevel::types::*;
+use crate::rssi::{rssi_to_dbm_with_offset, RSSI_OFFSET};
+use crate::{Cc1101, Error, RadioMode};
+
+/// Typical LNA gain of the CC1190 in high gain mode (in dB).
+pub const HGM_RX_GAIN_DB: i16 = 11;
+
+/// PATABLE value giving ~0 dBm out of the CC1101 at 915 MHz, the maximum drive level that
+/// should be fed into the CC1190 PA.
+pub const MAX_PA_INPUT: u8 = 0x8E;
+
+/// Let the CC1101 drive the front end: PA_EN on GDO0 (inverted PA_PD) and LNA_EN on GDO2
+/// (inverted LNA_PD). HGM has to be tied high, or driven separately.
+pub fn route_to_gdo<SPI, SpiE>(cc1101: &mut Cc1101<SPI>) -> Result<(), Error<SpiE>>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    cc1101.0.write_register(
+        Config::IOCFG0,
+        IOCFG0::default().gdo0_inv(1).gdo0_cfg(GdoCfg::PA_PD.value()).bits(),
+    )?;
+    cc1101.0.write_register(
+        Config::IOCFG2,
+        IOCFG2::default().gdo2_inv(1).gdo2_cfg(GdoCfg::LNA_PD.value()).bits(),
+    )?;
+    Ok(())
+}
+
+/// CC1190 front end driven from GPIOs.
+pub struct Cc1190<PA, LNA, HGM> {
+    pa_en: PA,
+    lna_en: LNA,
+    hgm: HGM,
+    high_gain: bool,
+    rx_gain_db: i16,
+}
+
+impl<PA, LNA, HGM> Cc1190<PA, LNA, HGM>
+where
+    PA: OutputPin,
+    LNA: OutputPin<Error = PA::Error>,
+    HGM: OutputPin<Error = PA::Error>,
+{
+    /// Make a new front end, in high gain mode.
+    ///
+    /// Pins are not touched until the first mode change.
+    pub fn new(pa_en: PA, lna_en: LNA, hgm: HGM) -> Self {
+        Cc1190 {
+            pa_en,
+            lna_en,
+            hgm,
+            high_gain: true,
+            rx_gain_db: HGM_RX_GAIN_DB,
+        }
+    }
+
+    /// Release the pins.
+    pub fn release(self) -> (PA, LNA, HGM) {
+        (self.pa_en, self.lna_en, self.hgm)
+    }
+
+    /// Select high gain mode (HGM high) or low gain mode, applied on the next mode change.
+    pub fn set_high_gain(&mut self, high_gain: bool) {
+        self.high_gain = high_gain;
+    }
+
+    /// Override the LNA gain used to correct RSSI readings (in dB).
+    ///
+    /// Defaults to [`HGM_RX_GAIN_DB`], set this to the measured gain when running in low gain mode.
+    pub fn set_rx_gain_db(&mut self, gain: i16) {
+        self.rx_gain_db = gain;
+    }
+
+    /// RSSI offset to use in place of the CC1101 one, accounting for the LNA gain.
+    pub fn rssi_offset(&self) -> i16 {
+        RSSI_OFFSET + self.rx_gain_db
+    }
+
+    /// Received Signal Strength Indicator at the antenna, in front of the LNA.
+    pub fn get_rssi_dbm<SPI, SpiE>(&self, cc1101: &mut Cc1101<SPI>) -> Result<i16, Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        Ok(rssi_to_dbm_with_offset(cc1101.0.read_register(Status::RSSI)?, self.rssi_offset()))
+    }
+
+    /// Limit the CC1101 output to [`MAX_PA_INPUT`], so the CC1190 PA is not overdriven.
+    pub fn configure_pa<SPI, SpiE>(&self, cc1101: &mut Cc1101<SPI>) -> Result<(), Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        cc1101.set_pa_ramp(&[MAX_PA_INPUT])
+    }
+
+    /// Set radio in Receive/Transmit/Idle/Calibrate mode, switching the front end along with it.
+    ///
+    /// The PA/LNA is enabled before the radio enters RX/TX, and only disabled once the radio
+    /// has left it, so the front end is never off while the radio is active.
+    pub fn set_radio_mode<SPI, SpiE>(
+        &mut self,
+        cc1101: &mut Cc1101<SPI>,
+        radio_mode: RadioMode,
+    ) -> Result<(), Error<SpiE, PA::Error>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        match radio_mode {
+            RadioMode::Receive => {
+                // Leave TX before switching the PA off
+                if cc1101.current_mode().map_err(Error::with_gpio)? == Some(RadioMode::Transmit) {
+                    cc1101.set_radio_mode(RadioMode::Idle).map_err(Error::with_gpio)?;
+                }
+                self.pa_en.set_low().map_err(Error::Gpio)?;
+                self.apply_gain().map_err(Error::Gpio)?;
+                self.lna_en.set_high().map_err(Error::Gpio)?;
+            }
+            RadioMode::Transmit => {
+                self.lna_en.set_low().map_err(Error::Gpio)?;
+                self.apply_gain().map_err(Error::Gpio)?;
+                self.pa_en.set_high().map_err(Error::Gpio)?;
+            }
+            RadioMode::Idle | RadioMode::Calibrate => {
+                cc1101.set_radio_mode(radio_mode).map_err(Error::with_gpio)?;
+                return self.power_down().map_err(Error::Gpio);
+            }
+        }
+        cc1101.set_radio_mode(radio_mode).map_err(Error::with_gpio)
+    }
+
+    /// Put the front end in power down, both PA and LNA disabled.
+    pub fn power_down(&mut self) -> Result<(), PA::Error> {
+        self.pa_en.set_low()?;
+        self.lna_en.set_low()
+    }
+
+    fn apply_gain(&mut self) -> Result<(), PA::Error> {
+        self.hgm.set_state(self.high_gain.into())
+    }
+}
@@ -0,0 +1,233 @@
+//! Channel plans checked against the limits of a frequency band.
+//!
+//! A [`ChannelPlan`] is built from the base frequency, spacing and number of channels, as the
+//! radio quantizes them, and is only constructed if every channel is within its [`Band`]. In a
+//! `const` the check happens at compile time:
+//!
+//! ```
+//! use cc1101::channels::{Band, ChannelPlan};
+//!
+//! const PLAN: ChannelPlan = ChannelPlan::new(Band::SRD_868, 868_000_000, 200_000, 10);
+//! ```
+//!
+//! [`Cc1101::set_channel_plan`] programs the plan, [`Cc1101::select_channel`] then only takes
+//! [`Channel`]s obtained from a plan.
+//!
+//! Calibrating the synthesizer takes ~700 µs on every channel change. With autocalibration
+//! disabled, [`Cc1101::calibrate_channels`] calibrates each channel once up front, and
+//! [`Cc1101::select_channel_calibrated`] restores the results along with the channel, so
+//! switching channels only takes the synthesizer settling time.
+
+use crate::lowlevel::convert::*;
+use crate::lowlevel::registers::*;
+use crate::lowlevel::FXOSC;
+use crate::{Cc1101, Error};
+use hal::spi::SpiDevice;
+
+/// A frequency band, channels must be within `start_hz..=end_hz`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Band {
+    pub start_hz: u64,
+    pub end_hz: u64,
+}
+
+impl Band {
+    /// 433.05 to 434.79 MHz ISM band (ITU region 1).
+    pub const ISM_433: Band = Band::new(433_050_000, 434_790_000);
+    /// 863 to 870 MHz SRD band (Europe, EN 300 220).
+    pub const SRD_868: Band = Band::new(863_000_000, 870_000_000);
+    /// 902 to 928 MHz ISM band (ITU region 2).
+    pub const ISM_915: Band = Band::new(902_000_000, 928_000_000);
+
+    pub const fn new(start_hz: u64, end_hz: u64) -> Self {
+        Band {
+            start_hz,
+            end_hz,
+        }
+    }
+
+    pub const fn contains(&self, hz: u64) -> bool {
+        self.start_hz <= hz && hz <= self.end_hz
+    }
+}
+
+/// Frequency ranges the synthesizer can tune to.
+pub const SUPPORTED_BANDS: [Band; 3] = [
+    Band::new(300_000_000, 348_000_000),
+    Band::new(387_000_000, 464_000_000),
+    Band::new(779_000_000, 928_000_000),
+];
+
+/// Whether the radio can tune to `hz`, see `SUPPORTED_BANDS`.
+pub const fn is_supported_frequency(hz: u64) -> bool {
+    SUPPORTED_BANDS[0].contains(hz)
+        || SUPPORTED_BANDS[1].contains(hz)
+        || SUPPORTED_BANDS[2].contains(hz)
+}
+
+/// Evenly spaced channels within a band.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ChannelPlan {
+    band: Band,
+    freq: (u8, u8, u8),
+    chanspc: (u8, u8),
+    count: u16,
+}
+
+impl ChannelPlan {
+    /// Build a plan of `count` channels, the first at `base_hz`, `spacing_hz` apart.
+    ///
+    /// Panics if a channel is outside of `band`, or if there are more than 256 channels. At
+    /// compile time when used in a `const`.
+    pub const fn new(band: Band, base_hz: u64, spacing_hz: u64, count: u16) -> Self {
+        match Self::try_new(band, base_hz, spacing_hz, count) {
+            Some(plan) => plan,
+            None => panic!("channel plan outside of its band"),
+        }
+    }
+
+    /// Same as `new`, returning `None` instead of panicking.
+    pub const fn try_new(band: Band, base_hz: u64, spacing_hz: u64, count: u16) -> Option<Self> {
+        let plan = ChannelPlan {
+            band,
+            freq: from_frequency(base_hz),
+            chanspc: from_chanspc(spacing_hz),
+            count,
+        };
+        if count == 0 || count > 256 {
+            return None;
+        }
+        if !band.contains(plan.frequency(0)) || !band.contains(plan.frequency(count - 1)) {
+            return None;
+        }
+        Some(plan)
+    }
+
+    pub const fn band(&self) -> Band {
+        self.band
+    }
+
+    /// Number of channels.
+    pub const fn count(&self) -> u16 {
+        self.count
+    }
+
+    /// Actual spacing between channels (in Hertz).
+    pub const fn spacing(&self) -> u64 {
+        to_chanspc(self.chanspc.0, self.chanspc.1)
+    }
+
+    /// Actual carrier frequency of channel `n` (in Hertz).
+    pub const fn frequency(&self, n: u16) -> u64 {
+        let (freq0, freq1, freq2) = self.freq;
+        let freq = (freq2 as u64) << 16 | (freq1 as u64) << 8 | freq0 as u64;
+        let (mantissa, exponent) = self.chanspc;
+        // f_carrier = f_osc / 2^16 * (FREQ + CHAN * (256 + CHANSPC_M) * 2^(CHANSPC_E - 2))
+        let offset = (n as u64 * (256 + mantissa as u64)) << exponent;
+        (((freq << 2) + offset) * FXOSC) >> 18
+    }
+
+    /// Channel `n` of the plan, `None` if there's no such channel.
+    pub const fn channel(&self, n: u16) -> Option<Channel> {
+        if n < self.count {
+            Some(Channel(n as u8))
+        } else {
+            None
+        }
+    }
+}
+
+/// A channel of a `ChannelPlan`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Channel(u8);
+
+impl Channel {
+    pub const fn number(&self) -> u8 {
+        self.0
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Program the base frequency and channel spacing of `plan`, and select its first channel.
+    ///
+    /// Both are corrected for the crystal error set with `set_crystal_ppm`.
+    pub fn set_channel_plan(&mut self, plan: &ChannelPlan) -> Result<(), Error<SpiE>> {
+        let (freq0, freq1, freq2) = match self.crystal_ppm() {
+            0 => plan.freq,
+            _ => from_frequency(self.xtal_corrected(plan.frequency(0))),
+        };
+        self.0.write_config_burst(Config::FREQ2, &[freq2, freq1, freq0])?;
+        let (mantissa, exponent) = match self.crystal_ppm() {
+            0 => plan.chanspc,
+            _ => from_chanspc(self.xtal_corrected(plan.spacing())),
+        };
+        // MDMCFG1, MDMCFG0
+        self.0.modify_config_burst(Config::MDMCFG1, |[mdmcfg1, mdmcfg0]: &mut [u8; 2]| {
+            *mdmcfg1 = MDMCFG1(*mdmcfg1).modify().chanspc_e(exponent).bits();
+            *mdmcfg0 = mantissa;
+        })?;
+        self.set_channel(0)
+    }
+
+    /// Tune to `channel`, of the plan programmed with `set_channel_plan`.
+    pub fn select_channel(&mut self, channel: Channel) -> Result<(), Error<SpiE>> {
+        self.set_channel(channel.0)
+    }
+
+    /// Calibrate channels 0 to `table.len() - 1` of the programmed plan, storing the FSCAL3,
+    /// FSCAL2 and FSCAL1 of channel `n` in `table[n]`.
+    ///
+    /// Leaves the radio in IDLE, tuned to the last channel calibrated. Calibrate again when
+    /// the temperature changes significantly, see `recal`.
+    pub fn calibrate_channels(&mut self, table: &mut [[u8; 3]]) -> Result<(), Error<SpiE>> {
+        for (n, fscal) in table.iter_mut().enumerate() {
+            self.set_channel(n as u8)?;
+            *fscal = self.recalibrate()?;
+        }
+        Ok(())
+    }
+
+    /// Tune to `channel` with the calibration from `table`, filled by `calibrate_channels`.
+    ///
+    /// Call in IDLE, then enter RX or TX with autocalibration disabled. Fails with
+    /// `InvalidConfig` if `table` doesn't cover `channel`.
+    pub fn select_channel_calibrated(
+        &mut self,
+        channel: Channel,
+        table: &[[u8; 3]],
+    ) -> Result<(), Error<SpiE>> {
+        let fscal = table.get(channel.0 as usize).ok_or(Error::InvalidConfig)?;
+        self.set_channel(channel.0)?;
+        self.restore_fscal(fscal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channels::*;
+
+    #[test]
+    fn test_channel_plan() {
+        const PLAN: ChannelPlan = ChannelPlan::new(Band::SRD_868, 868_000_000, 200_000, 10);
+        assert_eq!(PLAN.spacing(), 199_813);
+        assert_eq!(PLAN.frequency(0), 867_999_984);
+        assert_eq!(PLAN.frequency(9), 869_798_309);
+        assert!(PLAN.channel(9).is_some());
+        assert!(PLAN.channel(10).is_none());
+
+        assert!(ChannelPlan::try_new(Band::SRD_868, 868_000_000, 200_000, 12).is_none());
+        assert!(ChannelPlan::try_new(Band::ISM_433, 433_000_000, 25_000, 1).is_none());
+        assert!(ChannelPlan::try_new(Band::ISM_915, 902_200_000, 400_000, 64).is_some());
+    }
+
+    #[test]
+    fn test_is_supported_frequency() {
+        assert!(is_supported_frequency(433_920_000));
+        assert!(is_supported_frequency(928_000_000));
+        assert!(!is_supported_frequency(370_000_000));
+        assert!(!is_supported_frequency(2_400_000_000));
+    }
+}
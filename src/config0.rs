@@ -1,7 +1,11 @@
-use crate::{configs::config_1, Cc1101, Error};
+use crate::configs::{config_1, config_1_block};
+use crate::{Cc1101, Error, ReceivedPacket, STATE_POLL_BUDGET};
 use hal::spi::SpiDevice;
 
-use crate::lowlevel::{registers::*, types::*};
+use crate::lowlevel::registers::*;
+use crate::lowlevel::types::*;
+use crate::lowlevel::FIFO_SIZE;
+use crate::trace::TraceEvent;
 
 impl<SPI, SpiE> Cc1101<SPI>
 where
@@ -10,47 +14,244 @@ where
     /// If gdo2 pin is high, that means crc was successful
     /// and there's a valid packet we can read.
     /// Then just put that packet in the payload
+    ///
+    /// GDO2 has to be configured as `GdoCfg::CRC_OK`, see `set_gdo_config`.
+    ///
+    /// Reads exactly one packet, fixed or variable length (length byte first), and returns
+    /// its payload length and reception details. Packets longer than `payload` are dropped
+    /// and fail with `PacketLengthMismatch`, infinite length mode with `InvalidConfig`.
+    ///
+    /// RXBYTES is checked on every call: an RX FIFO overflow is recovered from (see
+    /// `Error::RxOverflow`) instead of waiting forever for a packet that won't come.
     pub fn receive<P: hal::digital::InputPin>(
         &mut self,
         gdo2: &mut P,
-    ) -> nb::Result<[u8; 32], Error<SpiE>> {
-        if gdo2.is_high().unwrap() {
-            let mut payload = [0u8; 32];
-            self.0.read_fifo(&mut payload).map_err(|e| nb::Error::Other(e.into()))?;
-            nb::Result::Ok(payload)
+        payload: &mut [u8],
+    ) -> nb::Result<ReceivedPacket, Error<SpiE, P::Error>> {
+        self.check_rx_overflow().map_err(|e| nb::Error::Other(Error::with_gpio(e)))?;
+        if gdo2.is_high().map_err(|e| nb::Error::Other(Error::Gpio(e)))? {
+            match self.read_packet(payload) {
+                Ok(Some(packet)) => Ok(packet),
+                Ok(None) => Err(nb::Error::WouldBlock),
+                Err(e) => Err(nb::Error::Other(Error::with_gpio(e))),
+            }
         } else {
             nb::Result::Err(nb::Error::WouldBlock)
         }
     }
 
+    fn check_rx_overflow(&mut self) -> Result<(), Error<SpiE>> {
+        match self.rx_bytes_available()?.error {
+            true => self.recover_rx_overflow(),
+            false => Ok(()),
+        }
+    }
+
+    /// `None` if the RX FIFO turned out to be empty.
+    fn read_packet(&mut self, payload: &mut [u8]) -> Result<Option<ReceivedPacket>, Error<SpiE>> {
+        // PKTLEN, PKTCTRL1, PKTCTRL0
+        let mut regs = [0u8; 3];
+        self.0.read_config_burst(Config::PKTLEN, &mut regs)?;
+        let len = match PKTCTRL0(regs[2]).length_config() {
+            l if l == LengthConfig::FIXED.value() => regs[0] as usize,
+            l if l == LengthConfig::VARIABLE.value() => {
+                return self.receive_filtered(payload, |_| true);
+            }
+            _ => return Err(Error::InvalidConfig),
+        };
+        let pktctrl1 = PKTCTRL1(regs[1]);
+        let status_len = 2 * pktctrl1.append_status() as usize;
+        if len > payload.len() {
+            self.discard(len + status_len)?;
+            self.0.trace_event(TraceEvent::PacketLengthMismatch);
+            return Err(Error::PacketLengthMismatch);
+        }
+        self.0.read_fifo(&mut payload[..len])?;
+        let mut status = [0u8; 2];
+        if status_len > 0 {
+            self.0.read_fifo(&mut status)?;
+        }
+        let info = self.notify_packet((status_len > 0).then_some(status))?;
+        let address = (pktctrl1.adr_chk() != 0 && len > 0).then(|| payload[0]);
+        Ok(Some(ReceivedPacket::new(len, info, address)))
+    }
+
+    /// Same as `receive`, for fixed length packets of `N` bytes.
+    ///
+    /// Once a packet is available, checks that the radio is configured for fixed length
+    /// packets of `N` bytes, failing with `PacketLengthMismatch` (leaving the FIFO untouched)
+    /// otherwise.
+    pub fn receive_fixed<const N: usize, P: hal::digital::InputPin>(
+        &mut self,
+        gdo2: &mut P,
+    ) -> nb::Result<([u8; N], ReceivedPacket), Error<SpiE, P::Error>> {
+        self.check_rx_overflow().map_err(|e| nb::Error::Other(Error::with_gpio(e)))?;
+        if !gdo2.is_high().map_err(|e| nb::Error::Other(Error::Gpio(e)))? {
+            return Err(nb::Error::WouldBlock);
+        }
+        // PKTLEN, PKTCTRL1, PKTCTRL0
+        let mut regs = [0u8; 3];
+        self.0
+            .read_config_burst(Config::PKTLEN, &mut regs)
+            .map_err(|e| nb::Error::Other(e.into()))?;
+        let fixed = PKTCTRL0(regs[2]).length_config() == LengthConfig::FIXED.value();
+        if !fixed || regs[0] as usize != N || N > FIFO_SIZE {
+            self.0.trace_event(TraceEvent::PacketLengthMismatch);
+            return Err(nb::Error::Other(Error::PacketLengthMismatch));
+        }
+        let mut payload = [0u8; N];
+        self.0.read_fifo(&mut payload).map_err(|e| nb::Error::Other(e.into()))?;
+        let status = self.read_appended_status().map_err(|e| nb::Error::Other(e.into()))?;
+        let info = self.notify_packet(status).map_err(|e| nb::Error::Other(e.into()))?;
+        let address = (PKTCTRL1(regs[1]).adr_chk() != 0 && N > 0).then(|| payload[0]);
+        Ok((payload, ReceivedPacket::new(N, info, address)))
+    }
+
     /// - write payload to FIFO
     /// - puts radio in transmit mode
-    /// - waits for radio to go back to Idle
-    /// - flushes the TX buffer
+    /// - waits for radio to leave TX (per `set_txoff_mode`)
+    /// - flushes the TX buffer, if back in Idle
+    ///
+    /// A TX FIFO underflow flushes the TX FIFO and fails with `TxUnderflow`, the radio left
+    /// in IDLE.
     pub fn transmit(&mut self, payload: &[u8; 32]) -> Result<(), Error<SpiE>> {
-        // We go to iddle right before only if CCA isn't on mode 0
+        // We go to iddle right before only if CCA isn't `CcaMode::Always`
         // self.to_idle()?;
-        self.0.write_fifo(payload)?;
+        self.write_tx_fifo(payload)?;
         self.set_radio_mode(crate::RadioMode::Transmit)?;
-        self.await_machine_state(MachineState::IDLE)?;
-        self.flush_tx()?;
-        Ok(())
+        self.finish_transmit()
+    }
+    /// Same as `transmit`, for a packet made of several slices, e.g. header, payload and
+    /// trailer.
+    ///
+    /// The slices are written to the TX FIFO in one transaction (see
+    /// `lowlevel::Cc1101::write_fifo_parts`), nothing is written if they don't fit.
+    pub fn transmit_parts(&mut self, parts: &[&[u8]]) -> Result<(), Error<SpiE>> {
+        let len: usize = parts.iter().map(|p| p.len()).sum();
+        if len > self.tx_fifo_free()? as usize {
+            self.0.trace_event(TraceEvent::TxOverflow);
+            return Err(Error::TxOverflow);
+        }
+        self.0.write_fifo_parts(parts)?;
+        self.set_radio_mode(crate::RadioMode::Transmit)?;
+        self.finish_transmit()
+    }
+
+    /// Same as `transmit`, for packets larger than the TX FIFO.
+    ///
+    /// The first FIFO-full of `data` is written before entering TX, the rest as the FIFO
+    /// drains, polling TXBYTES. `data` is the packet as written to the FIFO (length byte
+    /// first in variable length mode). Packets longer than 255 bytes are sent as is: the radio
+    /// is switched to infinite length mode with PKTLEN set to the length modulo 256, then to
+    /// fixed length mode once fewer than 256 bytes are left to send (see `crate::longpacket`),
+    /// and PKTLEN and PKTCTRL0 are restored afterwards.
+    ///
+    /// If the FIFO isn't refilled in time (e.g. a slow SPI bus at a high data rate), the
+    /// transmission is aborted and fails with `TxUnderflow`. Fails with `Timeout` if the radio
+    /// left TX with data left to write, after `STATE_POLL_BUDGET` reads outside TX.
+    pub fn transmit_large(&mut self, data: &[u8]) -> Result<(), Error<SpiE>> {
+        if data.len() < 256 {
+            return self.transmit_refilled(data, true);
+        }
+        // PKTLEN, PKTCTRL1, PKTCTRL0
+        let mut regs = [0u8; 3];
+        self.0.read_config_burst(Config::PKTLEN, &mut regs)?;
+        let infinite =
+            PKTCTRL0(regs[2]).modify().length_config(LengthConfig::INFINITE.value()).bits();
+        self.0.write_register(Config::PKTLEN, data.len() as u8)?;
+        self.0.write_register(Config::PKTCTRL0, infinite)?;
+        let result = self.transmit_refilled(data, false);
+        self.0.write_register(Config::PKTLEN, regs[0])?;
+        self.0.write_register(Config::PKTCTRL0, regs[2])?;
+        result
     }
+
+    /// Transmit `data`, refilling the TX FIFO as it drains, switching from infinite to fixed
+    /// length mode near the end unless already `fixed`.
+    fn transmit_refilled(&mut self, data: &[u8], mut fixed: bool) -> Result<(), Error<SpiE>> {
+        let mut sent = data.len().min(self.tx_fifo_free()? as usize);
+        self.write_tx_fifo(&data[..sent])?;
+        self.set_radio_mode(crate::RadioMode::Transmit)?;
+        let mut stalled = 0;
+        while sent < data.len() {
+            let fifo = self.tx_bytes_pending()?;
+            if fifo.error {
+                return self.recover_tx_underflow();
+            }
+            if !fixed && fixed_length_due(data.len() - sent, fifo.bytes as usize) {
+                self.0.modify_register(Config::PKTCTRL0, |r| {
+                    PKTCTRL0(r).modify().length_config(LengthConfig::FIXED.value()).bits()
+                })?;
+                fixed = true;
+            }
+            let n = FIFO_SIZE.saturating_sub(fifo.bytes as usize).min(data.len() - sent);
+            if n > 0 {
+                self.0.write_fifo(&data[sent..sent + n])?;
+                sent += n;
+                continue;
+            }
+            // FIFO full, only give up if it isn't draining because the radio left TX
+            match self.get_marc_state()? {
+                s if s == MachineState::TXFIFO_UNDERFLOW.value() => {
+                    return self.recover_tx_underflow()
+                }
+                s if s != MachineState::TX.value() => {
+                    stalled += 1;
+                    if stalled == STATE_POLL_BUDGET {
+                        return Err(Error::Timeout);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.finish_transmit()
+    }
+
     /// We don't wait until radio is in TX.
     /// We just do the required steps for transmission to start.
     ///
     /// - write payload to FIFO
     /// - sends command strobe for transmit mode
     pub fn transmit_start(&mut self, payload: &[u8; 32]) -> Result<(), Error<SpiE>> {
-        self.0.write_fifo(payload)?;
+        self.write_tx_fifo(payload)?;
         self.send_radio_mode_strobe(crate::RadioMode::Transmit)?;
         Ok(())
     }
-    /// - waits for radio to go back to Iddle
-    /// - flushes the TX buffer
+    /// Transmit, awaiting the end of the packet on a GDO pin instead of polling MARCSTATE.
+    ///
+    /// The GDO pin has to be configured as `GdoCfg::SYNC_WORD`, which asserts when the sync
+    /// word has been sent and de-asserts at the end of the packet.
+    ///
+    /// - write payload to FIFO
+    /// - sends command strobe for transmit mode
+    /// - waits for the GDO pin to go high, then low
+    ///
+    /// The TX FIFO is left as is, it has been drained by the transmission.
+    #[cfg(feature = "async")]
+    pub async fn transmit_async<P: embedded_hal_async::digital::Wait>(
+        &mut self,
+        payload: &[u8],
+        gdo: &mut P,
+    ) -> Result<(), Error<SpiE, P::Error>> {
+        self.write_tx_fifo(payload).map_err(Error::with_gpio)?;
+        self.send_radio_mode_strobe(crate::RadioMode::Transmit).map_err(Error::with_gpio)?;
+        gdo.wait_for_high().await.map_err(Error::Gpio)?;
+        gdo.wait_for_low().await.map_err(Error::Gpio)?;
+        self.notify_transmit_done();
+        Ok(())
+    }
+
+    /// - waits for radio to leave TX (per `set_txoff_mode`)
+    /// - flushes the TX buffer, if back in Iddle
+    ///
+    /// Same as `transmit` on a TX FIFO underflow.
     pub fn transmit_poll(&mut self) -> nb::Result<(), Error<SpiE>> {
-        if self.is_state_machine(MachineState::IDLE)? {
-            self.flush_tx()?;
+        let txoff = self.txoff_state()?;
+        if self.is_transmit_done(txoff)? {
+            self.notify_transmit_done();
+            if txoff == Some(MachineState::IDLE) {
+                self.flush_tx()?;
+            }
             Ok(())
         } else {
             nb::Result::Err(nb::Error::WouldBlock)
@@ -93,12 +294,47 @@ where
     //     Ok(false)
     // }
 
-    pub fn configure(&mut self)-> Result<(), SpiE> {
-        config_1(self);
+    pub fn configure(&mut self) -> Result<(), SpiE> {
+        config_1(self)?;
         self.write_patable()?;
         Ok(())
     }
+    /// Same as `configure`, then reads the registers back, failing with `VerifyFailed` on the
+    /// first one that doesn't hold the value written, e.g. because of SPI wiring problems.
+    pub fn configure_verified(&mut self) -> Result<(), Error<SpiE>> {
+        self.configure()?;
+        let block = config_1_block();
+        let registers = Config::ALL.map(|reg| (reg, block[reg.addr() as usize]));
+        if let Some((reg, got, expected)) = self.diff_config(&registers)?.next() {
+            return Err(Error::VerifyFailed {
+                reg,
+                expected,
+                got,
+            });
+        }
+        Ok(())
+    }
     pub fn write_patable(&mut self) -> Result<(), SpiE> {
         self.0.write_patable(&[0x03, 0x0E, 0x1E, 0x27, 0x8E, 0xCD, 0xC7, 0xC0])
     }
 }
+
+/// Whether to switch from infinite to fixed length mode, with `unwritten` bytes of the packet
+/// left to write and `in_fifo` in the TX FIFO: the radio counts the bytes it sends modulo 256,
+/// and ends the packet at PKTLEN the next time around.
+fn fixed_length_due(unwritten: usize, in_fifo: usize) -> bool {
+    unwritten + in_fifo < 256
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config0::*;
+
+    #[test]
+    fn test_fixed_length_due() {
+        assert!(!fixed_length_due(300, 64));
+        assert!(!fixed_length_due(192, 64));
+        assert!(fixed_length_due(191, 64));
+        assert!(fixed_length_due(0, 10));
+    }
+}
@@ -4,11 +4,9 @@
 // use cc1101::{GdoCfg, PacketLength, FREND0, IOCFG2, PKTCTRL0};
 use embedded_hal::spi::SpiDevice;
 
-
 use crate::lowlevel::registers::*;
 use crate::lowlevel::types::*;
-use crate::PacketLength;
-use crate::{Cc1101};
+use crate::{AutoCalibration, Cc1101};
 // We're gonna look at the registers so we can set our own good settings,
 // for variable packet length, no address check, default syncword,
 // base frequency 902, channel 0.
@@ -122,6 +120,47 @@ use crate::{Cc1101};
 //         .unwrap();
 // }
 
+/// Registers `config_1` changes from their reset value.
+pub fn config_1_registers() -> [(Config, u8); 18] {
+    [
+        // Set carrier base frequency 902.5 MHz
+        (Config::FREQ2, 0x21),
+        (Config::FREQ1, 0x6D),
+        (Config::FREQ0, 0x0A),
+        // Set power level to max
+        (Config::FREND0, FREND0::default().pa_power(7).bits()),
+        // Set channel
+        (Config::CHANNR, 18),
+        // Set IF 316.4kHz
+        (Config::FSCTRL1, FSCTRL1::default().freq_if(12).bits()),
+        // Set filter bandwidth to 562.5kHz, Data rate 250kBaud
+        (Config::MDMCFG4, MDMCFG4::default().chanbw_m(2).chanbw_e(0).drate_e(13).bits()),
+        (Config::MDMCFG3, MDMCFG3::default().drate_m(48).bits()),
+        // Set channel spacing to 421kHz (max)
+        (Config::MDMCFG1, MDMCFG1::default().chanspc_e(3).fec_en(1).num_preamble(7).bits()),
+        (Config::MDMCFG0, MDMCFG0::default().chanspc_m(255).bits()),
+        // Set modulation to GFSK
+        (Config::MDMCFG2, MDMCFG2::default().mod_format(1).bits()),
+        // Set deviation to 132kHz
+        (Config::DEVIATN, DEVIATN::default().deviation_m(2).deviation_e(6).bits()),
+        // Fixed packet length of 32 bytes
+        (
+            Config::PKTCTRL0,
+            PKTCTRL0::default()
+                .crc_en(1)
+                .white_data(1)
+                .length_config(LengthConfig::FIXED.value())
+                .bits(),
+        ),
+        (Config::PKTLEN, 32),
+        // Keep radio in rx mode even after packet received, make CCA always on
+        (Config::MCSM1, MCSM1::default().rxoff_mode(3).cca_mode(0).bits()),
+        (Config::PKTCTRL1, PKTCTRL1::default().pqt(4).crc_autoflush(1).append_status(0).bits()),
+        (Config::MCSM0, MCSM0::default().fs_autocal(AutoCalibration::FromIdle.into()).bits()),
+        (Config::IOCFG2, IOCFG2::default().gdo2_cfg(GdoCfg::CRC_OK.value()).bits()),
+    ]
+}
+
 /// Same as config_0, but:
 /// - Base Frequency 902.5Mhz (0x216D0A)
 /// - Filter Bandwidth 562.5kHz (m=2,e=0)
@@ -130,104 +169,32 @@ use crate::{Cc1101};
 /// - Modulation GFSK (001)
 /// - Deviation 132kHz (m=2,e=6)
 /// - Channel spacing 421kHz (Max) (m=255,e=3)
-pub fn config_1<T: SpiDevice>(cc1101: &mut Cc1101<T>) {
-  // Set carrier base frequency 902.5 MHz
-  cc1101.0.write_register(Config::FREQ2, 0x21).unwrap();
-  cc1101.0.write_register(Config::FREQ1, 0x6D).unwrap();
-  cc1101.0.write_register(Config::FREQ0, 0x0A).unwrap();
-
-  // Set power level to max
-  cc1101
-      .0
-      .write_register(Config::FREND0, FREND0::default().pa_power(7).bits())
-      .unwrap();
-
-  // Set channel
-  cc1101.0.write_register(Config::CHANNR, 18).unwrap();
-
-  // Set IF 316.4kHz
-  cc1101
-      .0
-      .write_register(Config::FSCTRL1, FSCTRL1::default().freq_if(12).bits())
-      .unwrap();
-
-  // Set filter bandwidth to 562.5kHz, Data rate 250kBaud
-  cc1101
-      .0
-      .write_register(
-          Config::MDMCFG4,
-          MDMCFG4::default()
-              .chanbw_m(2)
-              .chanbw_e(0)
-              .drate_e(13)
-              .bits(),
-      )
-      .unwrap();
-  cc1101
-      .0
-      .write_register(Config::MDMCFG3, MDMCFG3::default().drate_m(48).bits())
-      .unwrap();
-
-  // Set channel spacing to 421kHz (max)
-  cc1101
-      .0
-      .write_register(
-          Config::MDMCFG1,
-          MDMCFG1::default()
-              .chanspc_e(3)
-              .fec_en(1)
-              .num_preamble(7)
-              .bits(),
-      )
-      .unwrap();
-  cc1101
-      .0
-      .write_register(Config::MDMCFG0, MDMCFG0::default().chanspc_m(255).bits())
-      .unwrap();
-  // Set modulation to GFSK
-  cc1101
-      .0
-      .write_register(Config::MDMCFG2, MDMCFG2::default().mod_format(1).bits())
-      .unwrap();
-  // Set deviation to 132kHz
-  cc1101
-      .0
-      .write_register(
-          Config::DEVIATN,
-          DEVIATN::default().deviation_m(2).deviation_e(6).bits(),
-      )
-      .unwrap();
-
-  cc1101
-      .0
-      .write_register(
-          Config::PKTCTRL0,
-          PKTCTRL0::default().crc_en(1).white_data(1).bits(),
-      )
-      .unwrap();
-
-  // Keep radio in rx mode even after packet received, make CCA always on
-  cc1101
-      .0
-      .write_register(Config::MCSM1, MCSM1::default().rxoff_mode(3).cca_mode(0).bits())
-      .unwrap();
-
-  cc1101
-      .0
-      .write_register(
-          Config::PKTCTRL1,
-          PKTCTRL1::default()
-              .pqt(4)
-              .crc_autoflush(1)
-              .append_status(0)
-              .bits(),
-      )
-      .unwrap();
+///
+/// All configuration registers are written in one burst, the others at their reset value.
+pub fn config_1<T: SpiDevice>(cc1101: &mut Cc1101<T>) -> Result<(), T::Error> {
+    cc1101.0.write_config_burst(Config::IOCFG2, &config_1_block())
+}
 
-  cc1101
-      .set_autocalibration(AutoCalibration::FromIdle)
-      .unwrap();
-  cc1101.set_packet_length(PacketLength::Fixed(32)).unwrap();
+/// All configuration registers for `config_1`, in address order.
+pub fn config_1_block() -> [u8; Config::ALL.len()] {
+    let mut block = Config::ALL.map(|reg| reg.reset_value());
+    for (reg, value) in config_1_registers() {
+        block[reg.addr() as usize] = value;
+    }
+    block
+}
 
-  cc1101.0.write_register(Config::IOCFG2, GdoCfg::CRC_OK.value()).unwrap();
+#[cfg(test)]
+mod tests {
+    use crate::configs::*;
+
+    #[test]
+    fn test_config_1_block() {
+        let block = config_1_block();
+        assert_eq!(block[Config::FREQ2.addr() as usize], 0x21);
+        assert_eq!(block[Config::PKTLEN.addr() as usize], 32);
+        // Untouched registers keep their reset value
+        assert_eq!(block[Config::FOCCFG.addr() as usize], 0x36);
+        assert_eq!(block[Config::TEST0.addr() as usize], 0x0B);
+    }
 }
@@ -0,0 +1,138 @@
+//! Listening for two sync words, by alternating between two profiles.
+//!
+//! The CC1101 only matches one sync word at a time. A gateway bridging two device families
+//! that differ in sync word (and possibly data rate) can still hear both by switching between
+//! them while idle-listening: [`DualSyncListener`] dwells on each profile in turn, holds on
+//! to the current one while a preamble or sync word is being received, and tags received
+//! packets with the profile they matched.
+//!
+//! The dwell time should cover at least the preamble and sync word of the slower profile,
+//! packets whose start falls on the other profile are missed.
+
+use crate::lowlevel::registers::*;
+use crate::{Cc1101, Error, RadioMode, SyncMode};
+use hal::spi::SpiDevice;
+
+/// Radio settings of one device family.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SyncProfile {
+    pub sync_mode: SyncMode,
+    /// Data rate (in baud), `None` to keep the current one.
+    pub data_rate: Option<u64>,
+}
+
+/// A packet received by `DualSyncListener::poll`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TaggedPacket {
+    /// Index of the profile it was received with, 0 or 1.
+    pub profile: usize,
+    /// Payload length.
+    pub len: usize,
+}
+
+/// Alternates between two `SyncProfile`s.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DualSyncListener {
+    profiles: [SyncProfile; 2],
+    dwell_us: u32,
+    active: usize,
+    elapsed_us: u32,
+}
+
+impl DualSyncListener {
+    /// Listen `dwell_us` on each profile in turn, starting with the first one.
+    pub fn new(profiles: [SyncProfile; 2], dwell_us: u32) -> Self {
+        DualSyncListener {
+            profiles,
+            dwell_us,
+            active: 0,
+            elapsed_us: 0,
+        }
+    }
+
+    /// Index of the profile currently listened to.
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// Apply the active profile and enter RX.
+    pub fn start<SPI, SpiE>(&mut self, cc1101: &mut Cc1101<SPI>) -> Result<(), Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        self.elapsed_us = 0;
+        cc1101.set_radio_mode(RadioMode::Idle)?;
+        cc1101.flush_rx()?;
+        let profile = self.profiles[self.active];
+        cc1101.set_sync_mode(profile.sync_mode)?;
+        if let Some(baud) = profile.data_rate {
+            cc1101.set_data_rate(baud)?;
+        }
+        cc1101.set_radio_mode(RadioMode::Receive)
+    }
+
+    /// To be called periodically, `elapsed_us` after the previous call.
+    ///
+    /// Reads a complete variable length packet into `buf` if there is one, and switches
+    /// profile once the dwell time is over and nothing is being received. The radio is kept
+    /// in RX.
+    pub fn poll<SPI, SpiE>(
+        &mut self,
+        cc1101: &mut Cc1101<SPI>,
+        elapsed_us: u32,
+        buf: &mut [u8],
+    ) -> Result<Option<TaggedPacket>, Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        let mut regs = [0u8; 2];
+        cc1101.0.read_status_registers(&[Status::RXBYTES, Status::PKTSTATUS], &mut regs)?;
+        let pktstatus = PKTSTATUS(regs[1]);
+        // Data in the FIFO with no packet in progress: a packet is complete
+        if RXBYTES(regs[0]).num_rxbytes() > 0 && pktstatus.sfd() == 0 {
+            let received = cc1101.receive_filtered(buf, |_| true)?;
+            // Listen some more to the profile that just matched
+            self.start(cc1101)?;
+            return Ok(received.map(|packet| TaggedPacket {
+                profile: self.active,
+                len: packet.len,
+            }));
+        }
+        if self.tick(elapsed_us, pktstatus.pqt_reached() != 0 || pktstatus.sfd() != 0) {
+            self.start(cc1101)?;
+        }
+        Ok(None)
+    }
+
+    /// Let time pass, returns whether to switch to the (now active) other profile.
+    fn tick(&mut self, elapsed_us: u32, busy: bool) -> bool {
+        self.elapsed_us = self.elapsed_us.saturating_add(elapsed_us);
+        if busy || self.elapsed_us < self.dwell_us {
+            return false;
+        }
+        self.active ^= 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dualsync::*;
+
+    #[test]
+    fn test_alternate() {
+        let profile = |word| SyncProfile {
+            sync_mode: SyncMode::MatchFull(word),
+            data_rate: None,
+        };
+        let mut listener = DualSyncListener::new([profile(0xD391), profile(0x2DD4)], 1000);
+        assert!(!listener.tick(600, false));
+        assert!(listener.tick(600, false));
+        assert_eq!(listener.active(), 1);
+        listener.elapsed_us = 0;
+        assert!(!listener.tick(1500, true));
+        assert_eq!(listener.active(), 1);
+        assert!(listener.tick(0, false));
+        assert_eq!(listener.active(), 0);
+    }
+}
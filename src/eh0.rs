@@ -0,0 +1,163 @@
+//! Adapters for embedded-hal 0.2 HALs.
+//!
+//! The driver is written against embedded-hal 1.0, these wrap the 0.2 blocking SPI, chip select,
+//! pin and delay traits into their 1.0 counterparts:
+//!
+//! ```ignore
+//! let spi = cc1101::eh0::SpiDevice::new(spi, cs);
+//! let mut cc1101 = cc1101::Cc1101::new(spi)?;
+//! ```
+
+use embedded_hal_0_2 as eh0;
+use hal::spi::{ErrorKind, ErrorType, Operation};
+
+/// SPI device built from a 0.2 SPI bus and chip select pin, owning both.
+pub struct SpiDevice<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+}
+
+impl<SPI, CS> SpiDevice<SPI, CS> {
+    /// Wrap the bus and chip select pin, the pin should be high (deselected).
+    pub fn new(spi: SPI, cs: CS) -> Self {
+        SpiDevice {
+            spi,
+            cs,
+        }
+    }
+
+    /// Release the bus and chip select pin.
+    pub fn release(self) -> (SPI, CS) {
+        (self.spi, self.cs)
+    }
+}
+
+/// SPI device errors.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SpiError<SpiE, CsE> {
+    /// Bus error.
+    Spi(SpiE),
+    /// Chip select pin error.
+    Cs(CsE),
+}
+
+impl<SpiE: core::fmt::Debug, CsE: core::fmt::Debug> hal::spi::Error for SpiError<SpiE, CsE> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SpiError::Spi(_) => ErrorKind::Other,
+            SpiError::Cs(_) => ErrorKind::ChipSelectFault,
+        }
+    }
+}
+
+impl<SPI, CS, SpiE, CsE> ErrorType for SpiDevice<SPI, CS>
+where
+    SPI: eh0::blocking::spi::Transfer<u8, Error = SpiE>
+        + eh0::blocking::spi::Write<u8, Error = SpiE>,
+    CS: eh0::digital::v2::OutputPin<Error = CsE>,
+    SpiE: core::fmt::Debug,
+    CsE: core::fmt::Debug,
+{
+    type Error = SpiError<SpiE, CsE>;
+}
+
+impl<SPI, CS, SpiE, CsE> hal::spi::SpiDevice<u8> for SpiDevice<SPI, CS>
+where
+    SPI: eh0::blocking::spi::Transfer<u8, Error = SpiE>
+        + eh0::blocking::spi::Write<u8, Error = SpiE>,
+    CS: eh0::digital::v2::OutputPin<Error = CsE>,
+    SpiE: core::fmt::Debug,
+    CsE: core::fmt::Debug,
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        self.cs.set_low().map_err(SpiError::Cs)?;
+        let result = operations.iter_mut().try_for_each(|op| self.operation(op));
+        // Deselect even if the transfer failed, the bus error takes precedence
+        let cs = self.cs.set_high().map_err(SpiError::Cs);
+        result.and(cs)
+    }
+}
+
+impl<SPI, CS, SpiE> SpiDevice<SPI, CS>
+where
+    SPI: eh0::blocking::spi::Transfer<u8, Error = SpiE>
+        + eh0::blocking::spi::Write<u8, Error = SpiE>,
+{
+    fn operation<CsE>(&mut self, op: &mut Operation<'_, u8>) -> Result<(), SpiError<SpiE, CsE>> {
+        match op {
+            Operation::Read(buf) => {
+                buf.fill(0);
+                self.spi.transfer(buf).map_err(SpiError::Spi)?;
+            }
+            Operation::Write(buf) => self.spi.write(buf).map_err(SpiError::Spi)?,
+            Operation::Transfer(read, write) => {
+                // 0.2 has no separate buffers, transfer the common part in place and clock out
+                // the rest of the longer one
+                let n = read.len().min(write.len());
+                read[..n].copy_from_slice(&write[..n]);
+                self.spi.transfer(&mut read[..n]).map_err(SpiError::Spi)?;
+                if write.len() > n {
+                    self.spi.write(&write[n..]).map_err(SpiError::Spi)?;
+                } else {
+                    read[n..].fill(0);
+                    self.spi.transfer(&mut read[n..]).map_err(SpiError::Spi)?;
+                }
+            }
+            Operation::TransferInPlace(buf) => {
+                self.spi.transfer(buf).map_err(SpiError::Spi)?;
+            }
+            Operation::DelayNs(_) => {
+                // No delay provider, the CC1101 accesses used by the driver don't need one
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Pin wrapper, e.g. for a GDO pin.
+pub struct Pin<P>(pub P);
+
+impl<P, E> hal::digital::ErrorType for Pin<P>
+where
+    P: eh0::digital::v2::InputPin<Error = E>,
+    E: core::fmt::Debug,
+{
+    type Error = PinError<E>;
+}
+
+impl<P, E> hal::digital::InputPin for Pin<P>
+where
+    P: eh0::digital::v2::InputPin<Error = E>,
+    E: core::fmt::Debug,
+{
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.0.is_high().map_err(PinError)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.0.is_low().map_err(PinError)
+    }
+}
+
+/// Pin error.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PinError<E>(pub E);
+
+impl<E: core::fmt::Debug> hal::digital::Error for PinError<E> {
+    fn kind(&self) -> hal::digital::ErrorKind {
+        hal::digital::ErrorKind::Other
+    }
+}
+
+/// Delay wrapper.
+pub struct Delay<D>(pub D);
+
+impl<D: eh0::blocking::delay::DelayUs<u32>> hal::delay::DelayNs for Delay<D> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.delay_us(ns.div_ceil(1000));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.0.delay_us(us);
+    }
+}
@@ -0,0 +1,110 @@
+//! Early filtering of received packets.
+//!
+//! A gateway listening to a busy channel mostly receives traffic it doesn't care about.
+//! [`Cc1101::receive_filtered`] reads the length byte and the first few bytes of a variable
+//! length packet and hands them to a filter, the rest of the packet is only copied out of the
+//! RX FIFO if the filter accepts it. Rejected packets are flushed when possible, which costs a
+//! single strobe whatever their length.
+
+use crate::lowlevel::registers::*;
+use crate::lowlevel::types::*;
+use crate::trace::TraceEvent;
+use crate::{Cc1101, Error, ReceivedPacket};
+use hal::spi::SpiDevice;
+
+/// Number of leading payload bytes given to the filter.
+pub const HEADER_LEN: usize = 4;
+
+/// Start of a received packet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PacketHeader<'a> {
+    /// Payload length, from the length byte.
+    pub len: u8,
+    /// Destination address, when address filtering is enabled (also the first byte of `head`).
+    pub address: Option<u8>,
+    /// First `HEADER_LEN` bytes of the payload, fewer for shorter packets.
+    pub head: &'a [u8],
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Read a variable length packet into `buf` if `filter` accepts its header.
+    ///
+    /// To be called once a whole packet is in the RX FIFO, e.g. when GDO0 configured as
+    /// `GdoCfg::SYNC_WORD` de-asserts. Returns the payload length and reception details, or
+    /// `None` if the FIFO is empty or the packet was rejected. Packets longer than `buf` are
+    /// discarded and fail with `PacketLengthMismatch`, an RX FIFO overflow is recovered from
+    /// and fails with `RxOverflow`.
+    pub fn receive_filtered<F>(
+        &mut self,
+        buf: &mut [u8],
+        filter: F,
+    ) -> Result<Option<ReceivedPacket>, Error<SpiE>>
+    where
+        F: FnOnce(&PacketHeader) -> bool,
+    {
+        let fifo = self.rx_bytes_available()?;
+        if fifo.error {
+            return self.recover_rx_overflow();
+        }
+        if fifo.bytes == 0 {
+            return Ok(None);
+        }
+        let pktctrl1 = PKTCTRL1(self.0.read_register(Config::PKTCTRL1)?);
+        let status_len = 2 * pktctrl1.append_status() as usize;
+
+        let mut len = [0u8];
+        self.0.read_fifo(&mut len)?;
+        let len = len[0] as usize;
+        let mut head = [0u8; HEADER_LEN];
+        let n = len.min(HEADER_LEN);
+        if n > 0 {
+            self.0.read_fifo(&mut head[..n])?;
+        }
+        let header = PacketHeader {
+            len: len as u8,
+            address: (pktctrl1.adr_chk() != 0 && n > 0).then_some(head[0]),
+            head: &head[..n],
+        };
+        let too_long = len > buf.len();
+        if too_long || !filter(&header) {
+            self.discard(len - n + status_len)?;
+            return match too_long {
+                true => {
+                    self.0.trace_event(TraceEvent::PacketLengthMismatch);
+                    Err(Error::PacketLengthMismatch)
+                }
+                false => Ok(None),
+            };
+        }
+
+        buf[..n].copy_from_slice(&head[..n]);
+        if len > n {
+            self.0.read_fifo(&mut buf[n..len])?;
+        }
+        let mut status = [0u8; 2];
+        if status_len > 0 {
+            self.0.read_fifo(&mut status)?;
+        }
+        let info = self.notify_packet((status_len > 0).then_some(status))?;
+        Ok(Some(ReceivedPacket::new(len, info, header.address)))
+    }
+
+    /// Drop the next `n` bytes of the RX FIFO.
+    pub(crate) fn discard(&mut self, n: usize) -> Result<(), Error<SpiE>> {
+        if self.get_marc_state()? == MachineState::IDLE.value() {
+            // Nothing else can be in the FIFO after the packet
+            return self.flush_rx();
+        }
+        let mut scratch = [0u8; 16];
+        let mut left = n;
+        while left > 0 {
+            let chunk = left.min(scratch.len());
+            self.0.read_fifo(&mut scratch[..chunk])?;
+            left -= chunk;
+        }
+        Ok(())
+    }
+}
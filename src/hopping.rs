@@ -0,0 +1,222 @@
+//! Frequency hopping.
+//!
+//! A [`Hopper`] walks a hop sequence, a list of channels of the programmed plan (e.g. from a
+//! `hopplan::HopSequence`), spending a fixed dwell time on each. Retuning restores the
+//! calibration cached by `Cc1101::calibrate_channels`, so run with autocalibration disabled and
+//! a hop only costs the synthesizer settling time.
+//!
+//! Both ends run the same sequence and dwell time, calling [`Hopper::poll`] from a timer. The
+//! transmitter puts [`Hopper::sync`] in its packets, the receiver passes it to
+//! [`Hopper::synchronize`] to line up with the transmitter. Until then, a receiver can wait
+//! on a single channel (`hop_to`) for a packet, the transmitter gets there once per cycle.
+//!
+//! ```ignore
+//! let mut hopper = Hopper::new(&hops, &fscal, 100_000).unwrap();
+//! hopper.hop_to(&mut cc1101, 0)?;
+//! // Transmitter
+//! packet[..HOP_SYNC_LEN].copy_from_slice(&hopper.sync().to_bytes());
+//! // Receiver, on a packet
+//! hopper.synchronize(&mut cc1101, HopSync::from_bytes(&packet[..HOP_SYNC_LEN]), latency_us)?;
+//! ```
+
+use crate::channels::Channel;
+use crate::{Cc1101, Error, RadioMode};
+use hal::spi::SpiDevice;
+
+/// Length of an encoded `HopSync`.
+pub const HOP_SYNC_LEN: usize = 6;
+
+/// Position in the hop sequence, exchanged to synchronize both ends.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HopSync {
+    /// Index in the hop sequence.
+    pub index: u16,
+    /// Time spent on the current hop (in microseconds).
+    pub elapsed_us: u32,
+}
+
+impl HopSync {
+    /// Big endian index, then elapsed time.
+    pub fn to_bytes(&self) -> [u8; HOP_SYNC_LEN] {
+        let [i0, i1] = self.index.to_be_bytes();
+        let [e0, e1, e2, e3] = self.elapsed_us.to_be_bytes();
+        [i0, i1, e0, e1, e2, e3]
+    }
+
+    pub fn from_bytes(bytes: &[u8; HOP_SYNC_LEN]) -> Self {
+        let [i0, i1, e0, e1, e2, e3] = *bytes;
+        HopSync {
+            index: u16::from_be_bytes([i0, i1]),
+            elapsed_us: u32::from_be_bytes([e0, e1, e2, e3]),
+        }
+    }
+}
+
+/// Hops through a sequence of channels, `dwell_us` on each.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Hopper<'a> {
+    hops: &'a [Channel],
+    fscal: &'a [[u8; 3]],
+    dwell_us: u32,
+    index: usize,
+    elapsed_us: u32,
+}
+
+impl<'a> Hopper<'a> {
+    /// Hop through `hops` in order, wrapping around, with the calibration of each channel in
+    /// `fscal` (filled by `Cc1101::calibrate_channels`). Starts at the first hop, call
+    /// `hop_to` to tune to it.
+    ///
+    /// `None` if `hops` is empty, `dwell_us` is 0 or `fscal` has no calibration for one of the
+    /// hops.
+    pub fn new(hops: &'a [Channel], fscal: &'a [[u8; 3]], dwell_us: u32) -> Option<Self> {
+        if hops.is_empty() || dwell_us == 0 {
+            return None;
+        }
+        if hops.iter().any(|c| c.number() as usize >= fscal.len()) {
+            return None;
+        }
+        Some(Hopper {
+            hops,
+            fscal,
+            dwell_us,
+            index: 0,
+            elapsed_us: 0,
+        })
+    }
+
+    /// Index of the current hop.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.hops[self.index]
+    }
+
+    /// Current position, for the other end to `synchronize` to.
+    pub fn sync(&self) -> HopSync {
+        HopSync {
+            index: self.index as u16,
+            elapsed_us: self.elapsed_us,
+        }
+    }
+
+    /// Retune to hop `index` (wrapping around), staying in RX if the radio was in RX.
+    ///
+    /// Restarts the dwell time.
+    pub fn hop_to<SPI, SpiE>(
+        &mut self,
+        cc1101: &mut Cc1101<SPI>,
+        index: usize,
+    ) -> Result<(), Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        self.index = index % self.hops.len();
+        self.elapsed_us = 0;
+        self.retune(cc1101)
+    }
+
+    /// Retune to the next hop, see `hop_to`.
+    pub fn hop_next<SPI, SpiE>(&mut self, cc1101: &mut Cc1101<SPI>) -> Result<(), Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        self.hop_to(cc1101, self.index + 1)
+    }
+
+    /// Account for `elapsed_us` since the last call, hopping once the dwell time is over.
+    ///
+    /// Returns whether it hopped. Hops missed by a late call are skipped, the dwell time
+    /// keeps its phase.
+    pub fn poll<SPI, SpiE>(
+        &mut self,
+        cc1101: &mut Cc1101<SPI>,
+        elapsed_us: u32,
+    ) -> Result<bool, Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        if !self.tick(elapsed_us) {
+            return Ok(false);
+        }
+        self.retune(cc1101)?;
+        Ok(true)
+    }
+
+    /// Line up with the other end at `sync`, received `latency_us` ago.
+    ///
+    /// Only retunes if that's a different hop.
+    pub fn synchronize<SPI, SpiE>(
+        &mut self,
+        cc1101: &mut Cc1101<SPI>,
+        sync: HopSync,
+        latency_us: u32,
+    ) -> Result<(), Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        let index = self.index;
+        self.index = sync.index as usize % self.hops.len();
+        self.elapsed_us = 0;
+        self.tick(sync.elapsed_us.saturating_add(latency_us));
+        if self.index != index {
+            self.retune(cc1101)?;
+        }
+        Ok(())
+    }
+
+    /// Let `elapsed_us` pass, returns whether the hop changed.
+    fn tick(&mut self, elapsed_us: u32) -> bool {
+        let elapsed_us = self.elapsed_us as u64 + elapsed_us as u64;
+        let hops = elapsed_us / self.dwell_us as u64;
+        self.elapsed_us = (elapsed_us % self.dwell_us as u64) as u32;
+        self.index = ((self.index as u64 + hops) % self.hops.len() as u64) as usize;
+        hops > 0
+    }
+
+    fn retune<SPI, SpiE>(&mut self, cc1101: &mut Cc1101<SPI>) -> Result<(), Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        let rx = cc1101.is_in(RadioMode::Receive)?;
+        cc1101.set_radio_mode(RadioMode::Idle)?;
+        cc1101.select_channel_calibrated(self.channel(), self.fscal)?;
+        if rx {
+            cc1101.set_radio_mode(RadioMode::Receive)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channels::{Band, ChannelPlan};
+    use crate::hopping::*;
+
+    const PLAN: ChannelPlan = ChannelPlan::new(Band::ISM_433, 433_100_000, 200_000, 4);
+
+    #[test]
+    fn test_tick() {
+        let hops = [2, 0, 3, 1].map(|n| PLAN.channel(n).unwrap());
+        assert_eq!(Hopper::new(&[], &[], 1_000), None);
+        let fscal = [[0u8; 3]; 4];
+        assert_eq!(Hopper::new(&hops, &fscal, 0), None);
+        assert_eq!(Hopper::new(&hops, &fscal[..3], 1_000), None);
+        let mut hopper = Hopper::new(&hops, &fscal, 1_000).unwrap();
+        assert!(!hopper.tick(999));
+        assert!(hopper.tick(1));
+        assert_eq!((hopper.index(), hopper.channel().number()), (1, 0));
+        // Polled late, three and a half dwell times later
+        assert!(hopper.tick(3_500));
+        assert_eq!(
+            hopper.sync(),
+            HopSync {
+                index: 0,
+                elapsed_us: 500
+            }
+        );
+        assert_eq!(HopSync::from_bytes(&hopper.sync().to_bytes()), hopper.sync());
+    }
+}
@@ -0,0 +1,155 @@
+//! Hop sequences for frequency hopping under FCC 15.247 (902 to 928 MHz).
+//!
+//! With at least 50 hopping channels, the rules require pseudo-random use of all channels
+//! equally on average, and at most 0.4 s on any channel within a 20 s period.
+//! [`HopSequence`] is a seeded permutation of the channels of a [`ChannelPlan`], so both ends
+//! generate the same sequence from a shared seed, and [`DwellTracker`] accounts for the time
+//! spent on each channel.
+
+use crate::channels::{Channel, ChannelPlan};
+
+/// Minimum number of hopping channels.
+pub const MIN_CHANNELS: u16 = 50;
+/// Maximum time on a channel within `DWELL_WINDOW_US` (in microseconds).
+pub const MAX_DWELL_US: u32 = 400_000;
+/// Dwell time observation period (in microseconds).
+pub const DWELL_WINDOW_US: u32 = 20_000_000;
+
+/// Pseudo-random order of all the channels of a plan.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HopSequence {
+    plan: ChannelPlan,
+    order: [u8; 256],
+}
+
+impl HopSequence {
+    /// Shuffle the channels of `plan` from `seed`, `None` if it has fewer than `MIN_CHANNELS`.
+    ///
+    /// The sequence only depends on the number of channels and the seed, and is stable across
+    /// versions of this crate.
+    pub fn new(plan: &ChannelPlan, seed: u32) -> Option<Self> {
+        if plan.count() < MIN_CHANNELS {
+            return None;
+        }
+        let mut order = [0u8; 256];
+        for (n, o) in order.iter_mut().enumerate() {
+            *o = n as u8;
+        }
+        // Fisher-Yates, with xorshift32 (stuck at zero, hence the `max`)
+        let mut rng = seed.max(1);
+        for i in (1..plan.count() as usize).rev() {
+            rng ^= rng << 13;
+            rng ^= rng >> 17;
+            rng ^= rng << 5;
+            order.swap(i, rng as usize % (i + 1));
+        }
+        Some(HopSequence {
+            plan: *plan,
+            order,
+        })
+    }
+
+    /// Number of hops before the sequence repeats, the number of channels.
+    pub fn len(&self) -> usize {
+        self.plan.count() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Channel of hop `index`, wrapping around at the end of the sequence.
+    pub fn channel(&self, index: usize) -> Channel {
+        let n = self.order[index % self.len()];
+        // Always within the plan, only its channels are shuffled
+        self.plan.channel(n as u16).unwrap()
+    }
+
+    /// The channels, in hopping order.
+    pub fn iter(&self) -> impl Iterator<Item = Channel> + '_ {
+        (0..self.len()).map(|i| self.channel(i))
+    }
+}
+
+/// Time on air accounting per channel, over fixed `DWELL_WINDOW_US` windows.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DwellTracker {
+    dwell_us: [u32; 256],
+    elapsed_us: u32,
+}
+
+impl DwellTracker {
+    pub fn new() -> Self {
+        DwellTracker {
+            dwell_us: [0; 256],
+            elapsed_us: 0,
+        }
+    }
+
+    /// Let time pass, starting a new window once the current one is over.
+    pub fn advance(&mut self, us: u32) {
+        self.elapsed_us = self.elapsed_us.saturating_add(us);
+        if self.elapsed_us >= DWELL_WINDOW_US {
+            self.elapsed_us = 0;
+            self.dwell_us = [0; 256];
+        }
+    }
+
+    /// Whether `channel` can be used for another `us` in the current window.
+    pub fn allows(&self, channel: Channel, us: u32) -> bool {
+        self.dwell_us(channel).saturating_add(us) <= MAX_DWELL_US
+    }
+
+    /// Account for `us` spent on `channel`.
+    pub fn record(&mut self, channel: Channel, us: u32) {
+        let dwell = &mut self.dwell_us[channel.number() as usize];
+        *dwell = dwell.saturating_add(us);
+        self.advance(us);
+    }
+
+    /// Time spent on `channel` in the current window (in microseconds).
+    pub fn dwell_us(&self, channel: Channel) -> u32 {
+        self.dwell_us[channel.number() as usize]
+    }
+}
+
+impl Default for DwellTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channels::Band;
+    use crate::hopplan::*;
+
+    const PLAN: ChannelPlan = ChannelPlan::new(Band::ISM_915, 902_200_000, 400_000, 64);
+
+    #[test]
+    fn test_hop_sequence() {
+        let sequence = HopSequence::new(&PLAN, 1234).unwrap();
+        let mut seen = [false; 64];
+        for channel in sequence.iter() {
+            assert!(!seen[channel.number() as usize]);
+            seen[channel.number() as usize] = true;
+        }
+        assert_eq!(sequence, HopSequence::new(&PLAN, 1234).unwrap());
+        assert_ne!(sequence, HopSequence::new(&PLAN, 4321).unwrap());
+        assert_eq!(sequence.channel(64), sequence.channel(0));
+
+        let small = ChannelPlan::new(Band::ISM_915, 902_200_000, 400_000, 49);
+        assert!(HopSequence::new(&small, 1234).is_none());
+    }
+
+    #[test]
+    fn test_dwell_tracker() {
+        let channel = PLAN.channel(3).unwrap();
+        let mut tracker = DwellTracker::new();
+        tracker.record(channel, 300_000);
+        assert!(!tracker.allows(channel, 150_000));
+        assert!(tracker.allows(PLAN.channel(4).unwrap(), 400_000));
+        tracker.advance(DWELL_WINDOW_US);
+        assert_eq!(tracker.dwell_us(channel), 0);
+    }
+}
@@ -0,0 +1,196 @@
+//! Frame-level support for KeeLoq-style (HCS301) rolling-code remotes.
+//!
+//! Only the PHY framing is handled here: preamble, header, PWM bit encoding and guard time. The
+//! hopping code encryption is left to the caller, frames are built from and decoded to the
+//! already encrypted 32-bit hopping code.
+//!
+//! Frames use the raw OOK format from [`crate::remotes`], one bit per TE (basic pulse element).
+
+use crate::remotes::OokFrame;
+
+/// Typical basic pulse element duration (in microseconds).
+pub const TE_US: u32 = 400;
+
+const PREAMBLE_TE: usize = 23;
+const HEADER_TE: usize = 10;
+const GUARD_TE: usize = 39;
+const DATA_BITS: usize = 66;
+
+/// Transmitted code word.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct KeeloqCode {
+    /// Encrypted hopping code.
+    pub hopping: u32,
+    /// 28-bit serial number.
+    pub serial: u32,
+    /// Button status S3, S0, S1, S2 (bit 3 to 0).
+    pub buttons: u8,
+    /// Battery low.
+    pub vlow: bool,
+    /// Repeated transmission.
+    pub repeat: bool,
+}
+
+/// Plain text hopping code, before encryption.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HoppingCode {
+    /// Button status, same as in `KeeloqCode`.
+    pub buttons: u8,
+    /// Counter overflow bits.
+    pub overflow: u8,
+    /// 10-bit discrimination value.
+    pub discrimination: u16,
+    /// 16-bit synchronization counter.
+    pub counter: u16,
+}
+
+impl HoppingCode {
+    /// The 32-bit block fed to the cipher.
+    pub fn to_bits(&self) -> u32 {
+        (self.buttons as u32 & 0xf) << 28
+            | (self.overflow as u32 & 0x3) << 26
+            | (self.discrimination as u32 & 0x3ff) << 16
+            | self.counter as u32
+    }
+
+    /// Unpack a decrypted 32-bit block.
+    pub fn from_bits(bits: u32) -> Self {
+        HoppingCode {
+            buttons: (bits >> 28) as u8,
+            overflow: (bits >> 26 & 0x3) as u8,
+            discrimination: (bits >> 16 & 0x3ff) as u16,
+            counter: bits as u16,
+        }
+    }
+}
+
+impl KeeloqCode {
+    /// Build a code word, `encrypt` maps the plain text hopping code to the encrypted one.
+    pub fn new<F>(serial: u32, hopping: &HoppingCode, encrypt: F) -> Self
+    where
+        F: FnOnce(u32) -> u32,
+    {
+        KeeloqCode {
+            hopping: encrypt(hopping.to_bits()),
+            serial: serial & 0x0fff_ffff,
+            buttons: hopping.buttons & 0xf,
+            vlow: false,
+            repeat: false,
+        }
+    }
+
+    /// Decrypt the hopping code, `decrypt` maps the encrypted block to the plain text one.
+    pub fn decrypt<F>(&self, decrypt: F) -> HoppingCode
+    where
+        F: FnOnce(u32) -> u32,
+    {
+        HoppingCode::from_bits(decrypt(self.hopping))
+    }
+
+    /// The 66 data bits, in transmission order (LSB first).
+    fn data(&self) -> u128 {
+        self.hopping as u128
+            | (self.serial as u128 & 0x0fff_ffff) << 32
+            | (self.buttons as u128 & 0xf) << 60
+            | (self.vlow as u128) << 64
+            | (self.repeat as u128) << 65
+    }
+
+    fn from_data(data: u128) -> Self {
+        KeeloqCode {
+            hopping: data as u32,
+            serial: (data >> 32) as u32 & 0x0fff_ffff,
+            buttons: (data >> 60) as u8 & 0xf,
+            vlow: data >> 64 & 1 == 1,
+            repeat: data >> 65 & 1 == 1,
+        }
+    }
+
+    /// Encode the code word as a raw OOK frame with basic pulse element `te_us`.
+    pub fn encode(&self, te_us: u32) -> OokFrame {
+        let mut frame = OokFrame::new(te_us);
+        for n in 0..PREAMBLE_TE {
+            frame.push(n % 2 == 0, 1);
+        }
+        frame.push(false, HEADER_TE);
+        let data = self.data();
+        for n in 0..DATA_BITS {
+            // PWM: 1 is 1 TE high and 2 TE low, 0 is 2 TE high and 1 TE low.
+            let high = 2 - (data >> n & 1) as usize;
+            frame.push(true, high);
+            frame.push(false, 3 - high);
+        }
+        frame.push(false, GUARD_TE);
+        frame
+    }
+
+    /// Decode a code word from a raw OOK capture sampled once per TE, MSB first.
+    ///
+    /// Looks for the header (at least 8 TE low following a high pulse), then reads the data
+    /// bits. Returns `None` when no complete, well-formed code word is found.
+    pub fn decode(capture: &[u8]) -> Option<Self> {
+        let len = capture.len() * 8;
+        let bit = |n: usize| capture[n / 8] & (0x80 >> (n % 8)) != 0;
+
+        let mut n = 0;
+        while n < len {
+            // Find a high to low edge, followed by the header gap
+            if bit(n) && n + 1 < len && !bit(n + 1) {
+                let start = n + 1;
+                let mut end = start;
+                while end < len && !bit(end) {
+                    end += 1;
+                }
+                if end - start >= HEADER_TE - 2 {
+                    if let Some(code) = Self::decode_data(capture, end) {
+                        return Some(code);
+                    }
+                }
+                n = end;
+            } else {
+                n += 1;
+            }
+        }
+        None
+    }
+
+    fn decode_data(capture: &[u8], start: usize) -> Option<Self> {
+        if start + DATA_BITS * 3 > capture.len() * 8 {
+            return None;
+        }
+        let bit = |n: usize| capture[n / 8] & (0x80 >> (n % 8)) != 0;
+        let mut data = 0u128;
+        for i in 0..DATA_BITS {
+            let n = start + i * 3;
+            if !bit(n) || bit(n + 2) {
+                return None;
+            }
+            if !bit(n + 1) {
+                data |= 1 << i;
+            }
+        }
+        Some(Self::from_data(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keeloq::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let hopping = HoppingCode {
+            buttons: 0b0010,
+            overflow: 0,
+            discrimination: 0x155,
+            counter: 42,
+        };
+        let code = KeeloqCode::new(0x0abc_def1, &hopping, |p| p ^ 0xdead_beef);
+        let frame = code.encode(TE_US);
+        assert_eq!(frame.len(), 23 + 10 + 66 * 3 + 39);
+
+        let decoded = KeeloqCode::decode(frame.bytes()).unwrap();
+        assert_eq!(decoded, code);
+        assert_eq!(decoded.decrypt(|c| c ^ 0xdead_beef), hopping);
+    }
+}
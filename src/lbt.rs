@@ -0,0 +1,137 @@
+//! Listen-before-talk presets for EN 300 220 (868 MHz SRD band).
+//!
+//! A profile configures carrier sense and the CCA logic, so a `STX` strobe issued while the
+//! channel is busy leaves the radio in RX instead of transmitting. The listen and dead times are
+//! timing requirements the application has to honour between strobes.
+//!
+//! [`Cc1101::transmit_lbt`] does the listening for a single packet, backing off while the
+//! channel is busy.
+
+use crate::lowlevel::types::*;
+use crate::{CarrierSense, Cc1101, CcaMode, Error, RadioMode, RelativeThreshold, RxTime};
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+/// Input level (in dBm) at which the RSSI reaches the default AGC magnitude target with maximum
+/// gain, i.e. the carrier sense threshold for `CARRIER_SENSE_ABS_THR` = 0.
+///
+/// Approximate, varies with channel bandwidth, see DN022 for measured values.
+const CS_REFERENCE_DBM: i16 = -90;
+
+/// Time for the radio to act on an STX strobe in RX (in microseconds), well above the RX to TX
+/// turnaround. Still in RX after that, CCA failed.
+const STX_DECISION_US: u32 = 100;
+
+/// Listen-before-talk parameters.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LbtProfile {
+    /// Minimum time the channel has to be clear before transmitting (in microseconds).
+    pub listen_time_us: u32,
+    /// Signal level above which the channel is considered busy (in dBm).
+    pub threshold_dbm: i16,
+    /// Minimum time between the end of a transmission and the next one (in microseconds).
+    pub dead_time_us: u32,
+}
+
+impl LbtProfile {
+    /// EN 300 220-1 V2: 5 ms listen time, -90 dBm threshold, 100 ms transmitter off time.
+    pub const ETSI_V2: LbtProfile = LbtProfile {
+        listen_time_us: 5_000,
+        threshold_dbm: -90,
+        dead_time_us: 100_000,
+    };
+
+    /// EN 300 220-1 V3: 160 us minimum listen time, -85 dBm threshold, 100 ms transmitter off
+    /// time.
+    pub const ETSI_V3: LbtProfile = LbtProfile {
+        listen_time_us: 160,
+        threshold_dbm: -85,
+        dead_time_us: 100_000,
+    };
+}
+/// Result of `transmit_lbt`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LbtOutcome {
+    /// The channel was clear, the packet has been sent.
+    Sent,
+    /// The channel stayed busy, nothing was sent.
+    ChannelBusy,
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Configure carrier sense, RX termination and CCA for listen-before-talk.
+    ///
+    /// - Carrier sense absolute threshold derived from `threshold_dbm`
+    /// - RX stays on until strobed (no RX_TIME timeout)
+    /// - CCA: RSSI below threshold, unless currently receiving a packet
+    pub fn set_lbt_profile(&mut self, profile: &LbtProfile) -> Result<(), Error<SpiE>> {
+        self.set_carrier_sense(CarrierSense {
+            absolute_db: Some((profile.threshold_dbm - CS_REFERENCE_DBM).clamp(-7, 7) as i8),
+            relative: RelativeThreshold::Disabled,
+        })?;
+        self.set_rx_timeout(RxTime::default())?;
+        self.set_cca_mode(CcaMode::RssiBelowThresholdUnlessReceiving)?;
+        Ok(())
+    }
+
+    /// Transmit `payload` once the channel is clear, see `transmit` for the rest.
+    ///
+    /// The radio listens for `listen_time_us` (entering RX if needed), then checks CCA
+    /// (`is_channel_clear`), the STX strobe is only issued when the channel is clear. While
+    /// it's busy, it backs off for twice as long after each attempt. After `attempts` busy
+    /// attempts, the radio is put back in IDLE with the TX FIFO flushed, and `ChannelBusy` is
+    /// returned.
+    ///
+    /// CCA has to be configured, e.g. with `set_lbt_profile`. The dead time after the
+    /// transmission is up to the application.
+    pub fn transmit_lbt<D: DelayNs>(
+        &mut self,
+        payload: &[u8],
+        profile: &LbtProfile,
+        attempts: u8,
+        delay: &mut D,
+    ) -> Result<LbtOutcome, Error<SpiE>> {
+        if !self.is_in(RadioMode::Receive)? {
+            self.set_radio_mode(RadioMode::Receive)?;
+        }
+        self.write_tx_fifo(payload)?;
+        for attempt in 0..attempts {
+            delay.delay_us(profile.listen_time_us);
+            if self.is_channel_clear()? {
+                self.send_radio_mode_strobe(RadioMode::Transmit)?;
+                // Still in RX if the channel got busy in the meantime
+                if self.await_tx_start(delay)? {
+                    self.finish_transmit()?;
+                    return Ok(LbtOutcome::Sent);
+                }
+            }
+            if attempt + 1 < attempts {
+                delay.delay_us(profile.listen_time_us.saturating_mul(1 << attempt.min(8)));
+            }
+        }
+        self.set_radio_mode(RadioMode::Idle)?;
+        self.flush_tx()?;
+        Ok(LbtOutcome::ChannelBusy)
+    }
+
+    /// After an STX strobe in RX, whether the radio switched to TX, waiting up to
+    /// `STX_DECISION_US` for it to leave RX.
+    fn await_tx_start<D: DelayNs>(&mut self, delay: &mut D) -> Result<bool, Error<SpiE>> {
+        let mut waited_us = 0;
+        loop {
+            let state = self.get_marc_state()?;
+            let tx = [MachineState::RXTX_SWITCH, MachineState::TX, MachineState::TX_END];
+            if tx.iter().any(|s| s.value() == state) {
+                return Ok(true);
+            }
+            if waited_us >= STX_DECISION_US {
+                return Ok(false);
+            }
+            delay.delay_us(self.0.poll_interval_us);
+            waited_us = waited_us.saturating_add(self.0.poll_interval_us);
+        }
+    }
+}
@@ -5,55 +5,189 @@ extern crate embedded_hal as hal;
 #[cfg(feature = "std")]
 extern crate std;
 
-use core::fmt::{self, Display, Formatter};
+use core::convert::Infallible;
+use core::fmt::{self, Debug, Display, Formatter};
+use hal::delay::DelayNs;
 use hal::spi::SpiDevice;
 
 #[macro_use]
 pub mod lowlevel;
+pub mod beacon;
+#[cfg(feature = "bridge")]
+pub mod bridge;
+pub mod capture;
+pub mod cc1190;
+pub mod channels;
 pub mod config0;
 mod configs;
+pub mod dualsync;
+#[cfg(feature = "eh0")]
+pub mod eh0;
+pub mod filter;
+pub mod hopping;
+pub mod hopplan;
+pub mod keeloq;
+pub mod lbt;
+pub mod linkplan;
+pub mod longpacket;
+pub mod maintenance;
+pub mod observer;
+pub mod ota;
+pub mod pairing;
+pub mod per;
+pub mod pulse;
+pub mod ratescan;
+pub mod recal;
+pub mod remotes;
 pub mod rssi;
+pub mod rxdiag;
+pub mod rxwindow;
+pub mod scan;
+pub mod slip;
+#[cfg(feature = "spilog")]
+pub mod spilog;
+pub mod stats;
+pub mod syncscan;
+pub mod syncword;
+pub mod tempcomp;
+pub mod trace;
+pub mod watchdog;
+pub mod wor;
 
 use lowlevel::convert::*;
 pub use lowlevel::registers::*;
 pub use lowlevel::types::*;
-use rssi::rssi_to_dbm;
+use lowlevel::{burst_limit, FIFO_SIZE, PATABLE_SIZE};
+use rssi::{rssi_to_dbm, RssiHistogram, Whitener};
+use trace::TraceEvent;
+use watchdog::Health;
+
+/// Default interval between reads of the polling loops (in microseconds), see
+/// `Cc1101::set_poll_interval`.
+pub const DEFAULT_POLL_INTERVAL_US: u32 = 10;
+
+/// MARCSTATE reads before a state machine wait gives up with `Timeout`: at least ~16 ms at the
+/// fastest SPI clock, well above the slowest transition (calibration, under 1 ms).
+const STATE_POLL_BUDGET: u32 = 10_000;
+
+/// PKTCTRL0 PKT_FORMAT for synchronous serial mode.
+const PKT_FORMAT_SYNC_SERIAL: u8 = 1;
+/// PKTCTRL0 PKT_FORMAT for random TX data, the FIFOs aren't used.
+const PKT_FORMAT_RANDOM: u8 = 2;
+/// PKTCTRL0 PKT_FORMAT for asynchronous serial mode.
+const PKT_FORMAT_ASYNC_SERIAL: u8 = 3;
 
 /// CC1101 errors.
+///
+/// `GpioE` is the error type of the GDO or front end pins, for the APIs using them.
 #[derive(Debug)]
-pub enum Error<SpiE> {
+pub enum Error<SpiE, GpioE = Infallible> {
     /// The RX FIFO buffer overflowed, too small buffer for configured packet length.
+    ///
+    /// Returned by the receive paths once recovered: the RX FIFO has been flushed and the
+    /// radio is back in RX.
     RxOverflow,
     /// Corrupt packet received with invalid CRC.
     CrcMismatch,
+    /// Data doesn't fit in the free space of the TX FIFO, nothing was written.
+    TxOverflow,
+    /// The TX FIFO ran empty before the end of the packet, it wasn't refilled in time.
+    ///
+    /// Returned by the transmit paths once recovered: the TX FIFO has been flushed and the
+    /// radio is in IDLE.
+    TxUnderflow,
+    /// Burst access to an address that doesn't support it, or past the end of its range.
+    InvalidBurst,
+    /// Buffer length doesn't match the configured fixed packet length.
+    PacketLengthMismatch,
+    /// Operation not allowed in the radio's current state (MARCSTATE value).
+    InvalidState(u8),
+    /// Setting not supported with the rest of the configuration.
+    InvalidConfig,
+    /// Frequency outside of the bands the synthesizer supports, see `channels::SUPPORTED_BANDS`.
+    InvalidFrequency,
+    /// The radio didn't reach the expected state in time, it's absent or wedged.
+    Timeout,
+    /// A register read back a different value than was written.
+    VerifyFailed {
+        reg: Config,
+        expected: u8,
+        got: u8,
+    },
+    /// Platform-dependent GPIO errors, from the pins passed to the driver.
+    Gpio(GpioE),
     /// Platform-dependent SPI-errors, such as IO errors.
     Spi(SpiE),
 }
 
-impl<SpiE> From<SpiE> for Error<SpiE> {
+impl<SpiE> Error<SpiE> {
+    /// Convert to an error with a GPIO error type, for APIs that also access pins.
+    pub fn with_gpio<GpioE>(self) -> Error<SpiE, GpioE> {
+        match self {
+            Error::RxOverflow => Error::RxOverflow,
+            Error::CrcMismatch => Error::CrcMismatch,
+            Error::TxOverflow => Error::TxOverflow,
+            Error::TxUnderflow => Error::TxUnderflow,
+            Error::InvalidBurst => Error::InvalidBurst,
+            Error::PacketLengthMismatch => Error::PacketLengthMismatch,
+            Error::InvalidState(state) => Error::InvalidState(state),
+            Error::InvalidConfig => Error::InvalidConfig,
+            Error::InvalidFrequency => Error::InvalidFrequency,
+            Error::Timeout => Error::Timeout,
+            Error::VerifyFailed {
+                reg,
+                expected,
+                got,
+            } => Error::VerifyFailed {
+                reg,
+                expected,
+                got,
+            },
+            Error::Gpio(e) => match e {},
+            Error::Spi(e) => Error::Spi(e),
+        }
+    }
+}
+
+impl<SpiE, GpioE> From<SpiE> for Error<SpiE, GpioE> {
     fn from(e: SpiE) -> Self {
         Error::Spi(e)
     }
 }
 
-impl<SpiE: Display> Display for Error<SpiE> {
+impl<SpiE: Display, GpioE: Debug> Display for Error<SpiE, GpioE> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
             Self::RxOverflow => write!(f, "RX FIFO buffer overflowed"),
             Self::CrcMismatch => write!(f, "CRC mismatch"),
+            Self::TxOverflow => write!(f, "TX FIFO buffer would overflow"),
+            Self::TxUnderflow => write!(f, "TX FIFO buffer underflowed"),
+            Self::InvalidBurst => write!(f, "invalid burst access"),
+            Self::PacketLengthMismatch => write!(f, "packet length mismatch"),
+            Self::InvalidState(state) => write!(f, "invalid in radio state {:#04x}", state),
+            Self::InvalidConfig => write!(f, "unsupported with the current configuration"),
+            Self::InvalidFrequency => write!(f, "frequency outside the supported bands"),
+            Self::Timeout => write!(f, "timed out waiting for the radio"),
+            Self::VerifyFailed {
+                reg,
+                expected,
+                got,
+            } => {
+                write!(f, "{:?} reads {:#04x}, expected {:#04x}", reg, got, expected)
+            }
+            Self::Gpio(e) => write!(f, "GPIO error: {:?}", e),
             Self::Spi(e) => write!(f, "SPI error: {}", e),
         }
     }
 }
 
 #[cfg(feature = "std")]
-impl<SpiE: Display + core::fmt::Debug> std::error::Error for Error<SpiE> {}
+impl<SpiE: Display + Debug, GpioE: Debug> std::error::Error for Error<SpiE, GpioE> {}
 
 /// High level API for interacting with the CC1101 radio chip.
 pub struct Cc1101<SPI>(pub lowlevel::Cc1101<SPI>);
 
-impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI>
-{
+impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI> {
     /// Make a new device, only returns an instance of Cc1101
     ///
     /// You should:
@@ -64,22 +198,83 @@ impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI>
         Ok(Cc1101(lowlevel::Cc1101::new(spi)?))
     }
 
+    /// Sets the interval between reads of the polling loops taking a `DelayNs` (in
+    /// microseconds), `DEFAULT_POLL_INTERVAL_US` by default.
+    ///
+    /// From 10 µs on fast links, to milliseconds on low-power nodes to keep the MCU and SPI
+    /// bus idle. The loops wait the interval after each read rather than running on a timer,
+    /// a late wake up delays the next read without ever bunching reads up.
+    pub fn set_poll_interval(&mut self, us: u32) {
+        self.0.poll_interval_us = us.max(1);
+    }
+
+    /// Interval between reads of the polling loops (in microseconds).
+    pub fn poll_interval(&self) -> u32 {
+        self.0.poll_interval_us
+    }
+
     /// Sets the carrier frequency (in Hertz).
+    ///
+    /// Fails with `InvalidFrequency`, leaving the frequency as it was, outside of the bands
+    /// the synthesizer supports. The check is on the frequency on air, the register value is
+    /// corrected for the crystal error (see `set_crystal_ppm`).
     pub fn set_frequency(&mut self, hz: u64) -> Result<(), Error<SpiE>> {
-        let (freq0, freq1, freq2) = from_frequency(hz);
+        if !channels::is_supported_frequency(hz) {
+            return Err(Error::InvalidFrequency);
+        }
+        let (freq0, freq1, freq2) = from_frequency(self.xtal_corrected(hz));
         self.0.write_register(Config::FREQ0, freq0)?;
         self.0.write_register(Config::FREQ1, freq1)?;
         self.0.write_register(Config::FREQ2, freq2)?;
         Ok(())
     }
 
+    /// The carrier frequency (in Hertz), as programmed.
+    ///
+    /// Accounts for the crystal error set with `set_crystal_ppm`.
+    pub fn get_frequency(&mut self) -> Result<u64, Error<SpiE>> {
+        let mut freq = [0u8; 3];
+        self.0.read_config_burst(Config::FREQ2, &mut freq)?;
+        Ok(self.xtal_actual(to_frequency(freq[2], freq[1], freq[0])))
+    }
+
     /// Sets the frequency synthesizer intermediate frequency (in Hertz).
     pub fn set_synthesizer_if(&mut self, hz: u64) -> Result<(), Error<SpiE>> {
-        self.0
-            .write_register(Config::FSCTRL1, FSCTRL1::default().freq_if(from_freq_if(hz)).bits())?;
+        self.0.write_register(
+            Config::FSCTRL1,
+            FSCTRL1::default().freq_if(from_freq_if(self.xtal_corrected(hz))).bits(),
+        )?;
         Ok(())
     }
 
+    /// Declare the measured error of the crystal (in ppm), positive when it runs fast.
+    ///
+    /// Frequency, IF and channel spacing settings made afterwards are scaled so they hit their
+    /// target on the actual crystal frequency, settings made before are left as is.
+    pub fn set_crystal_ppm(&mut self, ppm: i32) {
+        self.0.xtal_ppm = ppm;
+    }
+
+    pub fn crystal_ppm(&self) -> i32 {
+        self.0.xtal_ppm
+    }
+
+    /// Cache the configuration registers in RAM, so the setters modifying a register cost a
+    /// single SPI transaction, see `lowlevel::Cc1101::set_shadow` for the caveats.
+    pub fn set_register_cache(&mut self, enable: bool) {
+        self.0.set_shadow(enable);
+    }
+
+    /// Frequency to program for the synthesizer to produce `hz` with the actual crystal.
+    pub(crate) fn xtal_corrected(&self, hz: u64) -> u64 {
+        (hz as i64 * 1_000_000 / (1_000_000 + self.0.xtal_ppm as i64)) as u64
+    }
+
+    /// Frequency the synthesizer produces with the actual crystal for programmed `hz`.
+    fn xtal_actual(&self, hz: u64) -> u64 {
+        (hz as i64 * (1_000_000 + self.0.xtal_ppm as i64) / 1_000_000) as u64
+    }
+
     /// Sets the target value for the averaged amplitude from the digital channel filter.
     pub fn set_agc_target(&mut self, target: TargetAmplitude) -> Result<(), Error<SpiE>> {
         self.0.modify_register(Config::AGCCTRL2, |r| {
@@ -99,6 +294,112 @@ impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI>
         Ok(())
     }
 
+    /// Configures the AGC, the carrier sense thresholds in AGCCTRL1 are left as they are.
+    pub fn set_agc(&mut self, config: AgcConfig) -> Result<(), Error<SpiE>> {
+        self.0.modify_config_burst(Config::AGCCTRL2, |[agc2, agc1, agc0]: &mut [u8; 3]| {
+            *agc2 = AGCCTRL2::default()
+                .max_dvga_gain(config.dvga_gain_reduction.min(3))
+                .max_lna_gain(config.lna_gain_reduction.min(7))
+                .magn_target(config.target.into())
+                .bits();
+            *agc1 = AGCCTRL1(*agc1).modify().agc_lna_priority(config.lna_priority as u8).bits();
+            *agc0 = AGCCTRL0::default()
+                .hyst_level(config.hysteresis.into())
+                .wait_time(config.wait_time.into())
+                .agc_freeze(config.freeze.into())
+                .filter_length(config.filter_length.into())
+                .bits();
+        })?;
+        Ok(())
+    }
+
+    /// Configures the clock recovery (bit synchronization) loop.
+    pub fn set_bit_sync(&mut self, config: BitSyncConfig) -> Result<(), Error<SpiE>> {
+        let gain = |g: u8| g.clamp(1, 4) - 1;
+        self.0.write_register(
+            Config::BSCFG,
+            BSCFG::default()
+                .bs_pre_ki(gain(config.pre_ki))
+                .bs_pre_kp(gain(config.pre_kp))
+                .bs_post_ki(config.post_ki_halved as u8)
+                .bs_post_kp(config.post_kp_reduced as u8)
+                .bs_limit(config.limit.into())
+                .bits(),
+        )?;
+        Ok(())
+    }
+
+    /// Sets when carrier sense asserts, used by CCA and the carrier sense GDO signal.
+    pub fn set_carrier_sense(&mut self, carrier_sense: CarrierSense) -> Result<(), Error<SpiE>> {
+        // -8 disables the absolute threshold
+        let abs_thr = carrier_sense.absolute_db.map_or(-8, |db| db.clamp(-7, 7)) as u8 & 0x0f;
+        self.0.modify_register(Config::AGCCTRL1, |r| {
+            AGCCTRL1(r)
+                .modify()
+                .carrier_sense_rel_thr(carrier_sense.relative.into())
+                .carrier_sense_abs_thr(abs_thr)
+                .bits()
+        })?;
+        Ok(())
+    }
+
+    /// Sets when the channel is clear, a TX strobe is ignored (the radio stays in RX) when it
+    /// isn't.
+    pub fn set_cca_mode(&mut self, mode: CcaMode) -> Result<(), Error<SpiE>> {
+        self.0
+            .modify_register(Config::MCSM1, |r| MCSM1(r).modify().cca_mode(mode.into()).bits())?;
+        Ok(())
+    }
+
+    /// Sets the state the radio goes to when a packet has been received.
+    pub fn set_rxoff_mode(&mut self, mode: OffMode) -> Result<(), Error<SpiE>> {
+        self.0
+            .modify_register(Config::MCSM1, |r| MCSM1(r).modify().rxoff_mode(mode.into()).bits())?;
+        Ok(())
+    }
+
+    /// Sets the state the radio goes to when a packet has been sent.
+    pub fn set_txoff_mode(&mut self, mode: OffMode) -> Result<(), Error<SpiE>> {
+        self.0
+            .modify_register(Config::MCSM1, |r| MCSM1(r).modify().txoff_mode(mode.into()).bits())?;
+        Ok(())
+    }
+
+    /// Sets when RX terminates by itself, for duty-cycled receivers and Wake-on-Radio.
+    ///
+    /// The timeout is relative to the WOR event 0 period, see `configure_wor` and
+    /// `configure_rx_window`. On timeout the radio goes to IDLE, or back to SLEEP in WOR.
+    pub fn set_rx_timeout(&mut self, rx_time: RxTime) -> Result<(), Error<SpiE>> {
+        // 7 disables the timeout
+        let timeout = rx_time.timeout.map_or(7, |timeout| timeout.min(6));
+        self.0.modify_register(Config::MCSM2, |r| {
+            MCSM2(r)
+                .modify()
+                .rx_time_rssi(rx_time.rssi_termination as u8)
+                .rx_time_qual(rx_time.qualifier.into())
+                .rx_time(timeout)
+                .bits()
+        })?;
+        Ok(())
+    }
+
+    /// Program the PA power levels, `levels[0]` to `levels[n - 1]` into the PATABLE.
+    ///
+    /// With OOK/ASK, '0' symbols are sent at `levels[0]` and '1' symbols at the last level,
+    /// the PA ramping through the levels in between on each transition, which shapes the
+    /// spectrum. Otherwise the PA ramps up through the levels at the start of a burst and down
+    /// at the end, the last one is the output power. Fails with `InvalidConfig` unless there
+    /// are 1 to `PATABLE_SIZE` levels.
+    pub fn set_pa_ramp(&mut self, levels: &[u8]) -> Result<(), Error<SpiE>> {
+        if levels.is_empty() || levels.len() > PATABLE_SIZE {
+            return Err(Error::InvalidConfig);
+        }
+        self.0.write_patable(levels)?;
+        let index = levels.len() as u8 - 1;
+        self.0.modify_register(Config::FREND0, |r| FREND0(r).modify().pa_power(index).bits())?;
+        Ok(())
+    }
+
     /// Configures when to run automatic calibration.
     pub fn set_autocalibration(&mut self, autocal: AutoCalibration) -> Result<(), Error<SpiE>> {
         self.0.modify_register(Config::MCSM0, |r| {
@@ -116,6 +417,12 @@ impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI>
         Ok(())
     }
 
+    /// The frequency deviation (in Hertz), as programmed.
+    pub fn get_deviation(&mut self) -> Result<u64, Error<SpiE>> {
+        let deviatn = DEVIATN(self.0.read_register(Config::DEVIATN)?);
+        Ok(to_deviation(deviatn.deviation_m(), deviatn.deviation_e()))
+    }
+
     /// Sets the data rate (in bits per second).
     pub fn set_data_rate(&mut self, baud: u64) -> Result<(), Error<SpiE>> {
         let (mantissa, exponent) = from_drate(baud);
@@ -125,6 +432,14 @@ impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI>
         Ok(())
     }
 
+    /// The data rate (in bits per second), as programmed.
+    pub fn get_data_rate(&mut self) -> Result<u64, Error<SpiE>> {
+        // MDMCFG4, MDMCFG3
+        let mut mdmcfg = [0u8; 2];
+        self.0.read_config_burst(Config::MDMCFG4, &mut mdmcfg)?;
+        Ok(to_drate(MDMCFG3(mdmcfg[1]).drate_m(), MDMCFG4(mdmcfg[0]).drate_e()))
+    }
+
     /// Sets the channel bandwidth (in Hertz).
     pub fn set_chanbw(&mut self, bandwidth: u64) -> Result<(), Error<SpiE>> {
         let (mantissa, exponent) = from_chanbw(bandwidth);
@@ -134,17 +449,350 @@ impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI>
         Ok(())
     }
 
+    /// The channel filter bandwidth (in Hertz), as programmed.
+    pub fn get_chanbw(&mut self) -> Result<u64, Error<SpiE>> {
+        let mdmcfg4 = MDMCFG4(self.0.read_register(Config::MDMCFG4)?);
+        Ok(to_chanbw(mdmcfg4.chanbw_m(), mdmcfg4.chanbw_e()))
+    }
+
+    /// Select channel `channel`, at base frequency + `channel` · channel spacing.
+    pub fn set_channel(&mut self, channel: u8) -> Result<(), Error<SpiE>> {
+        self.0.write_register(Config::CHANNR, channel)?;
+        Ok(())
+    }
+
+    /// Sets the channel spacing (in Hertz), returns the closest spacing the radio supports.
+    ///
+    /// Corrected for the crystal error set with `set_crystal_ppm`, the returned spacing is the
+    /// one for a nominal crystal.
+    pub fn set_channel_spacing(&mut self, hz: u64) -> Result<u64, Error<SpiE>> {
+        let (mantissa, exponent) = from_chanspc(self.xtal_corrected(hz));
+        // MDMCFG1, MDMCFG0
+        self.0.modify_config_burst(Config::MDMCFG1, |[mdmcfg1, mdmcfg0]: &mut [u8; 2]| {
+            *mdmcfg1 = MDMCFG1(*mdmcfg1).modify().chanspc_e(exponent).bits();
+            *mdmcfg0 = mantissa;
+        })?;
+        Ok(to_chanspc(mantissa, exponent))
+    }
+
+    /// The channel spacing (in Hertz), as programmed, for a nominal crystal.
+    pub fn get_channel_spacing(&mut self) -> Result<u64, Error<SpiE>> {
+        // MDMCFG1, MDMCFG0
+        let mut mdmcfg = [0u8; 2];
+        self.0.read_config_burst(Config::MDMCFG1, &mut mdmcfg)?;
+        Ok(to_chanspc(mdmcfg[1], MDMCFG1(mdmcfg[0]).chanspc_e()))
+    }
+
+    /// Select the narrowest channel filter bandwidth fitting the signal, returns it (in Hertz).
+    ///
+    /// The signal bandwidth is estimated with Carson's rule, `data_rate` + 2·`deviation`, and
+    /// the filter has to be wider by the worst case frequency error between transmitter and
+    /// receiver, 4·`crystal_ppm`·f_carrier (both crystals off by `crystal_ppm`, either way).
+    /// The carrier frequency is read back from the radio, so set it first.
+    pub fn auto_chanbw(
+        &mut self,
+        data_rate: u64,
+        deviation: u64,
+        crystal_ppm: u64,
+    ) -> Result<u64, Error<SpiE>> {
+        let mut freq = [0u8; 3];
+        self.0.read_config_burst(Config::FREQ2, &mut freq)?;
+        let carrier = to_frequency(freq[2], freq[1], freq[0]);
+        let required = data_rate + 2 * deviation + 4 * crystal_ppm * carrier / 1_000_000;
+        let (mantissa, exponent) = chanbw_at_least(required);
+        self.0.modify_register(Config::MDMCFG4, |r| {
+            MDMCFG4(r).modify().chanbw_m(mantissa).chanbw_e(exponent).bits()
+        })?;
+        Ok(to_chanbw(mantissa, exponent))
+    }
+
+    /// Apply temporary register values, run `f`, then restore the previous values.
+    ///
+    /// Registers are restored whether or not `f` succeeds, e.g. to hop to a control channel at
+    /// a lower data rate for a single exchange. At most `Config::ALL.len()` registers can be
+    /// given, fails with `InvalidConfig` (without any SPI access) otherwise.
+    ///
+    /// Every register is written back even if restoring one fails. `f`'s error takes precedence
+    /// over a restore error, and a restore error replaces `f`'s value: the radio can't be
+    /// trusted to be back in its previous configuration.
+    pub fn with_config<T, F>(&mut self, config: &[(Config, u8)], f: F) -> Result<T, Error<SpiE>>
+    where
+        F: FnOnce(&mut Self) -> Result<T, Error<SpiE>>,
+    {
+        let mut saved = [0u8; Config::ALL.len()];
+        if config.len() > saved.len() {
+            return Err(Error::InvalidConfig);
+        }
+        let mut applied = 0;
+        let mut result = Ok(());
+        for &(reg, value) in config {
+            result = self.0.read_register(reg).and_then(|v| {
+                saved[applied] = v;
+                self.0.write_register(reg, value)
+            });
+            if result.is_err() {
+                break;
+            }
+            applied += 1;
+        }
+
+        let result = result.map_err(Error::from).and_then(|_| f(self));
+
+        // Restore in reverse, so a register listed twice ends up with its original value
+        let mut restored = Ok(());
+        for (&(reg, _), &value) in config[..applied].iter().zip(&saved).rev() {
+            restored = restored.and(self.0.write_register(reg, value));
+        }
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
+
+    /// The registers of `desired` whose current value differs, as (register, current, desired).
+    ///
+    /// All configuration registers are read in one burst, e.g. to check the radio after a
+    /// brown-out or after other code had access to it.
+    pub fn diff_config<'a>(
+        &mut self,
+        desired: &'a [(Config, u8)],
+    ) -> Result<impl Iterator<Item = (Config, u8, u8)> + 'a, Error<SpiE>> {
+        let mut current = [0u8; Config::ALL.len()];
+        self.0.read_config_burst(Config::IOCFG2, &mut current)?;
+        Ok(desired.iter().filter_map(move |&(reg, value)| {
+            let now = current[reg.addr() as usize];
+            (now != value).then_some((reg, now, value))
+        }))
+    }
+
+    /// Write the registers of `desired` whose current value differs, returns how many.
+    pub fn reconcile(&mut self, desired: &[(Config, u8)]) -> Result<usize, Error<SpiE>> {
+        let mut count = 0;
+        for (reg, _, value) in self.diff_config(desired)? {
+            self.0.write_register(reg, value)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
     pub fn get_hw_info(&mut self) -> Result<(u8, u8), Error<SpiE>> {
         let partnum = self.0.read_register(Status::PARTNUM)?;
         let version = self.0.read_register(Status::VERSION)?;
         Ok((partnum, version))
     }
 
+    /// Burst read `buf.len()` bytes starting at `addr`.
+    ///
+    /// Fails with `InvalidBurst`, without accessing the bus, unless the whole range is within
+    /// the configuration registers, the PATABLE or the RX FIFO (see `lowlevel::burst_limit`).
+    pub fn read_burst(&mut self, addr: u8, buf: &mut [u8]) -> Result<(), Error<SpiE>> {
+        if buf.len() > burst_limit(addr).ok_or(Error::InvalidBurst)? {
+            return Err(Error::InvalidBurst);
+        }
+        Ok(self.0.read_burst(addr, buf)?)
+    }
+
+    /// Burst write `buf` starting at `addr`.
+    ///
+    /// Same validation as `read_burst`.
+    pub fn write_burst(&mut self, addr: u8, buf: &[u8]) -> Result<(), Error<SpiE>> {
+        if buf.len() > burst_limit(addr).ok_or(Error::InvalidBurst)? {
+            return Err(Error::InvalidBurst);
+        }
+        if addr < Command::PATABLE.addr() {
+            self.0.write_config_burst(Config::ALL[addr as usize], buf)?;
+        } else {
+            self.0.write_burst(addr, buf)?;
+        }
+        Ok(())
+    }
+
+    /// Read all configuration and status registers, e.g. to compare against SmartRF Studio
+    /// settings. The dump prints one register per line.
+    pub fn dump_registers(&mut self) -> Result<RegisterDump, Error<SpiE>> {
+        let mut dump = RegisterDump {
+            config: [0; Config::ALL.len()],
+            status: [0; Status::ALL.len()],
+        };
+        self.0.read_config_burst(Config::IOCFG2, &mut dump.config)?;
+        self.0.read_status_registers(&Status::ALL, &mut dump.status)?;
+        Ok(dump)
+    }
+
+    /// Send a command strobe, returns the chip status right after it.
+    ///
+    /// `fifo_bytes` counts the RX FIFO if `rx_fifo`, the TX FIFO otherwise. E.g. `SNOP` polls
+    /// the state and FIFO fill level in a single byte transaction.
+    pub fn strobe_with_status(
+        &mut self,
+        command: Command,
+        rx_fifo: bool,
+    ) -> Result<ChipStatus, Error<SpiE>> {
+        let status = self.0.strobe_with_status(command, rx_fifo)?;
+        Ok(ChipStatus::from_byte(status, rx_fifo))
+    }
+
+    /// Status byte the chip returned on the last SPI access, at no extra cost.
+    ///
+    /// `None` before the first access. Register reads answered by the register cache (see
+    /// `set_register_cache`) don't access the chip, and don't update it.
+    pub fn last_status(&self) -> Option<ChipStatus> {
+        self.0.last_status.map(|(byte, read)| ChipStatus::from_byte(byte, read))
+    }
+
+    /// Read the status registers describing the current radio and packet state.
+    pub fn status_snapshot(&mut self) -> Result<StatusSnapshot, Error<SpiE>> {
+        let mut regs = [0u8; 7];
+        self.0.read_status_registers(
+            &[
+                Status::MARCSTATE,
+                Status::PKTSTATUS,
+                Status::RXBYTES,
+                Status::TXBYTES,
+                Status::RSSI,
+                Status::LQI,
+                Status::FREQEST,
+            ],
+            &mut regs,
+        )?;
+        let [marcstate, pktstatus, rxbytes, txbytes, rssi, lqi, freqest] = regs;
+        Ok(StatusSnapshot {
+            marcstate,
+            pktstatus,
+            rxbytes,
+            txbytes,
+            rssi,
+            lqi,
+            freqest,
+        })
+    }
+
+    /// Number of bytes in the RX FIFO, and whether it overflowed.
+    pub fn rx_bytes_available(&mut self) -> Result<FifoStatus, Error<SpiE>> {
+        let rxbytes = RXBYTES(self.read_fifo_status(Status::RXBYTES)?);
+        Ok(FifoStatus {
+            bytes: rxbytes.num_rxbytes(),
+            error: rxbytes.rxfifo_overflow() != 0,
+        })
+    }
+
+    /// Number of bytes in the TX FIFO, and whether it underflowed.
+    pub fn tx_bytes_pending(&mut self) -> Result<FifoStatus, Error<SpiE>> {
+        let txbytes = TXBYTES(self.read_fifo_status(Status::TXBYTES)?);
+        Ok(FifoStatus {
+            bytes: txbytes.num_txbytes(),
+            error: txbytes.txfifo_underflow() != 0,
+        })
+    }
+
+    /// RXBYTES or TXBYTES, read until two consecutive reads match (errata: SPI read
+    /// synchronization, the value can be wrong while the FIFO is being filled or drained).
+    ///
+    /// Fails with `Timeout` if no two reads match within `STATE_POLL_BUDGET` reads.
+    fn read_fifo_status(&mut self, reg: Status) -> Result<u8, Error<SpiE>> {
+        let mut last = self.0.read_register(reg)?;
+        for _ in 0..STATE_POLL_BUDGET {
+            let value = self.0.read_register(reg)?;
+            if value == last {
+                return Ok(value);
+            }
+            last = value;
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Read and decode PKTSTATUS, e.g. to poll for a sync word or a clear channel without a
+    /// GDO pin.
+    pub fn packet_status(&mut self) -> Result<PacketStatus, Error<SpiE>> {
+        Ok(PacketStatus::from(self.0.read_register(Status::PKTSTATUS)?))
+    }
+
+    /// Whether the channel is clear, as the CCA logic sees it (PKTSTATUS CCA).
+    ///
+    /// Follows `set_cca_mode` and the `set_carrier_sense` thresholds. Only meaningful in RX,
+    /// once the RSSI has settled (see `rssi_settle_time_us`).
+    pub fn is_channel_clear(&mut self) -> Result<bool, Error<SpiE>> {
+        Ok(self.packet_status()?.clear_channel)
+    }
+
+    /// Free space in the TX FIFO (in bytes).
+    pub fn tx_fifo_free(&mut self) -> Result<u8, Error<SpiE>> {
+        let txbytes = TXBYTES(self.read_fifo_status(Status::TXBYTES)?);
+        Ok((FIFO_SIZE as u8).saturating_sub(txbytes.num_txbytes()))
+    }
+
+    /// Write `data` to the TX FIFO, refusing to write anything if it doesn't fit.
+    pub fn write_tx_fifo(&mut self, data: &[u8]) -> Result<(), Error<SpiE>> {
+        if data.len() > self.tx_fifo_free()? as usize {
+            self.0.trace_event(TraceEvent::TxOverflow);
+            return Err(Error::TxOverflow);
+        }
+        self.0.write_fifo(data)?;
+        Ok(())
+    }
+
     /// Received Signal Strength Indicator is an estimate of the signal power level in the chosen channel.
     pub fn get_rssi_dbm(&mut self) -> Result<i16, Error<SpiE>> {
         Ok(rssi_to_dbm(self.0.read_register(Status::RSSI)?))
     }
 
+    /// Time for the RSSI to settle after entering RX (in microseconds).
+    ///
+    /// Estimated from the channel bandwidth and AGC settings (DN505): the AGC needs a few
+    /// rounds of `WAIT_TIME` + `FILTER_LENGTH` channel filter samples, at twice the channel
+    /// bandwidth, to converge.
+    pub fn rssi_settle_time_us(&mut self) -> Result<u32, Error<SpiE>> {
+        let mdmcfg4 = MDMCFG4(self.0.read_register(Config::MDMCFG4)?);
+        let agcctrl0 = AGCCTRL0(self.0.read_register(Config::AGCCTRL0)?);
+        let bandwidth = to_chanbw(mdmcfg4.chanbw_m(), mdmcfg4.chanbw_e());
+        let samples = 8 * (agcctrl0.wait_time() as u64 + 1) + (8 << agcctrl0.filter_length());
+        Ok((3 * samples * 1_000_000).div_ceil(2 * bandwidth) as u32)
+    }
+
+    /// Same as `get_rssi_dbm`, but first waits for the RSSI to settle.
+    ///
+    /// Call right after entering RX, a reading taken before the AGC has settled is meaningless.
+    pub fn get_rssi_dbm_settled<D: DelayNs>(&mut self, delay: &mut D) -> Result<i16, Error<SpiE>> {
+        let settle_us = self.rssi_settle_time_us()?;
+        delay.delay_us(settle_us);
+        self.get_rssi_dbm()
+    }
+
+    /// Sample the RSSI into `histogram`, the radio should be in RX.
+    pub fn sample_rssi(&mut self, histogram: &mut RssiHistogram) -> Result<(), Error<SpiE>> {
+        histogram.record(self.get_rssi_dbm()?);
+        Ok(())
+    }
+
+    /// Fill `buf` with random bits taken from the RSSI noise, e.g. to seed a PRNG.
+    ///
+    /// Collects the least significant bit of RSSI readings in RX, debiased with a von Neumann
+    /// extractor. The channel should be quiet, a signal makes the readings predictable. Good
+    /// enough for backoff and nonces, hash it with other inputs for key material. The radio is
+    /// left in IDLE.
+    ///
+    /// Fails with `Timeout` if `STATE_POLL_BUDGET` readings don't yield a byte, the RSSI is
+    /// stuck (e.g. no chip answering).
+    pub fn gather_entropy(&mut self, buf: &mut [u8]) -> Result<(), Error<SpiE>> {
+        self.set_radio_mode(RadioMode::Receive)?;
+        let mut whitener = Whitener::default();
+        for byte in buf.iter_mut() {
+            let mut bits = 0;
+            let mut samples = 0;
+            while bits < 8 {
+                if samples == STATE_POLL_BUDGET {
+                    self.set_radio_mode(RadioMode::Idle)?;
+                    return Err(Error::Timeout);
+                }
+                samples += 1;
+                let rssi = self.0.read_register(Status::RSSI)?;
+                if whitener.shift_into(byte, rssi & 1 != 0) {
+                    bits += 1;
+                }
+            }
+        }
+        self.set_radio_mode(RadioMode::Idle)
+    }
+
     /// The Link Quality Indicator metric of the current quality of the received signal.
     /// The CRC check for last packet.
     pub fn get_crc_lqi(&mut self) -> Result<(bool, u8), Error<SpiE>> {
@@ -152,6 +800,28 @@ impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI>
         Ok((lqi.crc_ok() > 0, lqi.lqi()))
     }
 
+    /// Carrier frequency offset estimated during the last packet received, in steps of
+    /// FXOSC/2^14 (~1.6 kHz).
+    pub fn get_freq_offset_estimate(&mut self) -> Result<i8, Error<SpiE>> {
+        Ok(FREQEST(self.0.read_register(Status::FREQEST)?).freqoff_est() as i8)
+    }
+
+    /// Add the frequency offset estimate of the last packet received to the synthesizer
+    /// frequency offset (FSCTRL0), tracking the crystal drift between the two ends.
+    ///
+    /// Call it after a good packet (e.g. CRC OK), the estimate of a bad one may be noise.
+    /// Returns the new offset, saturated at the limits of the register. Takes effect on the
+    /// next calibration.
+    pub fn apply_afc(&mut self) -> Result<i8, Error<SpiE>> {
+        let estimate = self.get_freq_offset_estimate()?;
+        let mut offset = 0;
+        self.0.modify_register(Config::FSCTRL0, |r| {
+            offset = (FSCTRL0(r).freqoff() as i8).saturating_add(estimate);
+            FSCTRL0(r).modify().freqoff(offset as u8).bits()
+        })?;
+        Ok(offset)
+    }
+
     /// Configure the sync word to use, and at what level it should be verified.
     pub fn set_sync_mode(&mut self, sync_mode: SyncMode) -> Result<(), Error<SpiE>> {
         let reset: u16 = (SYNC1::default().bits() as u16) << 8 | (SYNC0::default().bits() as u16);
@@ -161,12 +831,26 @@ impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI>
             SyncMode::MatchPartial(word) => (SyncCheck::CHECK_15_16, word),
             SyncMode::MatchPartialRepeated(word) => (SyncCheck::CHECK_30_32, word),
             SyncMode::MatchFull(word) => (SyncCheck::CHECK_16_16, word),
+            SyncMode::CarrierSense => (SyncCheck::CHECK_0_0_CS, reset),
+            SyncMode::MatchPartialCarrierSense(word) => (SyncCheck::CHECK_15_16_CS, word),
+            SyncMode::MatchPartialRepeatedCarrierSense(word) => (SyncCheck::CHECK_30_32_CS, word),
+            SyncMode::MatchFullCarrierSense(word) => (SyncCheck::CHECK_16_16_CS, word),
         };
         self.0.modify_register(Config::MDMCFG2, |r| {
             MDMCFG2(r).modify().sync_mode(mode.value()).bits()
         })?;
-        self.0.write_register(Config::SYNC1, ((word >> 8) & 0xff) as u8)?;
-        self.0.write_register(Config::SYNC0, (word & 0xff) as u8)?;
+        self.0.write_config_burst(Config::SYNC1, &word.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Enable the digital DC blocking filter before the demodulator (the reset setting).
+    ///
+    /// The filter gives better sensitivity. Disabling it lowers the current consumption, but
+    /// only for data rates up to 250 kBaud, with the optimized settings from SmartRF Studio.
+    pub fn set_dc_filter(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(Config::MDMCFG2, |r| {
+            MDMCFG2(r).modify().dem_dcfilt_off(!enable as u8).bits()
+        })?;
         Ok(())
     }
 
@@ -187,6 +871,194 @@ impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI>
         Ok(())
     }
 
+    /// The modulation format, fails with `InvalidConfig` on a reserved MOD_FORMAT value.
+    pub fn get_modulation(&mut self) -> Result<Modulation, Error<SpiE>> {
+        use lowlevel::types::ModFormat as MF;
+
+        let mod_format = MDMCFG2(self.0.read_register(Config::MDMCFG2)?).mod_format();
+        Ok(match mod_format {
+            f if f == MF::MOD_2FSK.value() => Modulation::BinaryFrequencyShiftKeying,
+            f if f == MF::MOD_GFSK.value() => Modulation::GaussianFrequencyShiftKeying,
+            f if f == MF::MOD_ASK_OOK.value() => Modulation::OnOffKeying,
+            f if f == MF::MOD_4FSK.value() => Modulation::FourFrequencyShiftKeying,
+            f if f == MF::MOD_MSK.value() => Modulation::MinimumShiftKeying,
+            _ => return Err(Error::InvalidConfig),
+        })
+    }
+
+    /// Set the RX and TX FIFO thresholds, used by the FIFO threshold GDO signals.
+    pub fn set_fifo_thresholds(&mut self, threshold: FifoThreshold) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(Config::FIFOTHR, |r| {
+            FIFOTHR(r).modify().fifo_thr(threshold.value()).bits()
+        })?;
+        Ok(())
+    }
+
+    pub fn fifo_thresholds(&mut self) -> Result<FifoThreshold, Error<SpiE>> {
+        let fifothr = FIFOTHR(self.0.read_register(Config::FIFOTHR)?);
+        Ok(FifoThreshold::from_value(fifothr.fifo_thr()))
+    }
+
+    /// Attenuate the received signal, for strong signals that would saturate the front end
+    /// (close-in reception, see DN010).
+    pub fn set_rx_attenuation(&mut self, attenuation: RxAttenuation) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(Config::FIFOTHR, |r| {
+            FIFOTHR(r).modify().close_in_rx(attenuation.into()).bits()
+        })?;
+        Ok(())
+    }
+
+    /// Select the signal output on a GDO pin, active low when `inverted`.
+    ///
+    /// GDO1 keeps its drive strength setting, GDO0 has the temperature sensor disabled.
+    pub fn set_gdo_config(
+        &mut self,
+        pin: GdoPin,
+        cfg: GdoCfg,
+        inverted: bool,
+    ) -> Result<(), Error<SpiE>> {
+        let inv = inverted as u8;
+        match pin {
+            GdoPin::Gdo0 => self.0.write_register(
+                Config::IOCFG0,
+                IOCFG0::default().gdo0_inv(inv).gdo0_cfg(cfg.value()).bits(),
+            )?,
+            GdoPin::Gdo1 => self.0.modify_register(Config::IOCFG1, |r| {
+                IOCFG1(r).modify().gdo1_inv(inv).gdo1_cfg(cfg.value()).bits()
+            })?,
+            GdoPin::Gdo2 => self.0.write_register(
+                Config::IOCFG2,
+                IOCFG2::default().gdo2_inv(inv).gdo2_cfg(cfg.value()).bits(),
+            )?,
+        }
+        Ok(())
+    }
+
+    /// Exchange data over the GDO pins instead of the FIFOs, to implement protocols in
+    /// software. Goes to IDLE first, enter RX or TX to start.
+    ///
+    /// Data goes in (TX) and out (RX) on GDO0. In synchronous mode, GDO2 outputs the data
+    /// clock: TX data is sampled on its rising edge, RX data set up on its falling edge.
+    /// Asynchronous mode samples the TX data at the data rate, and outputs the demodulated
+    /// signal as is. No CRC, whitening or packet length handling, sync word detection only
+    /// in synchronous mode.
+    ///
+    /// Returns the settings it changed, for `exit_serial_mode` to restore.
+    pub fn enter_serial_mode(
+        &mut self,
+        mode: SerialMode,
+    ) -> Result<PacketModeSettings, Error<SpiE>> {
+        self.set_radio_mode(RadioMode::Idle)?;
+        let saved = PacketModeSettings {
+            iocfg2: self.0.read_register(Config::IOCFG2)?,
+            iocfg0: self.0.read_register(Config::IOCFG0)?,
+            pktctrl0: self.0.read_register(Config::PKTCTRL0)?,
+        };
+        let (pkt_format, data_out) = match mode {
+            SerialMode::Synchronous => (PKT_FORMAT_SYNC_SERIAL, GdoCfg::SERIAL_SYNC_DATA_OUT),
+            SerialMode::Asynchronous => (PKT_FORMAT_ASYNC_SERIAL, GdoCfg::SERIAL_DATA_OUT),
+        };
+        self.0.write_register(
+            Config::PKTCTRL0,
+            PKTCTRL0::default()
+                .white_data(0)
+                .pkt_format(pkt_format)
+                .crc_en(0)
+                .length_config(LengthConfig::INFINITE.value())
+                .bits(),
+        )?;
+        self.set_gdo_config(GdoPin::Gdo0, data_out, false)?;
+        if mode == SerialMode::Synchronous {
+            self.set_gdo_config(GdoPin::Gdo2, GdoCfg::SERIAL_CLOCK, false)?;
+        }
+        Ok(saved)
+    }
+
+    /// Go back to IDLE and to the packet mode settings from before `enter_serial_mode`.
+    pub fn exit_serial_mode(&mut self, saved: PacketModeSettings) -> Result<(), Error<SpiE>> {
+        self.set_radio_mode(RadioMode::Idle)?;
+        self.0.write_register(Config::IOCFG2, saved.iocfg2)?;
+        self.0.write_register(Config::IOCFG0, saved.iocfg0)?;
+        self.0.write_register(Config::PKTCTRL0, saved.pktctrl0)?;
+        Ok(())
+    }
+
+    /// Sets the minimum number of preamble bytes to be transmitted.
+    pub fn set_preamble_length(&mut self, preamble: NumPreamble) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(Config::MDMCFG1, |r| {
+            MDMCFG1(r).modify().num_preamble(preamble.value()).bits()
+        })?;
+        Ok(())
+    }
+
+    /// Enable convolutional forward error correction with interleaving.
+    ///
+    /// Only supported with fixed length packets (see `set_packet_length`), fails with
+    /// `InvalidConfig` otherwise. Halves the net data rate.
+    pub fn set_fec(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        if enable {
+            let pktctrl0 = PKTCTRL0(self.0.read_register(Config::PKTCTRL0)?);
+            if pktctrl0.length_config() != LengthConfig::FIXED.value() {
+                return Err(Error::InvalidConfig);
+            }
+        }
+        self.0.modify_register(Config::MDMCFG1, |r| {
+            MDMCFG1(r).modify().fec_en(enable as u8).bits()
+        })?;
+        Ok(())
+    }
+
+    /// Enable data whitening, XORing the payload and CRC with a PN9 sequence on air.
+    pub fn set_data_whitening(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(Config::PKTCTRL0, |r| {
+            PKTCTRL0(r).modify().white_data(enable as u8).bits()
+        })?;
+        Ok(())
+    }
+
+    /// Configure CRC calculation and checking, and what happens to packets failing the check.
+    pub fn set_crc(&mut self, mode: CrcMode) -> Result<(), Error<SpiE>> {
+        let (crc_en, autoflush) = match mode {
+            CrcMode::Disabled => (0, 0),
+            CrcMode::Enabled => (1, 0),
+            CrcMode::EnabledAutoflush => (1, 1),
+        };
+        self.0.modify_config_burst(Config::PKTCTRL1, |[pktctrl1, pktctrl0]: &mut [u8; 2]| {
+            *pktctrl1 = PKTCTRL1(*pktctrl1).modify().crc_autoflush(autoflush).bits();
+            *pktctrl0 = PKTCTRL0(*pktctrl0).modify().crc_en(crc_en).bits();
+        })?;
+        Ok(())
+    }
+
+    /// Only accept a sync word after a preamble of quality above 4·`pqt` (0 to 7, 0 to always
+    /// accept it).
+    ///
+    /// The preamble quality estimator counts up by 1 on each bit differing from the previous
+    /// one and down by 8 otherwise, so noise rarely reaches the threshold. Whether the current
+    /// signal does is `PacketStatus::pqt_reached`, also available on a GDO pin as
+    /// `GdoCfg::PQT_REACHED`.
+    pub fn set_preamble_quality_threshold(&mut self, pqt: u8) -> Result<(), Error<SpiE>> {
+        self.0
+            .modify_register(Config::PKTCTRL1, |r| PKTCTRL1(r).modify().pqt(pqt.min(7)).bits())?;
+        Ok(())
+    }
+
+    /// Append the RSSI and LQI of each received packet after its payload in the RX FIFO.
+    ///
+    /// The receive APIs then take them from there, rather than from the RSSI and LQI
+    /// registers, which may have changed since the packet ended.
+    pub fn set_append_status(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(Config::PKTCTRL1, |r| {
+            PKTCTRL1(r).modify().append_status(enable as u8).bits()
+        })?;
+        Ok(())
+    }
+
+    /// RSSI, LQI and CRC status of the last packet read by the receive APIs.
+    pub fn last_packet_info(&self) -> Option<PacketInfo> {
+        self.0.last_packet
+    }
+
     /// Configure device address, and address filtering.
     pub fn set_address_filter(&mut self, filter: AddressFilter) -> Result<(), Error<SpiE>> {
         use lowlevel::types::AddressCheck as AC;
@@ -197,10 +1069,11 @@ impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI>
             AddressFilter::DeviceLowBroadcast(addr) => (AC::SELF_LOW_BROADCAST, addr),
             AddressFilter::DeviceHighLowBroadcast(addr) => (AC::SELF_HIGH_LOW_BROADCAST, addr),
         };
-        self.0.modify_register(Config::PKTCTRL1, |r| {
-            PKTCTRL1(r).modify().adr_chk(mode.value()).bits()
+        // PKTCTRL1, PKTCTRL0, ADDR
+        self.0.modify_config_burst(Config::PKTCTRL1, |[pktctrl1, _, a]: &mut [u8; 3]| {
+            *pktctrl1 = PKTCTRL1(*pktctrl1).modify().adr_chk(mode.value()).bits();
+            *a = addr;
         })?;
-        self.0.write_register(Config::ADDR, addr)?;
         Ok(())
     }
 
@@ -213,24 +1086,116 @@ impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI>
             PacketLength::Variable(max_limit) => (LC::VARIABLE, max_limit),
             PacketLength::Infinite => (LC::INFINITE, PKTLEN::default().bits()),
         };
-        self.0.modify_register(Config::PKTCTRL0, |r| {
-            PKTCTRL0(r).modify().length_config(format.value()).bits()
+        // PKTLEN, PKTCTRL1, PKTCTRL0
+        self.0.modify_config_burst(Config::PKTLEN, |[len, _, pktctrl0]: &mut [u8; 3]| {
+            *len = pktlen;
+            *pktctrl0 = PKTCTRL0(*pktctrl0).modify().length_config(format.value()).bits();
         })?;
-        self.0.write_register(Config::PKTLEN, pktlen)?;
         Ok(())
     }
 
     /// Set radio in Receive/Transmit/Idle/Calibrate mode.
-    /// 
+    ///
     /// Blocks until radio is in that mode.
     pub fn set_radio_mode(&mut self, radio_mode: RadioMode) -> Result<(), Error<SpiE>> {
+        self.0.trace_event(TraceEvent::Mode(radio_mode));
         let target = self.send_radio_mode_strobe(radio_mode)?;
-        self.await_machine_state(target)
-    }
+        self.await_machine_state(target)?;
+        if target == MachineState::IDLE {
+            self.0.state = Some(target);
+        }
+        Ok(())
+    }
+
+    /// State the radio goes to when a packet has been sent, `None` if it stays in TX.
+    pub(crate) fn txoff_state(&mut self) -> Result<Option<MachineState>, Error<SpiE>> {
+        Ok(match MCSM1(self.0.read_register(Config::MCSM1)?).txoff_mode() {
+            0 => Some(MachineState::IDLE),
+            1 => Some(MachineState::FSTXON),
+            2 => None,
+            _ => Some(MachineState::RX),
+        })
+    }
+
+    /// Whether the packet being sent is done, `txoff` from `txoff_state`.
+    ///
+    /// When staying in TX, done once the TX FIFO is drained. A TX FIFO underflow is recovered
+    /// from and fails with `TxUnderflow`, see `recover_tx_underflow`.
+    pub(crate) fn is_transmit_done(
+        &mut self,
+        txoff: Option<MachineState>,
+    ) -> Result<bool, Error<SpiE>> {
+        match txoff {
+            Some(target) => match self.get_marc_state()? {
+                state if state == MachineState::TXFIFO_UNDERFLOW.value() => {
+                    self.recover_tx_underflow()
+                }
+                state => Ok(state == target.value()),
+            },
+            None => match self.tx_bytes_pending()? {
+                fifo if fifo.error => self.recover_tx_underflow(),
+                fifo => Ok(fifo.bytes == 0),
+            },
+        }
+    }
+
+    /// Wait for the packet being sent to be done, then flush the TX FIFO if the radio went
+    /// back to IDLE.
+    pub(crate) fn finish_transmit(&mut self) -> Result<(), Error<SpiE>> {
+        let txoff = self.txoff_state()?;
+        let mut stalled = 0;
+        while !self.is_transmit_done(txoff)? {
+            // A packet can take seconds at low data rates, only give up while not in TX
+            if !self.is_state_machine(MachineState::TX)? {
+                stalled += 1;
+                if stalled == STATE_POLL_BUDGET {
+                    return Err(Error::Timeout);
+                }
+            }
+        }
+        self.notify_transmit_done();
+        if txoff == Some(MachineState::IDLE) {
+            self.flush_tx()?;
+        }
+        Ok(())
+    }
+
+    /// The mode the radio is in, `None` if it's in neither RX, TX nor IDLE.
+    ///
+    /// Answered without SPI access when the radio was last put in IDLE by `set_radio_mode`.
+    /// Otherwise reads MARCSTATE, RX and TX can be left on their own (packet end, RX timeout,
+    /// FIFO overflow or underflow).
+    pub fn current_mode(&mut self) -> Result<Option<RadioMode>, Error<SpiE>> {
+        let state = match self.0.state {
+            Some(state) => state,
+            None => match MachineState::from_value(self.get_marc_state()?) {
+                Some(state) => state,
+                None => return Ok(None),
+            },
+        };
+        Ok(match state {
+            MachineState::RX => Some(RadioMode::Receive),
+            MachineState::TX => Some(RadioMode::Transmit),
+            MachineState::IDLE => Some(RadioMode::Idle),
+            _ => None,
+        })
+    }
+
+    /// Whether the radio is in `radio_mode` (Calibrate counts as Idle), see `current_mode`.
+    pub fn is_in(&mut self, radio_mode: RadioMode) -> Result<bool, Error<SpiE>> {
+        let radio_mode = match radio_mode {
+            RadioMode::Calibrate => RadioMode::Idle,
+            mode => mode,
+        };
+        Ok(self.current_mode()? == Some(radio_mode))
+    }
     /// Send command strobe for Receive/Transmit/Idle/Calibrate mode.
-    /// 
+    ///
     /// Returns machine state for that RadioMode.
-    pub fn send_radio_mode_strobe(&mut self, radio_mode: RadioMode)-> Result<MachineState, Error<SpiE>> {
+    pub fn send_radio_mode_strobe(
+        &mut self,
+        radio_mode: RadioMode,
+    ) -> Result<MachineState, Error<SpiE>> {
         Ok(match radio_mode {
             RadioMode::Receive => {
                 // self.set_radio_mode(RadioMode::Idle)?;
@@ -258,47 +1223,242 @@ impl<SPI: SpiDevice<u8, Error = SpiE>, SpiE> Cc1101<SPI>
     pub fn reset(&mut self) -> Result<(), Error<SpiE>> {
         Ok(self.0.write_strobe(Command::SRES)?)
     }
+    /// Flush the RX FIFO.
+    ///
+    /// Only valid in IDLE or RX FIFO overflow, fails with `InvalidState` (without flushing)
+    /// otherwise, flushing in RX would corrupt the packet being received. Use `abort_receive`
+    /// to leave RX and flush.
     pub fn flush_rx(&mut self) -> Result<(), Error<SpiE>> {
-        Ok(self.0.write_strobe(Command::SFRX)?)
+        self.flush(MachineState::RXFIFO_OVERFLOW, Command::SFRX)
     }
+    /// Flush the TX FIFO.
+    ///
+    /// Only valid in IDLE or TX FIFO underflow, same as `flush_rx`. Use `abort_transmit` to
+    /// leave TX and flush.
     pub fn flush_tx(&mut self) -> Result<(), Error<SpiE>> {
-        Ok(self.0.write_strobe(Command::SFTX)?)
+        self.flush(MachineState::TXFIFO_UNDERFLOW, Command::SFTX)
+    }
+
+    fn flush(&mut self, fifo_error: MachineState, flush: Command) -> Result<(), Error<SpiE>> {
+        if self.0.state != Some(MachineState::IDLE) {
+            let state = self.get_marc_state()?;
+            if state != MachineState::IDLE.value() && state != fifo_error.value() {
+                self.0.trace_event(TraceEvent::InvalidState(state));
+                return Err(Error::InvalidState(state));
+            }
+        }
+        Ok(self.0.write_strobe(flush)?)
     }
+    /// Abort any ongoing transmission: go to IDLE and flush the TX FIFO.
+    ///
+    /// Safe to call in any state, and to call again if interrupted (e.g. from a timeout or
+    /// cancellation path): the FIFO is only flushed once the radio is in IDLE, or from the
+    /// underflow state where flushing is what exits it.
+    pub fn abort_transmit(&mut self) -> Result<(), Error<SpiE>> {
+        self.abort(MachineState::TXFIFO_UNDERFLOW, Command::SFTX)
+    }
+
+    /// Abort any ongoing reception: go to IDLE and flush the RX FIFO.
+    ///
+    /// Same guarantees as `abort_transmit`.
+    pub fn abort_receive(&mut self) -> Result<(), Error<SpiE>> {
+        self.abort(MachineState::RXFIFO_OVERFLOW, Command::SFRX)
+    }
+
+    /// Recover from an RX FIFO overflow: flush the RX FIFO and go back to RX.
+    ///
+    /// Always fails with `RxOverflow`, for the receive paths to report the dropped data.
+    pub(crate) fn recover_rx_overflow<T>(&mut self) -> Result<T, Error<SpiE>> {
+        self.0.trace_event(TraceEvent::RxOverflow);
+        self.abort_receive()?;
+        self.notify_recovery(Health::RecoveredRxOverflow);
+        self.set_radio_mode(RadioMode::Receive)?;
+        Err(Error::RxOverflow)
+    }
+
+    /// Recover from a TX FIFO underflow: flush the TX FIFO, leaving the radio in IDLE.
+    ///
+    /// Always fails with `TxUnderflow`, for the transmit paths to report the lost packet.
+    pub(crate) fn recover_tx_underflow<T>(&mut self) -> Result<T, Error<SpiE>> {
+        self.0.trace_event(TraceEvent::TxUnderflow);
+        self.abort_transmit()?;
+        self.notify_recovery(Health::RecoveredTxUnderflow);
+        Err(Error::TxUnderflow)
+    }
+
+    fn abort(&mut self, fifo_error: MachineState, flush: Command) -> Result<(), Error<SpiE>> {
+        if !self.is_state_machine(fifo_error)? {
+            self.0.write_strobe(Command::SIDLE)?;
+            self.await_machine_state(MachineState::IDLE)?;
+        }
+        self.0.write_strobe(flush)?;
+        self.await_machine_state(MachineState::IDLE)?;
+        self.0.state = Some(MachineState::IDLE);
+        Ok(())
+    }
+
     /// Sends a no-op continuously
-    /// 
-    /// Blocks until chip is ready.
+    ///
+    /// Blocks until chip is ready, fails with `Timeout` if it doesn't get ready within
+    /// `STATE_POLL_BUDGET` attempts.
     pub fn wake_up_wait(&mut self) -> Result<(), Error<SpiE>> {
-        while self.0.chip_rdyn()? == false {}
+        for _ in 0..STATE_POLL_BUDGET {
+            if self.0.chip_rdyn()? {
+                return Ok(());
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Same as `wake_up_wait`, giving up after `timeout_us`.
+    pub fn wake_up_wait_timeout<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        timeout_us: u32,
+    ) -> Result<(), Error<SpiE>> {
+        let mut waited_us = 0;
+        while !self.0.chip_rdyn()? {
+            if waited_us >= timeout_us {
+                return Err(Error::Timeout);
+            }
+            delay.delay_us(self.0.poll_interval_us);
+            waited_us = waited_us.saturating_add(self.0.poll_interval_us);
+        }
         Ok(())
     }
     /// Enter pwr down mode when CSn goes high
+    ///
+    /// The PATABLE (past its first entry) and FSTEST to TEST0 are lost, see `sleep`.
     pub fn power_down(&mut self) -> Result<(), Error<SpiE>> {
         Ok(self.0.write_strobe(Command::SPWD)?)
     }
+
+    /// Go to IDLE, then SLEEP, returning the registers SLEEP loses, for `wake`.
+    pub fn sleep(&mut self) -> Result<SleepSnapshot, Error<SpiE>> {
+        self.set_radio_mode(RadioMode::Idle)?;
+        let mut snapshot = SleepSnapshot {
+            patable: [0; PATABLE_SIZE],
+            test: [0; 6],
+        };
+        self.0.read_patable(&mut snapshot.patable)?;
+        self.0.read_config_burst(Config::FSTEST, &mut snapshot.test)?;
+        self.power_down()?;
+        Ok(snapshot)
+    }
+
+    /// Wake up from SLEEP and restore the registers saved by `sleep`. The radio is in IDLE.
+    pub fn wake(&mut self, snapshot: &SleepSnapshot) -> Result<(), Error<SpiE>> {
+        self.wake_up_wait()?;
+        self.0.write_patable(&snapshot.patable)?;
+        self.0.write_config_burst(Config::FSTEST, &snapshot.test)?;
+        Ok(())
+    }
+    /// Transmit an unmodulated carrier at the current frequency and output power, until
+    /// `stop_test_transmission`, for regulatory and antenna measurements.
+    ///
+    /// OOK with the same PA power for both symbols, fed with random data (no FIFO involved).
+    pub fn start_carrier_test(&mut self) -> Result<TestTransmission, Error<SpiE>> {
+        self.start_test_transmission(true)
+    }
+
+    /// Transmit PN9 data from the built-in generator with the current settings, until
+    /// `stop_test_transmission`, for spectrum mask and bit error rate measurements.
+    ///
+    /// The FIFO isn't involved, nor are preamble, sync word and CRC.
+    pub fn start_pn9_test(&mut self) -> Result<TestTransmission, Error<SpiE>> {
+        self.start_test_transmission(false)
+    }
+
+    /// Stop the transmission started by `start_carrier_test` or `start_pn9_test` and restore
+    /// the settings it changed. The radio is in IDLE.
+    pub fn stop_test_transmission(&mut self, saved: TestTransmission) -> Result<(), Error<SpiE>> {
+        self.set_radio_mode(RadioMode::Idle)?;
+        self.0.write_register(Config::MDMCFG2, saved.mdmcfg2)?;
+        self.0.write_register(Config::PKTCTRL0, saved.pktctrl0)?;
+        self.0.write_register(Config::FREND0, saved.frend0)?;
+        self.0.write_patable(&saved.patable)?;
+        Ok(())
+    }
+
+    /// Transmit random data, with the carrier on for both symbols if `carrier`.
+    fn start_test_transmission(&mut self, carrier: bool) -> Result<TestTransmission, Error<SpiE>> {
+        self.set_radio_mode(RadioMode::Idle)?;
+        let mut saved = TestTransmission {
+            mdmcfg2: self.0.read_register(Config::MDMCFG2)?,
+            pktctrl0: self.0.read_register(Config::PKTCTRL0)?,
+            frend0: self.0.read_register(Config::FREND0)?,
+            patable: [0; PATABLE_SIZE],
+        };
+        self.0.read_patable(&mut saved.patable)?;
+        self.0.write_register(
+            Config::PKTCTRL0,
+            PKTCTRL0(saved.pktctrl0)
+                .modify()
+                .pkt_format(PKT_FORMAT_RANDOM)
+                .length_config(LengthConfig::INFINITE.value())
+                .bits(),
+        )?;
+        if carrier {
+            self.0.write_register(
+                Config::MDMCFG2,
+                MDMCFG2(saved.mdmcfg2)
+                    .modify()
+                    .mod_format(ModFormat::MOD_ASK_OOK.value())
+                    .manchester_en(0)
+                    .bits(),
+            )?;
+            let power = saved.patable[FREND0(saved.frend0).pa_power() as usize];
+            self.set_pa_ramp(&[power, power])?;
+        }
+        self.set_radio_mode(RadioMode::Transmit)?;
+        Ok(saved)
+    }
+
     pub fn to_idle(&mut self) -> Result<(), Error<SpiE>> {
-        Ok(self.set_radio_mode(RadioMode::Idle)?)
+        self.set_radio_mode(RadioMode::Idle)
     }
     pub fn to_tx(&mut self) -> Result<(), Error<SpiE>> {
-        Ok(self.set_radio_mode(RadioMode::Transmit)?)
+        self.set_radio_mode(RadioMode::Transmit)
     }
     pub fn to_rx(&mut self) -> Result<(), Error<SpiE>> {
-        Ok(self.set_radio_mode(RadioMode::Receive)?)
+        self.set_radio_mode(RadioMode::Receive)
     }
 
-
+    /// Poll MARCSTATE until the radio is in `target`, fails with `Timeout` after
+    /// `STATE_POLL_BUDGET` reads.
     pub fn await_machine_state(&mut self, target: MachineState) -> Result<(), Error<SpiE>> {
-        loop {
+        for _ in 0..STATE_POLL_BUDGET {
             if self.is_state_machine(target)? {
-                break;
+                return Ok(());
             }
         }
+        Err(Error::Timeout)
+    }
+
+    /// Same as `await_machine_state`, giving up after `timeout_us`.
+    pub fn await_machine_state_timeout<D: DelayNs>(
+        &mut self,
+        target: MachineState,
+        delay: &mut D,
+        timeout_us: u32,
+    ) -> Result<(), Error<SpiE>> {
+        let mut waited_us = 0;
+        while !self.is_state_machine(target)? {
+            if waited_us >= timeout_us {
+                return Err(Error::Timeout);
+            }
+            delay.delay_us(self.0.poll_interval_us);
+            waited_us = waited_us.saturating_add(self.0.poll_interval_us);
+        }
         Ok(())
     }
-    pub fn is_state_machine(&mut self,target: MachineState) -> Result<bool, Error<SpiE>> {
+    pub fn is_state_machine(&mut self, target: MachineState) -> Result<bool, Error<SpiE>> {
         Ok(target.value() == self.get_marc_state()?)
     }
     pub fn get_marc_state(&mut self) -> Result<u8, Error<SpiE>> {
-        Ok(MARCSTATE(self.0.read_register(Status::MARCSTATE)?).marc_state())
+        let state = MARCSTATE(self.0.read_register(Status::MARCSTATE)?).marc_state();
+        self.0.trace_event(TraceEvent::State(state));
+        Ok(state)
     }
 }
 
@@ -350,6 +1510,240 @@ pub enum RadioMode {
     Calibrate,
 }
 
+/// General purpose digital output pin.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GdoPin {
+    Gdo0,
+    /// Shared with the SPI SO pin, only driven while CSn is high.
+    Gdo1,
+    Gdo2,
+}
+
+/// Serial data mode, see `Cc1101::enter_serial_mode`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SerialMode {
+    /// Data clocked by the radio, output on GDO2.
+    Synchronous,
+    /// Raw data, timed by the MCU.
+    Asynchronous,
+}
+
+/// Settings changed by `Cc1101::enter_serial_mode`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PacketModeSettings {
+    iocfg2: u8,
+    iocfg0: u8,
+    pktctrl0: u8,
+}
+
+/// RXBYTES or TXBYTES, decoded.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FifoStatus {
+    /// Number of bytes in the FIFO.
+    pub bytes: u8,
+    /// RX FIFO overflow or TX FIFO underflow.
+    pub error: bool,
+}
+
+/// PKTSTATUS, decoded.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PacketStatus {
+    /// The last CRC comparison matched, cleared when entering or restarting RX.
+    pub crc_ok: bool,
+    /// Carrier sense.
+    pub carrier_sense: bool,
+    /// Preamble quality threshold reached.
+    pub pqt_reached: bool,
+    /// Channel is clear.
+    pub clear_channel: bool,
+    /// Sync word found, until the end of the packet.
+    pub sync_word: bool,
+    /// Current GDO2 level.
+    pub gdo2: bool,
+    /// Current GDO0 level.
+    pub gdo0: bool,
+}
+
+impl From<u8> for PacketStatus {
+    fn from(value: u8) -> Self {
+        let pktstatus = PKTSTATUS(value);
+        PacketStatus {
+            crc_ok: pktstatus.crc_ok() != 0,
+            carrier_sense: pktstatus.cs() != 0,
+            pqt_reached: pktstatus.pqt_reached() != 0,
+            clear_channel: pktstatus.cca() != 0,
+            sync_word: pktstatus.sfd() != 0,
+            gdo2: pktstatus.gdo2() != 0,
+            gdo0: pktstatus.gdo0() != 0,
+        }
+    }
+}
+
+/// Settings changed by `Cc1101::start_carrier_test` and `Cc1101::start_pn9_test`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TestTransmission {
+    mdmcfg2: u8,
+    pktctrl0: u8,
+    frend0: u8,
+    patable: [u8; PATABLE_SIZE],
+}
+
+/// Registers lost in SLEEP, saved by `Cc1101::sleep`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SleepSnapshot {
+    pub patable: [u8; PATABLE_SIZE],
+    /// FSTEST, PTEST, AGCTEST, TEST2, TEST1 and TEST0 (0x29 to 0x2E).
+    pub test: [u8; 6],
+}
+
+/// Reception details of a packet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PacketInfo {
+    /// RSSI (in dBm).
+    pub rssi_dbm: i16,
+    /// Link Quality Indicator, lower is better.
+    pub lqi: u8,
+    pub crc_ok: bool,
+}
+
+impl PacketInfo {
+    /// Decode the two status bytes appended to a packet (or the RSSI and LQI registers).
+    pub fn from_status([rssi, lqi]: [u8; 2]) -> Self {
+        let lqi = LQI(lqi);
+        PacketInfo {
+            rssi_dbm: rssi_to_dbm(rssi),
+            lqi: lqi.lqi(),
+            crc_ok: lqi.crc_ok() != 0,
+        }
+    }
+}
+
+/// A packet read by the receive APIs, with the details of its reception.
+///
+/// RSSI and LQI come from the status bytes appended to the packet when enabled (see
+/// `Cc1101::set_append_status`), from the RSSI and LQI registers otherwise.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ReceivedPacket {
+    /// Payload length (in bytes), without the length byte.
+    pub len: usize,
+    /// RSSI (in dBm).
+    pub rssi_dbm: i16,
+    /// Link Quality Indicator, lower is better.
+    pub lqi: u8,
+    pub crc_ok: bool,
+    /// Destination address, when address filtering is enabled (also the first payload byte).
+    pub address: Option<u8>,
+}
+
+impl ReceivedPacket {
+    pub(crate) fn new(len: usize, info: PacketInfo, address: Option<u8>) -> Self {
+        ReceivedPacket {
+            len,
+            rssi_dbm: info.rssi_dbm,
+            lqi: info.lqi,
+            crc_ok: info.crc_ok,
+            address,
+        }
+    }
+}
+
+/// Chip status byte, see `Cc1101::last_status`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ChipStatus {
+    /// The crystal is running and the chip accepts commands (CHIP_RDYn low).
+    pub ready: bool,
+    pub state: ChipState,
+    /// Whether `fifo_bytes` counts the RX FIFO (after a read access) or the TX FIFO.
+    pub rx_fifo: bool,
+    /// Bytes available in the RX FIFO, or free in the TX FIFO, saturating at 15.
+    pub fifo_bytes: u8,
+}
+
+impl ChipStatus {
+    /// Decode a status byte, received during a read access if `read`.
+    pub fn from_byte(byte: u8, read: bool) -> Self {
+        let state = match (byte >> 4) & 0x07 {
+            0 => ChipState::Idle,
+            1 => ChipState::Receive,
+            2 => ChipState::Transmit,
+            3 => ChipState::Fstxon,
+            4 => ChipState::Calibrate,
+            5 => ChipState::Settling,
+            6 => ChipState::RxFifoOverflow,
+            _ => ChipState::TxFifoUnderflow,
+        };
+        ChipStatus {
+            ready: byte & 0x80 == 0,
+            state,
+            rx_fifo: read,
+            fifo_bytes: byte & 0x0f,
+        }
+    }
+}
+
+/// Main radio state, as reported in the chip status byte.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ChipState {
+    Idle,
+    Receive,
+    Transmit,
+    /// Frequency synthesizer on, ready to transmit.
+    Fstxon,
+    /// Frequency synthesizer calibration running.
+    Calibrate,
+    /// PLL locking.
+    Settling,
+    /// RX FIFO overflowed, flush it with SFRX.
+    RxFifoOverflow,
+    /// TX FIFO underflowed, flush it with SFTX.
+    TxFifoUnderflow,
+}
+
+/// Raw values of the status registers, read one after the other.
+///
+/// Decode with the matching register types, e.g. `RXBYTES(snapshot.rxbytes).num_rxbytes()`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct StatusSnapshot {
+    pub marcstate: u8,
+    pub pktstatus: u8,
+    pub rxbytes: u8,
+    pub txbytes: u8,
+    pub rssi: u8,
+    pub lqi: u8,
+    pub freqest: u8,
+}
+
+/// Values of all configuration and status registers, see `Cc1101::dump_registers`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RegisterDump {
+    /// Configuration registers, in address order (`Config::ALL`).
+    pub config: [u8; Config::ALL.len()],
+    /// Status registers, in address order (`Status::ALL`).
+    pub status: [u8; Status::ALL.len()],
+}
+
+impl RegisterDump {
+    pub fn config(&self, reg: Config) -> u8 {
+        self.config[reg.addr() as usize]
+    }
+
+    pub fn status(&self, reg: Status) -> u8 {
+        self.status[(reg.addr() - Status::PARTNUM.addr()) as usize]
+    }
+}
+
+impl Display for RegisterDump {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (reg, value) in Config::ALL.iter().zip(self.config) {
+            writeln!(f, "{:?} {:#04x}", reg, value)?;
+        }
+        for (reg, value) in Status::ALL.iter().zip(self.status) {
+            writeln!(f, "{:?} {:#04x}", reg, value)?;
+        }
+        Ok(())
+    }
+}
+
 /// Sync word configuration.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum SyncMode {
@@ -361,6 +1755,28 @@ pub enum SyncMode {
     MatchPartialRepeated(u16),
     /// Match 16 of 16 bits of given sync word.
     MatchFull(u16),
+    /// No sync word, receive while carrier sense is above threshold (see `set_carrier_sense`),
+    /// e.g. for OOK protocols without a sync word.
+    CarrierSense,
+    /// `MatchPartial`, with carrier sense above threshold.
+    MatchPartialCarrierSense(u16),
+    /// `MatchPartialRepeated`, with carrier sense above threshold.
+    MatchPartialRepeatedCarrierSense(u16),
+    /// `MatchFull`, with carrier sense above threshold.
+    MatchFullCarrierSense(u16),
+}
+
+/// CRC configuration.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CrcMode {
+    /// No CRC.
+    Disabled,
+    /// CRC appended in TX and checked in RX, packets failing the check are kept.
+    Enabled,
+    /// Same as `Enabled`, packets failing the check are flushed from the RX FIFO.
+    ///
+    /// Only for packets that fit in the RX FIFO.
+    EnabledAutoflush,
 }
 
 /// Target amplitude for AGC.
@@ -410,3 +1826,263 @@ impl From<FilterLength> for u8 {
         value as Self
     }
 }
+
+/// AGC settings, the defaults are the reset values.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AgcConfig {
+    /// Number of the highest DVGA gain settings that can't be used, from 0 to 3.
+    pub dvga_gain_reduction: u8,
+    /// Maximum LNA + LNA 2 gain below the maximum possible gain, from 0 (no reduction) to 7
+    /// (17.1 dB below).
+    pub lna_gain_reduction: u8,
+    pub target: TargetAmplitude,
+    /// Decrease the LNA gain first, rather than LNA 2.
+    pub lna_priority: bool,
+    pub hysteresis: AgcHysteresis,
+    pub wait_time: AgcWaitTime,
+    pub freeze: AgcFreeze,
+    pub filter_length: FilterLength,
+}
+
+impl Default for AgcConfig {
+    fn default() -> Self {
+        AgcConfig {
+            dvga_gain_reduction: 0,
+            lna_gain_reduction: 0,
+            target: TargetAmplitude::Db33,
+            lna_priority: true,
+            hysteresis: AgcHysteresis::Medium,
+            wait_time: AgcWaitTime::Samples16,
+            freeze: AgcFreeze::Normal,
+            filter_length: FilterLength::Samples16,
+        }
+    }
+}
+
+/// Hysteresis on the magnitude deviation of the AGC.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum AgcHysteresis {
+    /// No hysteresis, small symmetric dead zone, high gain.
+    None = 0,
+    /// Low hysteresis, small asymmetric dead zone, medium gain.
+    Low = 1,
+    /// Medium hysteresis, medium asymmetric dead zone, medium gain.
+    Medium = 2,
+    /// Large hysteresis, large asymmetric dead zone, low gain.
+    High = 3,
+}
+
+impl From<AgcHysteresis> for u8 {
+    fn from(value: AgcHysteresis) -> Self {
+        value as Self
+    }
+}
+
+/// Channel filter samples from a gain adjustment until the AGC accumulates new samples.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum AgcWaitTime {
+    Samples8 = 0,
+    Samples16 = 1,
+    Samples24 = 2,
+    Samples32 = 3,
+}
+
+impl From<AgcWaitTime> for u8 {
+    fn from(value: AgcWaitTime) -> Self {
+        value as Self
+    }
+}
+
+/// When the AGC gain is frozen.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum AgcFreeze {
+    /// Always adjust the gain when required.
+    Normal = 0,
+    /// Freeze the gain when a sync word is found.
+    OnSyncWord = 1,
+    /// Freeze the analog gain, keep adjusting the digital gain.
+    Analog = 2,
+    /// Freeze both the analog and digital gain.
+    All = 3,
+}
+
+impl From<AgcFreeze> for u8 {
+    fn from(value: AgcFreeze) -> Self {
+        value as Self
+    }
+}
+
+/// Clock recovery loop settings, the defaults are the reset values.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BitSyncConfig {
+    /// Integral gain before the sync word, 1 to 4 times KI.
+    pub pre_ki: u8,
+    /// Proportional gain before the sync word, 1 to 4 times KP.
+    pub pre_kp: u8,
+    /// After the sync word, integral gain of KI/2 rather than `pre_ki`.
+    pub post_ki_halved: bool,
+    /// After the sync word, proportional gain of KP rather than `pre_kp`.
+    pub post_kp_reduced: bool,
+    /// Data rate offset the loop can compensate for.
+    pub limit: BitSyncLimit,
+}
+
+impl Default for BitSyncConfig {
+    fn default() -> Self {
+        BitSyncConfig {
+            pre_ki: 2,
+            pre_kp: 3,
+            post_ki_halved: true,
+            post_kp_reduced: true,
+            limit: BitSyncLimit::Disabled,
+        }
+    }
+}
+
+/// Saturation point of the data rate offset compensation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum BitSyncLimit {
+    /// No data rate offset compensation.
+    Disabled = 0,
+    /// ±3.125% data rate offset.
+    Percent3_125 = 1,
+    /// ±6.25% data rate offset.
+    Percent6_25 = 2,
+    /// ±12.5% data rate offset.
+    Percent12_5 = 3,
+}
+
+impl From<BitSyncLimit> for u8 {
+    fn from(value: BitSyncLimit) -> Self {
+        value as Self
+    }
+}
+
+/// Carrier sense thresholds, carrier sense asserts when either is met.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CarrierSense {
+    /// RSSI threshold relative to the AGC magnitude target (in dB, from -7 to 7), `None` to
+    /// disable it.
+    pub absolute_db: Option<i8>,
+    /// Threshold on a sudden RSSI increase.
+    pub relative: RelativeThreshold,
+}
+
+/// Relative carrier sense threshold.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum RelativeThreshold {
+    Disabled = 0,
+    /// 6 dB increase in RSSI.
+    Db6 = 1,
+    /// 10 dB increase in RSSI.
+    Db10 = 2,
+    /// 14 dB increase in RSSI.
+    Db14 = 3,
+}
+
+impl From<RelativeThreshold> for u8 {
+    fn from(value: RelativeThreshold) -> Self {
+        value as Self
+    }
+}
+
+/// Clear channel assessment mode.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum CcaMode {
+    /// The channel is always clear.
+    Always = 0,
+    /// Clear if the RSSI is below the carrier sense threshold.
+    RssiBelowThreshold = 1,
+    /// Clear unless currently receiving a packet.
+    UnlessReceiving = 2,
+    /// Clear if the RSSI is below the carrier sense threshold, unless currently receiving a
+    /// packet.
+    RssiBelowThresholdUnlessReceiving = 3,
+}
+
+impl From<CcaMode> for u8 {
+    fn from(value: CcaMode) -> Self {
+        value as Self
+    }
+}
+
+/// State the radio goes to when a packet has been received or sent.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum OffMode {
+    Idle = 0,
+    /// Frequency synthesizer on, ready to transmit.
+    Fstxon = 1,
+    Transmit = 2,
+    Receive = 3,
+}
+
+impl From<OffMode> for u8 {
+    fn from(value: OffMode) -> Self {
+        value as Self
+    }
+}
+
+/// RX termination settings, the defaults are the reset values (no timeout).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RxTime {
+    /// RX_TIME, from 0 (the longest, 3.6% of the event 0 period at WOR_RES 0) to 6, halving
+    /// each step and with each WOR_RES step. `None` waits for a packet without timeout.
+    pub timeout: Option<u8>,
+    /// What keeps the radio in RX once the timeout expires.
+    pub qualifier: RxTimeQualifier,
+    /// Terminate RX right away without carrier sense (in the first 8 symbols with OOK/ASK).
+    pub rssi_termination: bool,
+}
+
+impl Default for RxTime {
+    fn default() -> Self {
+        RxTime {
+            timeout: None,
+            qualifier: RxTimeQualifier::SyncWord,
+            rssi_termination: false,
+        }
+    }
+}
+
+/// Condition for staying in RX at the RX timeout.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum RxTimeQualifier {
+    /// A sync word was found.
+    SyncWord = 0,
+    /// A sync word was found, or the preamble quality threshold is reached.
+    SyncWordOrPreamble = 1,
+}
+
+impl From<RxTimeQualifier> for u8 {
+    fn from(value: RxTimeQualifier) -> Self {
+        value as Self
+    }
+}
+
+/// RX attenuation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum RxAttenuation {
+    /// 0 dB
+    Db0 = 0,
+    /// 6 dB
+    Db6 = 1,
+    /// 12 dB
+    Db12 = 2,
+    /// 18 dB
+    Db18 = 3,
+}
+
+impl From<RxAttenuation> for u8 {
+    fn from(value: RxAttenuation) -> Self {
+        value as Self
+    }
+}
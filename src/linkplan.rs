@@ -0,0 +1,189 @@
+//! Link planning from application requirements.
+//!
+//! [`plan_link`] picks coherent modem settings for a range class, payload size and latency
+//! budget in a frequency band: data rate, deviation and modulation from the datasheet's
+//! reference settings, the narrowest channel filter fitting the signal and crystal tolerance,
+//! preamble length and output power. The resulting [`LinkPlan`] is applied with
+//! [`Cc1101::apply_link_plan`].
+//!
+//! ```
+//! use cc1101::channels::Band;
+//! use cc1101::linkplan::{plan_link, LinkRequirements, RangeClass};
+//!
+//! let plan = plan_link(&LinkRequirements {
+//!     range: RangeClass::Long,
+//!     payload_len: 20,
+//!     latency_budget_us: 500_000,
+//!     band: Band::SRD_868,
+//!     crystal_ppm: 20,
+//! });
+//! assert_eq!(plan.map(|plan| plan.data_rate), Some(1_200));
+//! ```
+
+use crate::channels::Band;
+use crate::lowlevel::convert::*;
+use crate::lowlevel::registers::*;
+use crate::lowlevel::types::*;
+use crate::{Cc1101, Error, Modulation};
+use hal::spi::SpiDevice;
+
+/// How far the link has to reach, trading data rate for sensitivity.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum RangeClass {
+    /// Same room, fast: 250 kBaud at 0 dBm.
+    Short,
+    /// Same building: 38.4 kBaud at 10 dBm.
+    Medium,
+    /// Across buildings, slow: 1.2 kBaud at 10 dBm.
+    Long,
+}
+
+/// What the application needs from the link.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LinkRequirements {
+    pub range: RangeClass,
+    /// Payload length (in bytes), without length byte or CRC.
+    pub payload_len: u8,
+    /// Longest acceptable time on air for a packet (in microseconds).
+    pub latency_budget_us: u32,
+    /// The link is planned at the center of the band.
+    pub band: Band,
+    /// Crystal tolerance of both ends (in ppm).
+    pub crystal_ppm: u32,
+}
+
+/// Recommended settings.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LinkPlan {
+    /// Range class the settings are for, shorter than required when the latency budget
+    /// didn't allow for a slower rate.
+    pub range: RangeClass,
+    pub frequency_hz: u64,
+    pub modulation: Modulation,
+    pub data_rate: u64,
+    pub deviation: u64,
+    /// Channel filter bandwidth (in Hertz), as the radio quantizes it.
+    pub chanbw_hz: u64,
+    pub preamble: NumPreamble,
+    /// PATABLE\[0\] value.
+    pub pa_table: u8,
+    /// Output power (in dBm).
+    pub power_dbm: i8,
+    /// Typical sensitivity at 1% packet error rate (in dBm), from the datasheet at 868 MHz.
+    pub sensitivity_dbm: i16,
+    /// Time on air of a packet (in microseconds), with a 2 byte sync word, length byte and
+    /// CRC.
+    pub airtime_us: u32,
+}
+
+impl LinkPlan {
+    /// Maximum path loss the link tolerates (in dB).
+    pub fn link_budget_db(&self) -> i16 {
+        self.power_dbm as i16 - self.sensitivity_dbm
+    }
+}
+
+/// Datasheet reference settings: range, modulation, data rate, deviation and typical
+/// sensitivity.
+const PROFILES: [(RangeClass, Modulation, u64, u64, i16); 3] = [
+    (RangeClass::Long, Modulation::GaussianFrequencyShiftKeying, 1_200, 5_200, -109),
+    (RangeClass::Medium, Modulation::GaussianFrequencyShiftKeying, 38_400, 20_000, -102),
+    (RangeClass::Short, Modulation::GaussianFrequencyShiftKeying, 250_000, 127_000, -88),
+];
+
+/// PATABLE values for 0 and 10 dBm, from the datasheet's optimum PATABLE settings.
+fn pa_table(frequency_hz: u64) -> (u8, u8) {
+    match frequency_hz {
+        0..=500_000_000 => (0x60, 0xC0),
+        500_000_001..=900_000_000 => (0x50, 0xC2),
+        _ => (0x8E, 0xC3),
+    }
+}
+
+/// Settings for the longest range meeting the latency budget, `None` if even the shortest
+/// range class is too slow.
+pub fn plan_link(req: &LinkRequirements) -> Option<LinkPlan> {
+    let frequency_hz = (req.band.start_hz + req.band.end_hz) / 2;
+    PROFILES.iter().filter(|profile| profile.0 <= req.range).find_map(|&profile| {
+        let (range, modulation, data_rate, deviation, sensitivity_dbm) = profile;
+        let preamble = match range {
+            RangeClass::Short => NumPreamble::N_8,
+            _ => NumPreamble::N_4,
+        };
+        // Preamble, sync word, length byte, payload and CRC
+        let bits = (preamble.bytes() as u32 + 2 + 1 + req.payload_len as u32 + 2) as u64 * 8;
+        let airtime_us = (bits * 1_000_000).div_ceil(data_rate) as u32;
+        if airtime_us > req.latency_budget_us {
+            return None;
+        }
+        let required =
+            data_rate + 2 * deviation + 4 * req.crystal_ppm as u64 * frequency_hz / 1_000_000;
+        let (mantissa, exponent) = chanbw_at_least(required);
+        let (pa_0dbm, pa_10dbm) = pa_table(frequency_hz);
+        let (pa_table, power_dbm) = match range {
+            RangeClass::Short => (pa_0dbm, 0),
+            _ => (pa_10dbm, 10),
+        };
+        Some(LinkPlan {
+            range,
+            frequency_hz,
+            modulation,
+            data_rate,
+            deviation,
+            chanbw_hz: to_chanbw(mantissa, exponent),
+            preamble,
+            pa_table,
+            power_dbm,
+            sensitivity_dbm,
+            airtime_us,
+        })
+    })
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Apply the settings of `plan`, the packet format and sync word are left as they are.
+    pub fn apply_link_plan(&mut self, plan: &LinkPlan) -> Result<(), Error<SpiE>> {
+        self.set_frequency(plan.frequency_hz)?;
+        self.set_modulation(plan.modulation)?;
+        self.set_data_rate(plan.data_rate)?;
+        self.set_deviation(plan.deviation)?;
+        let (mantissa, exponent) = chanbw_at_least(plan.chanbw_hz);
+        self.0.modify_register(Config::MDMCFG4, |r| {
+            MDMCFG4(r).modify().chanbw_m(mantissa).chanbw_e(exponent).bits()
+        })?;
+        self.set_preamble_length(plan.preamble)?;
+        self.set_pa_ramp(&[plan.pa_table])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::linkplan::*;
+
+    #[test]
+    fn test_plan_link() {
+        let mut req = LinkRequirements {
+            range: RangeClass::Long,
+            payload_len: 20,
+            latency_budget_us: 500_000,
+            band: Band::ISM_433,
+            crystal_ppm: 10,
+        };
+        let plan = plan_link(&req).unwrap();
+        assert_eq!(
+            (plan.range, plan.data_rate, plan.airtime_us),
+            (RangeClass::Long, 1_200, 193_334)
+        );
+        assert!(plan.chanbw_hz >= 1_200 + 2 * 5_200 + 4 * 10 * 434);
+        assert_eq!(plan.link_budget_db(), 119);
+
+        // Too long at 1.2 kBaud
+        req.latency_budget_us = 10_000;
+        assert_eq!(plan_link(&req).map(|plan| plan.range), Some(RangeClass::Medium));
+        req.latency_budget_us = 100;
+        assert_eq!(plan_link(&req), None);
+    }
+}
@@ -0,0 +1,170 @@
+//! Packets longer than 255 bytes.
+//!
+//! The length byte limits packets to 255 bytes. Longer ones start with a 2 byte length
+//! header (big endian, payload bytes following it) and are received in infinite length
+//! mode, draining the RX FIFO as they come in. The radio counts the received bytes modulo
+//! 256, so PKTLEN is set to the packet length modulo 256, and once fewer than 256 bytes are
+//! left the radio is switched to fixed length mode: the packet then ends at the right byte,
+//! with CRC check and appended status as usual.
+//!
+//! [`LongPacketReceiver::poll`] has to be called often enough for the RX FIFO not to
+//! overflow, e.g. on the RX FIFO threshold GDO signal, or from a timer.
+
+use crate::lowlevel::registers::*;
+use crate::lowlevel::types::*;
+use crate::trace::TraceEvent;
+use crate::{Cc1101, Error, PacketLength, RadioMode};
+use hal::spi::SpiDevice;
+
+/// Length of the header giving the payload length.
+pub const LENGTH_HEADER_LEN: usize = 2;
+
+/// Receives a packet of up to 65535 bytes, see the module documentation.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct LongPacketReceiver {
+    /// Payload length, once the header is read.
+    len: Option<usize>,
+    /// Payload bytes read so far.
+    read: usize,
+    /// Whether the radio was switched to fixed length mode.
+    fixed: bool,
+    /// Appended status bytes after the payload.
+    status_len: usize,
+}
+
+impl LongPacketReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Payload bytes read so far of the packet being received.
+    pub fn progress(&self) -> usize {
+        self.read
+    }
+
+    /// Switch to infinite length mode and enter RX, dropping any packet in progress.
+    pub fn start<SPI, SpiE>(&mut self, cc1101: &mut Cc1101<SPI>) -> Result<(), Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        *self = Self::new();
+        cc1101.set_radio_mode(RadioMode::Idle)?;
+        cc1101.flush_rx()?;
+        cc1101.set_packet_length(PacketLength::Infinite)?;
+        cc1101.set_radio_mode(RadioMode::Receive)
+    }
+
+    /// Drain the RX FIFO into `buf`, returns the payload length once a packet is complete.
+    ///
+    /// The radio is then back in infinite length mode and in RX (re-entered if RXOFF_MODE left
+    /// it in IDLE after the packet), ready for the next packet. Packets longer than `buf` fail
+    /// with `PacketLengthMismatch`, leaving the radio in IDLE with the packet dropped. An RX
+    /// FIFO overflow fails with `RxOverflow`, with the packet dropped and the radio already
+    /// back in RX, in infinite length mode.
+    pub fn poll<SPI, SpiE>(
+        &mut self,
+        cc1101: &mut Cc1101<SPI>,
+        buf: &mut [u8],
+    ) -> Result<Option<usize>, Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        let fifo = cc1101.rx_bytes_available()?;
+        if fifo.error {
+            *self = Self::new();
+            cc1101.set_packet_length(PacketLength::Infinite)?;
+            return cc1101.recover_rx_overflow();
+        }
+        let mut available = fifo.bytes as usize;
+        let len = match self.len {
+            Some(len) => len,
+            // Keep a byte in the FIFO, see below
+            None if available <= LENGTH_HEADER_LEN => return Ok(None),
+            None => {
+                let mut header = [0u8; LENGTH_HEADER_LEN];
+                cc1101.0.read_fifo(&mut header)?;
+                available -= LENGTH_HEADER_LEN;
+                let len = u16::from_be_bytes(header) as usize;
+                if len > buf.len() {
+                    *self = Self::new();
+                    cc1101.abort_receive()?;
+                    cc1101.0.trace_event(TraceEvent::PacketLengthMismatch);
+                    return Err(Error::PacketLengthMismatch);
+                }
+                // Only used once in fixed length mode
+                cc1101.0.write_register(Config::PKTLEN, (LENGTH_HEADER_LEN + len) as u8)?;
+                let pktctrl1 = PKTCTRL1(cc1101.0.read_register(Config::PKTCTRL1)?);
+                self.status_len = 2 * pktctrl1.append_status() as usize;
+                self.len = Some(len);
+                len
+            }
+        };
+
+        let (n, switch) = self.plan(len, available);
+        if switch {
+            cc1101.0.modify_register(Config::PKTCTRL0, |r| {
+                PKTCTRL0(r).modify().length_config(LengthConfig::FIXED.value()).bits()
+            })?;
+            self.fixed = true;
+        }
+        if n > 0 {
+            cc1101.0.read_fifo(&mut buf[self.read..self.read + n])?;
+            self.read += n;
+        }
+        if self.read < len || available - n < self.status_len {
+            return Ok(None);
+        }
+
+        let status = match self.status_len {
+            0 => None,
+            _ => {
+                let mut status = [0u8; 2];
+                cc1101.0.read_fifo(&mut status)?;
+                Some(status)
+            }
+        };
+        cc1101.notify_packet(status)?;
+        cc1101.0.modify_register(Config::PKTCTRL0, |r| {
+            PKTCTRL0(r).modify().length_config(LengthConfig::INFINITE.value()).bits()
+        })?;
+        if cc1101.is_in(RadioMode::Idle)? {
+            cc1101.set_radio_mode(RadioMode::Receive)?;
+        }
+        *self = Self::new();
+        Ok(Some(len))
+    }
+
+    /// Payload bytes to read with `available` bytes in the FIFO, and whether to switch to fixed
+    /// length mode.
+    fn plan(&self, len: usize, available: usize) -> (usize, bool) {
+        let remaining = len - self.read;
+        // Payload bytes the radio has yet to receive
+        let on_air = remaining.saturating_sub(available);
+        let switch = !self.fixed && on_air < 256;
+        let n = match on_air {
+            0 => remaining,
+            // Leave a byte in the FIFO while receiving (errata: reading the last byte while
+            // it's being written corrupts it)
+            _ => available.saturating_sub(1),
+        };
+        (n, switch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::longpacket::*;
+
+    #[test]
+    fn test_plan() {
+        let mut receiver = LongPacketReceiver::new();
+        assert_eq!(receiver.plan(1000, 40), (39, false));
+        receiver.read = 700;
+        assert_eq!(receiver.plan(1000, 50), (49, true));
+        receiver.fixed = true;
+        receiver.read = 980;
+        assert_eq!(receiver.plan(1000, 22), (20, false));
+        // Short packet, fixed length mode right away
+        assert_eq!(LongPacketReceiver::new().plan(10, 3), (2, true));
+    }
+}
@@ -1,6 +1,7 @@
 //! Low level unrestricted access to the CC1101 radio chip.
 
 use hal::spi::{Operation, SpiDevice};
+use heapless::Vec;
 
 #[macro_use]
 mod macros;
@@ -12,13 +13,104 @@ pub mod registers;
 pub mod types;
 
 use self::registers::*;
+use self::types::MachineState;
+use crate::trace::TraceEvent;
 
 pub const FXOSC: u64 = 27_000_000;
 
+/// Size of the RX and TX FIFOs (in bytes).
+pub const FIFO_SIZE: usize = 64;
+
+/// Maximum number of slices written in one transaction by `write_fifo_parts`.
+pub const MAX_FIFO_PARTS: usize = 8;
+
+/// Size of the PATABLE (in bytes).
+pub const PATABLE_SIZE: usize = 8;
+
+/// Maximum length of a burst access starting at `addr`, `None` if `addr` doesn't support burst
+/// access.
+///
+/// Configuration registers can be burst accessed up to the last one, the PATABLE and the FIFOs
+/// up to their size. Status register addresses with the burst bit set are single status register
+/// reads, not bursts.
+pub fn burst_limit(addr: u8) -> Option<usize> {
+    match addr {
+        a if (a as usize) < Config::ALL.len() => Some(Config::ALL.len() - a as usize),
+        a if a == Command::PATABLE.addr() => Some(PATABLE_SIZE),
+        a if a == Command::FIFO.addr() => Some(FIFO_SIZE),
+        _ => None,
+    }
+}
+
+/// In-RAM copy of the configuration registers, see `Cc1101::set_shadow`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Shadow {
+    values: [u8; Config::ALL.len()],
+    /// One bit per register, set when its value is known.
+    valid: u64,
+}
+
+impl Default for Shadow {
+    fn default() -> Self {
+        Shadow {
+            values: [0; Config::ALL.len()],
+            valid: 0,
+        }
+    }
+}
+
+impl Shadow {
+    fn get(&self, reg: Config) -> Option<u8> {
+        let addr = reg.addr() as usize;
+        (self.valid & 1 << addr != 0).then_some(self.values[addr])
+    }
+
+    /// Values of `N` consecutive registers starting at `start`, if they're all known.
+    fn get_range<const N: usize>(&self, start: Config) -> Option<[u8; N]> {
+        let start = start.addr() as usize;
+        let mask = ((1u64 << N) - 1) << start;
+        (self.valid & mask == mask).then(|| core::array::from_fn(|n| self.values[start + n]))
+    }
+
+    /// Record the values of consecutive registers starting at `start`.
+    fn set(&mut self, start: Config, values: &[u8]) {
+        for (n, &value) in values.iter().enumerate() {
+            let addr = start.addr() as usize + n;
+            self.values[addr] = value;
+            // The calibration results are written by the chip
+            if !(Config::FSCAL3.addr() as usize..=Config::FSCAL0.addr() as usize).contains(&addr) {
+                self.valid |= 1 << addr;
+            }
+        }
+    }
+}
+
 pub struct Cc1101<SPI> {
     pub(crate) spi: SPI,
+    /// `Some(IDLE)` while the radio is known to be in IDLE, the only state it never leaves on
+    /// its own (RX and TX can end in a FIFO error without any strobe).
+    ///
+    /// Cleared by any state-changing strobe.
+    pub(crate) state: Option<MachineState>,
+    /// Measured crystal error (in ppm), compensated for in frequency settings.
+    pub(crate) xtal_ppm: i32,
+    /// Link event observer, see `crate::observer`.
+    pub(crate) observer: Option<&'static dyn crate::observer::LinkObserver>,
+    /// Link statistics, see `crate::stats`.
+    pub(crate) stats: crate::stats::Stats,
+    /// Reception details of the last packet read.
+    pub(crate) last_packet: Option<crate::PacketInfo>,
+    /// Known register values, when enabled.
+    pub(crate) shadow: Option<Shadow>,
+    /// Status byte of the last SPI access, and whether it was a read access.
+    pub(crate) last_status: Option<(u8, bool)>,
+    /// Event timeline, see `crate::trace`.
+    #[cfg(feature = "trace")]
+    pub(crate) trace: crate::trace::Trace,
     //    gdo0: GDO0,
     //    gdo2: GDO2,
+    /// Interval between reads of the polling loops taking a `DelayNs` (in microseconds).
+    pub(crate) poll_interval_us: u32,
 }
 
 impl<SPI, SpiE> Cc1101<SPI>
@@ -27,78 +119,280 @@ where
 {
     pub fn new(spi: SPI) -> Result<Self, SpiE> {
         let cc1101 = Cc1101 {
+            poll_interval_us: crate::DEFAULT_POLL_INTERVAL_US,
             spi,
+            state: None,
+            xtal_ppm: 0,
+            observer: None,
+            stats: crate::stats::Stats::default(),
+            last_packet: None,
+            shadow: None,
+            last_status: None,
+            #[cfg(feature = "trace")]
+            trace: crate::trace::Trace::new(),
         };
         Ok(cc1101)
     }
 
+    /// Keep an in-RAM copy of the configuration registers (or stop), so reading a register,
+    /// e.g. to modify it, doesn't cost an SPI transaction once its value is known.
+    ///
+    /// Values are learned as registers are read and written, the calibration results (FSCAL3
+    /// to FSCAL0) are always read from the chip, and the copy is cleared by the reset and
+    /// power down strobes. Only enable it if nothing else changes the registers, e.g. another
+    /// driver sharing the chip, or a reset through the power supply: enable it again after
+    /// that, which starts from scratch. `read_config_burst` always reads the chip.
+    pub fn set_shadow(&mut self, enable: bool) {
+        self.shadow = enable.then(Shadow::default);
+    }
+
     pub fn read_register<R>(&mut self, reg: R) -> Result<u8, SpiE>
     where
         R: Into<Register>,
     {
-        let mut buffer = [reg.into().raddr(), 0u8];
+        let reg = reg.into();
+        if let (Register::Config(config), Some(shadow)) = (reg, &self.shadow) {
+            if let Some(value) = shadow.get(config) {
+                return Ok(value);
+            }
+        }
+        let mut buffer = [reg.raddr(), 0u8];
         self.spi.transfer_in_place(&mut buffer)?;
+        self.last_status = Some((buffer[0], true));
+        if let (Register::Config(config), Some(shadow)) = (reg, &mut self.shadow) {
+            shadow.set(config, &buffer[1..]);
+        }
         Ok(buffer[1])
     }
 
-    fn read_burst(&mut self, addr: u8, buf: &mut [u8]) -> Result<(), SpiE> {
-        let mut buffer = [addr | 0b1100_0000];
+    /// Read several status registers.
+    ///
+    /// Status registers can't be read in a burst: their addresses with the burst bit set select
+    /// single status register reads instead of command strobes. So this is a sequence of single
+    /// reads, one transaction each.
+    pub fn read_status_registers(&mut self, regs: &[Status], buf: &mut [u8]) -> Result<(), SpiE> {
+        debug_assert_eq!(regs.len(), buf.len());
+        for (&reg, b) in regs.iter().zip(buf.iter_mut()) {
+            *b = self.read_register(reg)?;
+        }
+        Ok(())
+    }
+
+    /// Burst read into `buf`, the header byte and the payload are separate operations of one
+    /// transaction, the payload going straight into `buf`.
+    pub(crate) fn read_burst(&mut self, addr: u8, buf: &mut [u8]) -> Result<(), SpiE> {
+        let mut header = [addr | 0b1100_0000];
         self.spi
-            .transaction(&mut [Operation::TransferInPlace(&mut buffer), Operation::Read(buf)])?;
+            .transaction(&mut [Operation::TransferInPlace(&mut header), Operation::Read(buf)])?;
+        self.last_status = Some((header[0], true));
         Ok(())
     }
-    fn write_burst(&mut self, addr: u8, buf: &[u8]) -> Result<(), SpiE> {
-        let mut buffer = [addr | 0b0100_0000];
+
+    /// Burst write from `buf`, same structure as `read_burst`.
+    pub(crate) fn write_burst(&mut self, addr: u8, buf: &[u8]) -> Result<(), SpiE> {
+        let mut header = [addr | 0b0100_0000];
         self.spi
-            .transaction(&mut [Operation::TransferInPlace(&mut buffer), Operation::Write(buf)])?;
+            .transaction(&mut [Operation::TransferInPlace(&mut header), Operation::Write(buf)])?;
+        self.last_status = Some((header[0], false));
         Ok(())
     }
 
-    /// The FIFO is 64 bytes long
+    /// Read consecutive configuration registers, starting at `start`, in one transaction.
+    pub fn read_config_burst(&mut self, start: Config, buf: &mut [u8]) -> Result<(), SpiE> {
+        debug_assert!(start.addr() as usize + buf.len() <= Config::ALL.len());
+        self.read_burst(start.addr(), buf)?;
+        if let Some(shadow) = &mut self.shadow {
+            shadow.set(start, buf);
+        }
+        Ok(())
+    }
+
+    /// Write consecutive configuration registers, starting at `start`, in one transaction.
+    pub fn write_config_burst(&mut self, start: Config, buf: &[u8]) -> Result<(), SpiE> {
+        let end = start.addr() as usize + buf.len();
+        debug_assert!(end <= Config::ALL.len());
+        self.write_burst(start.addr(), buf)?;
+        if let Some(shadow) = &mut self.shadow {
+            shadow.set(start, buf);
+        }
+        Ok(())
+    }
+
+    /// Read-modify-write `N` consecutive configuration registers, starting at `start`.
+    ///
+    /// One burst read, and one burst write only if `f` changed anything.
+    pub fn modify_config_burst<const N: usize, F>(
+        &mut self,
+        start: Config,
+        f: F,
+    ) -> Result<(), SpiE>
+    where
+        F: FnOnce(&mut [u8; N]),
+    {
+        let mut regs = match self.shadow.and_then(|shadow| shadow.get_range(start)) {
+            Some(regs) => regs,
+            None => {
+                let mut regs = [0u8; N];
+                self.read_config_burst(start, &mut regs)?;
+                regs
+            }
+        };
+        let old = regs;
+        f(&mut regs);
+        if regs != old {
+            self.write_config_burst(start, &regs)?;
+        }
+        Ok(())
+    }
+
+    /// The FIFO is `FIFO_SIZE` bytes long
+    ///
+    /// `buf` is passed as is to the SPI device, as the second operation of the transaction
+    /// after the header byte: a DMA-backed `SpiDevice` can transfer into it directly, no copy
+    /// is made. There are no alignment requirements on the driver side, `buf` only has to stay
+    /// borrowed for the duration of the call, which the signature guarantees.
     pub fn read_fifo(&mut self, buf: &mut [u8]) -> Result<(), SpiE> {
         self.read_burst(Command::FIFO.addr(), buf)
     }
-    /// The FIFO is 64 bytes long
+    /// The FIFO is `FIFO_SIZE` bytes long
+    ///
+    /// Same zero-copy guarantees as `read_fifo`.
     pub fn write_fifo(&mut self, buf: &[u8]) -> Result<(), SpiE> {
         self.write_burst(Command::FIFO.addr(), buf)
     }
-    /// The PATABLE is 8 bytes long
+    /// Write several slices to the TX FIFO, back to back.
+    ///
+    /// Up to `MAX_FIFO_PARTS` slices go in one transaction, one write operation each, so the
+    /// data doesn't have to be assembled in a contiguous buffer first.
+    pub fn write_fifo_parts(&mut self, parts: &[&[u8]]) -> Result<(), SpiE> {
+        for chunk in parts.chunks(MAX_FIFO_PARTS) {
+            let mut header = [Command::FIFO.addr() | 0b0100_0000];
+            // Can't overflow, capacity is one header and `MAX_FIFO_PARTS` parts
+            let mut ops: Vec<Operation<u8>, { MAX_FIFO_PARTS + 1 }> = Vec::new();
+            let _ = ops.push(Operation::TransferInPlace(&mut header));
+            for part in chunk {
+                let _ = ops.push(Operation::Write(part));
+            }
+            self.spi.transaction(&mut ops)?;
+            // Release the borrow of `header`
+            drop(ops);
+            self.last_status = Some((header[0], false));
+        }
+        Ok(())
+    }
+    /// Read a whole FIFO worth of data, the fixed size path to benchmark DMA transfers with.
+    ///
+    /// The RX FIFO must hold `FIFO_SIZE` bytes, reading an empty FIFO returns garbage.
+    pub fn read_fifo_full(&mut self, buf: &mut [u8; FIFO_SIZE]) -> Result<(), SpiE> {
+        self.read_fifo(buf)
+    }
+    /// Fill the whole TX FIFO, which must be empty.
+    pub fn write_fifo_full(&mut self, buf: &[u8; FIFO_SIZE]) -> Result<(), SpiE> {
+        self.write_fifo(buf)
+    }
+    /// The PATABLE is `PATABLE_SIZE` bytes long
     pub fn read_patable(&mut self, buf: &mut [u8]) -> Result<(), SpiE> {
         self.read_burst(Command::PATABLE.addr(), buf)
     }
-    /// The PATABLE is 8 bytes long
+    /// The PATABLE is `PATABLE_SIZE` bytes long
     pub fn write_patable(&mut self, buf: &[u8]) -> Result<(), SpiE> {
         self.write_burst(Command::PATABLE.addr(), buf)
     }
 
     pub fn write_strobe(&mut self, com: Command) -> Result<(), SpiE> {
-        self.spi.write(&[com.addr()])?;
+        self.strobe_with_status(com, false)?;
         Ok(())
     }
+
+    /// Send a command strobe, returns the status byte received with it.
+    ///
+    /// The FIFO bytes field of the status byte counts the bytes available in the RX FIFO if
+    /// `rx_fifo` (the strobe is sent with the read bit set), the bytes free in the TX FIFO
+    /// otherwise.
+    pub fn strobe_with_status(&mut self, com: Command, rx_fifo: bool) -> Result<u8, SpiE> {
+        if !matches!(com, Command::SNOP | Command::SFRX | Command::SFTX | Command::SWORRST) {
+            self.state = None;
+        }
+        // Registers are back to their reset value, FSTEST to TEST0 are lost in SLEEP
+        if let (Command::SRES | Command::SPWD, Some(shadow)) = (com, &mut self.shadow) {
+            *shadow = Shadow::default();
+        }
+        self.trace_event(TraceEvent::Strobe(com));
+        let mut buffer = [com.addr() | (rx_fifo as u8) << 7];
+        self.spi.transfer_in_place(&mut buffer)?;
+        self.last_status = Some((buffer[0], rx_fifo));
+        Ok(buffer[0])
+    }
+
+    /// Record `event` in the timeline, a no-op without the `trace` feature.
+    #[inline]
+    pub(crate) fn trace_event(&mut self, _event: TraceEvent) {
+        #[cfg(feature = "trace")]
+        self.trace.record(_event);
+    }
     /// Sends a NoOp to read status byte
-    /// 
+    ///
     /// Returns wether chip is ready to accept commands (when chip_rdyn (bit 7) is low (false))
     pub fn chip_rdyn(&mut self) -> Result<bool, SpiE> {
         let mut c = [Command::SNOP.addr()];
         self.spi.transfer_in_place(&mut c)?;
+        self.last_status = Some((c[0], false));
         Ok(c[0] & 0x80 == 0)
     }
 
-    pub fn write_register<R>(&mut self, reg: R, byte: u8) -> Result<(), SpiE>
-    where
-        R: Into<Register>,
-    {
-        self.spi.write(&[reg.into().waddr(), byte])?;
+    /// Write a configuration register.
+    ///
+    /// Only configuration registers are writable, the addresses of the status registers are
+    /// command strobes without the burst bit, and undefined with it.
+    pub fn write_register(&mut self, reg: Config, byte: u8) -> Result<(), SpiE> {
+        let mut buffer = [Register::Config(reg).waddr(), byte];
+        self.spi.transfer_in_place(&mut buffer)?;
+        // Status after the data byte
+        self.last_status = Some((buffer[1], false));
+        if let Some(shadow) = &mut self.shadow {
+            shadow.set(reg, &[byte]);
+        }
         Ok(())
     }
 
-    pub fn modify_register<R, F>(&mut self, reg: R, f: F) -> Result<(), SpiE>
+    pub fn modify_register<F>(&mut self, reg: Config, f: F) -> Result<(), SpiE>
     where
-        R: Into<Register> + Copy,
         F: FnOnce(u8) -> u8,
     {
         let r = self.read_register(reg)?;
-        self.write_register(reg, f(r))?;
+        let w = f(r);
+        if w != r {
+            self.write_register(reg, w)?;
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::lowlevel::*;
+
+    #[test]
+    fn test_burst_limit() {
+        assert_eq!(burst_limit(Config::IOCFG2.addr()), Some(47));
+        assert_eq!(burst_limit(Config::TEST0.addr()), Some(1));
+        assert_eq!(burst_limit(Status::PARTNUM.addr()), None);
+        assert_eq!(burst_limit(Command::SRES.addr()), None);
+        assert_eq!(burst_limit(Command::PATABLE.addr()), Some(PATABLE_SIZE));
+        assert_eq!(burst_limit(Command::FIFO.addr()), Some(FIFO_SIZE));
+        assert_eq!(burst_limit(0x40), None);
+    }
+
+    #[test]
+    fn test_shadow() {
+        let mut shadow = Shadow::default();
+        assert_eq!(shadow.get(Config::MDMCFG4), None);
+        // MDMCFG4, MDMCFG3, then the calibration results
+        shadow.set(Config::MDMCFG4, &[0x8c, 0x22]);
+        shadow.set(Config::FSCAL3, &[0xa9, 0x0a]);
+        assert_eq!(shadow.get(Config::MDMCFG3), Some(0x22));
+        assert_eq!(shadow.get_range(Config::MDMCFG4), Some([0x8c, 0x22]));
+        assert_eq!(shadow.get_range::<3>(Config::MDMCFG4), None);
+        assert_eq!(shadow.get(Config::FSCAL3), None);
+    }
+}
@@ -8,12 +8,21 @@ pub const fn from_frequency(hz: u64) -> (u8, u8, u8) {
     (freq0, freq1, freq2)
 }
 
+pub const fn to_frequency(freq0: u8, freq1: u8, freq2: u8) -> u64 {
+    let freq = (freq2 as u64) << 16 | (freq1 as u64) << 8 | freq0 as u64;
+    (freq * FXOSC) >> 16
+}
+
 pub const fn from_deviation(v: u64) -> (u8, u8) {
     let exponent = 64 - (v.rotate_left(14) / FXOSC).leading_zeros() - 1;
     let mantissa = (v.rotate_left(17) / (FXOSC.rotate_left(exponent))) - 7;
     ((mantissa & 0x7) as u8, (exponent & 0x7) as u8)
 }
 
+pub const fn to_deviation(mantissa: u8, exponent: u8) -> u64 {
+    (FXOSC * (8 + mantissa as u64)) << exponent >> 17
+}
+
 // TODO: Not defined for all values, need to figure out.
 pub const fn from_drate(v: u64) -> (u8, u8) {
     let exponent = 64 - (v.rotate_left(19) / FXOSC).leading_zeros();
@@ -26,12 +35,57 @@ pub const fn from_drate(v: u64) -> (u8, u8) {
     }
 }
 
+pub const fn to_drate(mantissa: u8, exponent: u8) -> u64 {
+    (FXOSC * (256 + mantissa as u64)) << exponent >> 28
+}
+
 pub fn from_chanbw(v: u64) -> (u8, u8) {
     let exponent = 64 - (FXOSC / (8 * 4 * v)).leading_zeros() - 1;
     let mantissa = FXOSC / (v * 8 * 2u64.pow(exponent)) - 4;
     (mantissa as u8 & 0x3, exponent as u8 & 0x3)
 }
 
+pub const fn to_chanbw(mantissa: u8, exponent: u8) -> u64 {
+    FXOSC / ((8 * (4 + mantissa as u64)) << exponent)
+}
+
+/// Channel spacing (in Hertz) to (mantissa, exponent), rounded to the closest setting and
+/// clamped to the supported range.
+pub const fn from_chanspc(hz: u64) -> (u8, u8) {
+    // f_chanspc = f_osc / 2^18 * (256 + CHANSPC_M) * 2^CHANSPC_E
+    let mut exponent = 0;
+    loop {
+        let step = FXOSC << exponent;
+        let m = ((hz << 18) + step / 2) / step;
+        if m < 256 {
+            return (0, exponent);
+        }
+        if m <= 511 || exponent == 3 {
+            let m = if m > 511 {
+                511
+            } else {
+                m
+            };
+            return ((m - 256) as u8, exponent);
+        }
+        exponent += 1;
+    }
+}
+
+pub const fn to_chanspc(mantissa: u8, exponent: u8) -> u64 {
+    (FXOSC * (256 + mantissa as u64)) << exponent >> 18
+}
+
+/// Narrowest channel filter bandwidth of at least `hz`, as (mantissa, exponent). The widest
+/// one if `hz` is wider than that.
+pub fn chanbw_at_least(hz: u64) -> (u8, u8) {
+    (0..4)
+        .rev()
+        .flat_map(|e| (0..4).rev().map(move |m| (m, e)))
+        .find(|&(m, e)| to_chanbw(m, e) >= hz)
+        .unwrap_or((0, 0))
+}
+
 pub fn from_freq_if(hz: u64) -> u8 {
     // Round towards the closest setting, rather than down.
     (((hz << 10) + FXOSC / 2) / FXOSC).try_into().unwrap()
@@ -44,17 +98,20 @@ mod tests {
 
     #[test]
     fn test_frequency() {
-        assert_eq!(from_frequency(433_000_000), (0x62, 0xA7, 0x10));
-        assert_eq!(from_frequency(868_000_000), (0x76, 0x62, 0x21));
-        assert_eq!(from_frequency(902_000_000), (0x3B, 0xB1, 0x22));
-        assert_eq!(from_frequency(918_000_000), (0xC4, 0x4E, 0x23));
+        assert_eq!(from_frequency(433_000_000), (0x7B, 0x09, 0x10));
+        assert_eq!(from_frequency(868_000_000), (0xED, 0x25, 0x20));
+        assert_eq!(from_frequency(902_000_000), (0x4B, 0x68, 0x21));
+        assert_eq!(from_frequency(918_000_000), (0x00, 0x00, 0x22));
+        assert_eq!(to_frequency(0x00, 0x00, 0x22), 918_000_000);
+        let (freq0, freq1, freq2) = from_frequency(433_920_000);
+        assert!((433_919_600..=433_920_000).contains(&to_frequency(freq0, freq1, freq2)));
     }
 
     #[test]
     fn test_deviation() {
         // f_dev = f_osc / 2^17 * (8 + DEVIATION_M) * 2^DEVIATION_E
         fn calc_rev_dev(dev_m: u8, dev_e: u8) -> u64 {
-            (((FXOSC as f32 / (2u64.pow(17) as f32)) as f32)
+            ((FXOSC as f32 / (2u64.pow(17) as f32))
                 * (8f32 + dev_m as f32)
                 * (2u64.pow(dev_e as u32) as f32)) as u64
         }
@@ -62,26 +119,29 @@ mod tests {
         for e in 0..7 {
             for m in 1..7 {
                 assert_eq!(from_deviation(calc_rev_dev(m, e)), (m, e));
+                assert_eq!(from_deviation(to_deviation(m, e)), (m, e));
             }
         }
     }
 
     #[test]
     fn test_drate() {
-        // Some sample settings from SmartRF Studio
-        assert_eq!((117, 5), from_drate(1156));
-        assert_eq!((117, 7), from_drate(4624));
-        assert_eq!((117, 10), from_drate(36994));
-        assert_eq!((34, 12), from_drate(115051));
-        assert_eq!((59, 14), from_drate(499877));
-        assert_eq!((59, 13), from_drate(249938));
-        assert_eq!((248, 11), from_drate(99975));
-        assert_eq!((131, 11), from_drate(76766));
-        assert_eq!((131, 10), from_drate(38383));
-        assert_eq!((147, 8), from_drate(9992));
-        assert_eq!((131, 7), from_drate(4797));
-        assert_eq!((131, 6), from_drate(2398));
-        assert_eq!((131, 5), from_drate(1199));
+        // SmartRF Studio sample settings, rescaled for the 27 MHz crystal
+        assert_eq!((117, 5), from_drate(1200));
+        assert_eq!((117, 7), from_drate(4802));
+        assert_eq!((117, 10), from_drate(38417));
+        assert_eq!((34, 12), from_drate(119476));
+        assert_eq!((59, 14), from_drate(519104));
+        assert_eq!((59, 13), from_drate(259552));
+        assert_eq!((248, 11), from_drate(103820));
+        assert_eq!((131, 11), from_drate(79719));
+        assert_eq!((131, 10), from_drate(39859));
+        assert_eq!((147, 8), from_drate(10376));
+        assert_eq!((131, 7), from_drate(4982));
+        assert_eq!((131, 6), from_drate(2491));
+        assert_eq!((131, 5), from_drate(1245));
+        assert_eq!(to_drate(117, 5), 1200);
+        assert_eq!(to_drate(59, 14), 519_104);
 
         /* TODO: make this work
         fn calc_drate_rev(mantissa: u8, exponent: u8) -> u64 {
@@ -119,10 +179,43 @@ mod tests {
         assert_eq!(from_chanbw(58035), (0b11, 0b11));
     }
 
+    #[test]
+    fn test_to_chanbw() {
+        assert_eq!(to_chanbw(0, 0), 843750);
+        assert_eq!(to_chanbw(3, 3), 60267);
+        for e in 0..4 {
+            for m in 0..4 {
+                assert_eq!(from_chanbw(to_chanbw(m, e)), (m, e));
+            }
+        }
+    }
+
+    #[test]
+    fn test_chanbw_at_least() {
+        assert_eq!(chanbw_at_least(0), (3, 3));
+        assert_eq!(chanbw_at_least(60_267), (3, 3));
+        assert_eq!(chanbw_at_least(60_268), (2, 3));
+        assert_eq!(chanbw_at_least(100_000), (0, 3));
+        assert_eq!(chanbw_at_least(10_000_000), (0, 0));
+    }
+
+    #[test]
+    fn test_chanspc() {
+        assert_eq!(from_chanspc(200_000), (229, 2));
+        assert_eq!(to_chanspc(229, 2), 199_813);
+        assert_eq!(from_chanspc(1), (0, 0));
+        assert_eq!(from_chanspc(1_000_000), (255, 3));
+        for e in 0..4 {
+            for m in [0, 1, 128, 255] {
+                assert_eq!(from_chanspc(to_chanspc(m, e)), (m, e));
+            }
+        }
+    }
+
     #[test]
     fn test_freq_if() {
-        assert_eq!(from_freq_if(381_000), 0x0F);
-        assert_eq!(from_freq_if(203_125), 0x08);
-        assert_eq!(from_freq_if(152_300), 0x06);
+        assert_eq!(from_freq_if(395_508), 0x0F);
+        assert_eq!(from_freq_if(210_938), 0x08);
+        assert_eq!(from_freq_if(158_203), 0x06);
     }
 }
@@ -81,3 +81,16 @@ macro_rules! register {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::lowlevel::registers::*;
+
+    #[test]
+    fn test_single_bit_field() {
+        // APPEND_STATUS (bit 2) must not touch CRC_AUTOFLUSH (bit 3)
+        assert_eq!(PKTCTRL1::default().crc_autoflush(1).append_status(0).bits(), 0b0000_1000);
+        assert_eq!(PKTCTRL1(0b0000_1100).append_status(), 1);
+        assert_eq!(PKTCTRL1::mask().append_status(), 0b0000_0100);
+    }
+}
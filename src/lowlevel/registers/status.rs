@@ -32,6 +32,24 @@ pub enum Status {
 }
 
 impl Status {
+    /// All status registers, in address order.
+    pub const ALL: [Status; 14] = [
+        Status::PARTNUM,
+        Status::VERSION,
+        Status::FREQEST,
+        Status::LQI,
+        Status::RSSI,
+        Status::MARCSTATE,
+        Status::WORTIME1,
+        Status::WORTIME0,
+        Status::PKTSTATUS,
+        Status::VCO_VC_DAC,
+        Status::TXBYTES,
+        Status::RXBYTES,
+        Status::RCCTRL1_STATUS,
+        Status::RCCTRL0_STATUS,
+    ];
+
     pub fn addr(&self) -> u8 {
         *self as u8
     }
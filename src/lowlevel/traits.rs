@@ -11,7 +11,7 @@ impl OffsetSize for u8 {
     }
 
     fn size(self) -> u8 {
-        1
+        0
     }
 }
 
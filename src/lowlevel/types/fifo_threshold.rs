@@ -40,4 +40,39 @@ impl FifoThreshold {
     pub fn value(&self) -> u8 {
         *self as u8
     }
+
+    /// Threshold from a FIFO_THR value, only the 4 low bits are used.
+    pub fn from_value(value: u8) -> Self {
+        use FifoThreshold::*;
+
+        const THRESHOLDS: [FifoThreshold; 16] = [
+            TX_61_RX_4,
+            TX_57_RX_8,
+            TX_53_RX_12,
+            TX_49_RX_16,
+            TX_45_RX_20,
+            TX_41_RX_24,
+            TX_37_RX_28,
+            TX_33_RX_32,
+            TX_29_RX_36,
+            TX_25_RX_40,
+            TX_21_RX_44,
+            TX_17_RX_48,
+            TX_13_RX_52,
+            TX_9_RX_56,
+            TX_5_RX_60,
+            TX_1_RX_64,
+        ];
+        THRESHOLDS[(value & 0x0f) as usize]
+    }
+
+    /// TX FIFO threshold (in bytes).
+    pub fn tx_bytes(&self) -> u8 {
+        61 - 4 * self.value()
+    }
+
+    /// RX FIFO threshold (in bytes).
+    pub fn rx_bytes(&self) -> u8 {
+        4 * (self.value() + 1)
+    }
 }
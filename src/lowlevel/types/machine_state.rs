@@ -31,4 +31,61 @@ impl MachineState {
     pub fn value(&self) -> u8 {
         *self as u8
     }
+
+    /// Machine state from a MARCSTATE value, `None` if out of range.
+    pub fn from_value(value: u8) -> Option<Self> {
+        use MachineState::*;
+
+        const STATES: [MachineState; 23] = [
+            SLEEP,
+            IDLE,
+            XOFF,
+            VCOON_MC,
+            REGON_MC,
+            MANCAL,
+            VCOON,
+            REGON,
+            STARTCAL,
+            BWBOOST,
+            FS_LOCK,
+            IFADCON,
+            ENDCAL,
+            RX,
+            RX_END,
+            RX_RST,
+            TXRX_SWITCH,
+            RXFIFO_OVERFLOW,
+            FSTXON,
+            TX,
+            TX_END,
+            RXTX_SWITCH,
+            TXFIFO_UNDERFLOW,
+        ];
+        STATES.get(value as usize).copied()
+    }
+
+    /// Whether this is a transitional state (calibration, settling, RX/TX switching), which the
+    /// radio should only pass through.
+    pub fn is_transitional(&self) -> bool {
+        use MachineState::*;
+
+        matches!(
+            self,
+            VCOON_MC
+                | REGON_MC
+                | MANCAL
+                | VCOON
+                | REGON
+                | STARTCAL
+                | BWBOOST
+                | FS_LOCK
+                | IFADCON
+                | ENDCAL
+                | RX_END
+                | RX_RST
+                | TXRX_SWITCH
+                | TX_END
+                | RXTX_SWITCH
+        )
+    }
 }
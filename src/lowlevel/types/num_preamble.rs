@@ -16,4 +16,18 @@ impl NumPreamble {
     pub fn value(&self) -> u8 {
         *self as u8
     }
+
+    /// Number of preamble bytes.
+    pub fn bytes(&self) -> u8 {
+        match self {
+            NumPreamble::N_2 => 2,
+            NumPreamble::N_3 => 3,
+            NumPreamble::N_4 => 4,
+            NumPreamble::N_6 => 6,
+            NumPreamble::N_8 => 8,
+            NumPreamble::N_12 => 12,
+            NumPreamble::N_16 => 16,
+            NumPreamble::N_24 => 24,
+        }
+    }
 }
@@ -0,0 +1,274 @@
+//! Remote register access, to inspect and fix the radio settings of deployed nodes.
+//!
+//! A peer sends [`Request`]s over whatever link the nodes use, the node hands them to its
+//! [`MaintenanceServer`] and transmits the reply it builds. Both are authenticated with a tag
+//! computed by a caller supplied function (e.g. a truncated HMAC or CMAC with a shared key),
+//! and carry a sequence number that must increase from one request to the next so that
+//! recorded requests can't be replayed.
+//!
+//! | request | sequence (BE) | direction | command | arguments | tag (BE) |
+//! |---------|---------------|-----------|---------|-----------|----------|
+//! | bytes   | 4             | 1 (0x00)  | 1       | 0 to n    | 4        |
+//!
+//! | reply   | sequence (BE) | direction | status  | data      | tag (BE) |
+//! |---------|---------------|-----------|---------|-----------|----------|
+//! | bytes   | 4             | 1 (0x80)  | 1       | 0 to n    | 4        |
+//!
+//! The reply echoes the sequence number of the request. The direction byte is covered by the
+//! tag, so a reply can't be reflected back as a request or the other way around. The FIFOs
+//! are not accessible.
+
+use crate::lowlevel::registers::*;
+use crate::{Cc1101, Error};
+use hal::spi::SpiDevice;
+
+const CMD_READ: u8 = 0x01;
+const CMD_WRITE: u8 = 0x02;
+const CMD_DUMP: u8 = 0x03;
+const CMD_REBOOT: u8 = 0x04;
+
+const STATUS_OK: u8 = 0x00;
+const STATUS_REJECTED: u8 = 0x01;
+
+const DIR_REQUEST: u8 = 0x00;
+const DIR_REPLY: u8 = 0x80;
+
+const HEADER_LEN: usize = 6;
+const TAG_LEN: usize = 4;
+/// Number of configuration registers, returned by a dump.
+pub const DUMP_LEN: usize = 47;
+/// Length of the longest reply, to a dump.
+pub const MAX_REPLY_LEN: usize = HEADER_LEN + DUMP_LEN + TAG_LEN;
+
+/// A maintenance request.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Request<'a> {
+    /// Burst read the given number of registers starting at the given address.
+    Read(u8, u8),
+    /// Burst write the data starting at the given address.
+    Write(u8, &'a [u8]),
+    /// Read all the configuration registers.
+    Dump,
+    /// Reset the radio, once the reply has been sent.
+    Reboot,
+}
+
+impl Request<'_> {
+    /// Encode the request with sequence number `seq` into `buf`, returns the encoded length.
+    ///
+    /// `None` if `buf` is too small.
+    pub fn encode<F>(&self, seq: u32, mut mac: F, buf: &mut [u8]) -> Option<usize>
+    where
+        F: FnMut(&[u8]) -> u32,
+    {
+        let body = buf.get_mut(5..)?;
+        let body_len = match *self {
+            Request::Read(addr, len) => put(body, &[CMD_READ, addr, len])?,
+            Request::Write(addr, data) => {
+                put(body, &[CMD_WRITE, addr])? + put(body.get_mut(2..)?, data)?
+            }
+            Request::Dump => put(body, &[CMD_DUMP])?,
+            Request::Reboot => put(body, &[CMD_REBOOT])?,
+        };
+        buf[..4].copy_from_slice(&seq.to_be_bytes());
+        buf[4] = DIR_REQUEST;
+        let len = 5 + body_len;
+        let tag = mac(&buf[..len]);
+        put(buf.get_mut(len..)?, &tag.to_be_bytes())?;
+        Some(len + TAG_LEN)
+    }
+
+    fn decode(body: &'_ [u8]) -> Option<Request<'_>> {
+        match body {
+            [CMD_READ, addr, len] => Some(Request::Read(*addr, *len)),
+            [CMD_WRITE, addr, data @ ..] => Some(Request::Write(*addr, data)),
+            [CMD_DUMP] => Some(Request::Dump),
+            [CMD_REBOOT] => Some(Request::Reboot),
+            _ => None,
+        }
+    }
+}
+
+/// A decoded reply.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Reply<'a> {
+    /// Request carried out, with the registers read if any.
+    Ok(&'a [u8]),
+    /// Request not carried out, e.g. an invalid register range.
+    Rejected,
+}
+
+impl Reply<'_> {
+    /// Decode the reply to request `seq`, `None` if it isn't an authentic reply to it.
+    pub fn decode<F>(frame: &[u8], seq: u32, mut mac: F) -> Option<Reply<'_>>
+    where
+        F: FnMut(&[u8]) -> u32,
+    {
+        let (body, reply_seq) = authenticate(frame, DIR_REPLY, &mut mac)?;
+        if reply_seq != seq {
+            return None;
+        }
+        match body {
+            [STATUS_OK, data @ ..] => Some(Reply::Ok(data)),
+            [STATUS_REJECTED] => Some(Reply::Rejected),
+            _ => None,
+        }
+    }
+}
+
+/// Copy `data` to the start of `buf`, returns its length.
+fn put(buf: &mut [u8], data: &[u8]) -> Option<usize> {
+    buf.get_mut(..data.len())?.copy_from_slice(data);
+    Some(data.len())
+}
+
+/// Check the tag and `direction` of `frame`, returns its body after the direction byte, and
+/// the sequence number.
+fn authenticate<'a, F>(frame: &'a [u8], direction: u8, mac: &mut F) -> Option<(&'a [u8], u32)>
+where
+    F: FnMut(&[u8]) -> u32,
+{
+    if frame.len() < HEADER_LEN + TAG_LEN {
+        return None;
+    }
+    let (signed, tag) = frame.split_at(frame.len() - TAG_LEN);
+    if mac(signed) != u32::from_be_bytes(tag.try_into().ok()?) {
+        return None;
+    }
+    let (seq, body) = signed.split_at(4);
+    match body {
+        [dir, body @ ..] if *dir == direction => {
+            Some((body, u32::from_be_bytes(seq.try_into().ok()?)))
+        }
+        _ => None,
+    }
+}
+
+/// What to do after handling a request.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Handled {
+    /// Nothing to send, the request wasn't authentic or was a replay.
+    Ignored,
+    /// Transmit the reply, of the given length.
+    Reply(usize),
+    /// Transmit the reply, of the given length, then `reset` the radio and configure it again.
+    Reboot(usize),
+}
+
+/// Node side of the maintenance protocol.
+pub struct MaintenanceServer<F> {
+    mac: F,
+    last_seq: u32,
+}
+
+impl<F> MaintenanceServer<F>
+where
+    F: FnMut(&[u8]) -> u32,
+{
+    /// Accept requests authenticated with `mac`, with sequence numbers above `last_seq`.
+    ///
+    /// `last_seq` should be persisted across restarts (see `last_seq`), otherwise requests
+    /// recorded before a restart can be replayed after it.
+    pub fn new(mac: F, last_seq: u32) -> Self {
+        MaintenanceServer {
+            mac,
+            last_seq,
+        }
+    }
+
+    /// Sequence number of the last accepted request.
+    pub fn last_seq(&self) -> u32 {
+        self.last_seq
+    }
+
+    /// Handle a received `request`, writing the reply into `reply` (at least `MAX_REPLY_LEN`).
+    pub fn handle<SPI, SpiE>(
+        &mut self,
+        cc1101: &mut Cc1101<SPI>,
+        request: &[u8],
+        reply: &mut [u8],
+    ) -> Result<Handled, Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        let Some((body, seq)) = authenticate(request, DIR_REQUEST, &mut self.mac) else {
+            return Ok(Handled::Ignored);
+        };
+        if seq <= self.last_seq || reply.len() < MAX_REPLY_LEN {
+            return Ok(Handled::Ignored);
+        }
+        self.last_seq = seq;
+
+        let request = Request::decode(body);
+        let data = &mut reply[HEADER_LEN..MAX_REPLY_LEN - TAG_LEN];
+        let result = match request {
+            Some(Request::Read(addr, len)) if addr != Command::FIFO.addr() => {
+                match data.get_mut(..len as usize) {
+                    Some(buf) => cc1101.read_burst(addr, buf).map(|_| len as usize),
+                    None => Err(Error::InvalidBurst),
+                }
+            }
+            Some(Request::Write(addr, buf)) if addr != Command::FIFO.addr() => {
+                cc1101.write_burst(addr, buf).map(|_| 0)
+            }
+            Some(Request::Dump) => cc1101.read_burst(0, &mut data[..DUMP_LEN]).map(|_| DUMP_LEN),
+            Some(Request::Reboot) => Ok(0),
+            _ => Err(Error::InvalidBurst),
+        };
+        let (status, len) = match result {
+            Ok(len) => (STATUS_OK, len),
+            Err(Error::InvalidBurst) => (STATUS_REJECTED, 0),
+            Err(e) => return Err(e),
+        };
+
+        reply[..4].copy_from_slice(&seq.to_be_bytes());
+        reply[4] = DIR_REPLY;
+        reply[5] = status;
+        let len = HEADER_LEN + len;
+        let tag = (self.mac)(&reply[..len]);
+        reply[len..len + TAG_LEN].copy_from_slice(&tag.to_be_bytes());
+        match request {
+            Some(Request::Reboot) => Ok(Handled::Reboot(len + TAG_LEN)),
+            _ => Ok(Handled::Reply(len + TAG_LEN)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maintenance::*;
+
+    fn mac(data: &[u8]) -> u32 {
+        data.iter().fold(0x811c9dc5, |h, &b| (h ^ b as u32).wrapping_mul(0x01000193))
+    }
+
+    #[test]
+    fn test_request_encoding() {
+        let mut buf = [0u8; 16];
+        let len = Request::Write(0x0d, &[0x21, 0x62]).encode(7, mac, &mut buf).unwrap();
+        assert_eq!(len, 13);
+        let (body, seq) = authenticate(&buf[..len], DIR_REQUEST, &mut mac).unwrap();
+        assert_eq!(seq, 7);
+        assert_eq!(Request::decode(body), Some(Request::Write(0x0d, &[0x21, 0x62])));
+
+        // Not a reply
+        assert!(authenticate(&buf[..len], DIR_REPLY, &mut mac).is_none());
+        buf[6] ^= 1;
+        assert!(authenticate(&buf[..len], DIR_REQUEST, &mut mac).is_none());
+        assert!(Request::Dump.encode(1, mac, &mut [0u8; 8]).is_none());
+    }
+
+    #[test]
+    fn test_reply_decoding() {
+        let mut frame = [0, 0, 0, 9, DIR_REPLY, STATUS_OK, 0xaa, 0, 0, 0, 0];
+        let tag = mac(&frame[..7]);
+        frame[7..].copy_from_slice(&tag.to_be_bytes());
+        assert_eq!(Reply::decode(&frame, 9, mac), Some(Reply::Ok(&[0xaa])));
+        assert_eq!(Reply::decode(&frame, 8, mac), None);
+
+        // A request reflected as a reply
+        let mut request = [0u8; 16];
+        let len = Request::Read(0x01, 1).encode(9, mac, &mut request).unwrap();
+        assert_eq!(Reply::decode(&request[..len], 9, mac), None);
+    }
+}
@@ -0,0 +1,77 @@
+//! Link event notifications, e.g. to drive LEDs, logs or link quality dashboards.
+//!
+//! Register an observer with [`Cc1101::set_observer`], the driver then calls it from the
+//! receive, transmit and watchdog APIs. Callbacks take `&self`, keep state in cells or atomics.
+
+use crate::lowlevel::registers::*;
+use crate::trace::TraceEvent;
+use crate::watchdog::Health;
+use crate::{Cc1101, PacketInfo};
+use hal::spi::SpiDevice;
+
+/// Link event callbacks, all default to doing nothing.
+pub trait LinkObserver {
+    /// A packet was read from the RX FIFO, with the RSSI (in dBm) and LQI of its reception.
+    fn on_packet(&self, _rssi_dbm: i16, _lqi: u8) {}
+    /// A packet failed the CRC check.
+    fn on_crc_error(&self, _rssi_dbm: i16) {}
+    /// A transmission completed.
+    fn on_transmit_done(&self) {}
+    /// The watchdog recovered the radio from a wedged state.
+    fn on_recovery(&self, _health: Health) {}
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Register `observer`, replacing the previous one. `None` to unregister.
+    pub fn set_observer(&mut self, observer: Option<&'static dyn LinkObserver>) {
+        self.0.observer = observer;
+    }
+
+    /// Account for the packet just read, and report it.
+    ///
+    /// `status` are the status bytes appended to the packet, if any, the RSSI and LQI
+    /// registers are read otherwise.
+    pub(crate) fn notify_packet(&mut self, status: Option<[u8; 2]>) -> Result<PacketInfo, SpiE> {
+        let status = match status {
+            Some(status) => status,
+            None => [self.0.read_register(Status::RSSI)?, self.0.read_register(Status::LQI)?],
+        };
+        let info = PacketInfo::from_status(status);
+        self.0.stats.record_packet(info.rssi_dbm, info.lqi, info.crc_ok);
+        self.0.last_packet = Some(info);
+        match self.0.observer {
+            Some(observer) if info.crc_ok => observer.on_packet(info.rssi_dbm, info.lqi),
+            Some(observer) => observer.on_crc_error(info.rssi_dbm),
+            None => {}
+        }
+        Ok(info)
+    }
+
+    /// Read the status bytes following the packet just read, when status appending is on.
+    pub(crate) fn read_appended_status(&mut self) -> Result<Option<[u8; 2]>, SpiE> {
+        if PKTCTRL1(self.0.read_register(Config::PKTCTRL1)?).append_status() == 0 {
+            return Ok(None);
+        }
+        let mut status = [0u8; 2];
+        self.0.read_fifo(&mut status)?;
+        Ok(Some(status))
+    }
+
+    pub(crate) fn notify_transmit_done(&mut self) {
+        self.0.stats.tx_packets = self.0.stats.tx_packets.wrapping_add(1);
+        if let Some(observer) = self.0.observer {
+            observer.on_transmit_done();
+        }
+    }
+
+    pub(crate) fn notify_recovery(&mut self, health: Health) {
+        self.0.stats.record_recovery(health);
+        self.0.trace_event(TraceEvent::Recovery(health));
+        if let Some(observer) = self.0.observer {
+            observer.on_recovery(health);
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! Chunked bulk transfer, for firmware update images.
+//!
+//! The [`Sender`] announces the image (length and CRC-32), the [`Receiver`] answers with the
+//! offset it wants to start from, so that an interrupted transfer resumes where it stopped,
+//! then chunks flow with at most `window` of them unacknowledged. The receiver acknowledges
+//! each chunk with the offset of the next byte it expects (cumulative acknowledgement), and
+//! drops out of order chunks, the sender goes back to the acknowledged offset when the
+//! acknowledgements stall (go-back-N).
+//!
+//! Chunks are handed to a [`ChunkSink`], e.g. writing to the update slot in flash. Frames are
+//! at most [`MAX_FRAME_LEN`] bytes, small enough for a variable length packet in the FIFO.
+//!
+//! | frame | kind | fields                                               |
+//! |-------|------|------------------------------------------------------|
+//! | start | 0x51 | length (4), image CRC-32 (4)                         |
+//! | chunk | 0x52 | offset (4), length (1), chunk CRC-32 (4), data (0-48) |
+//! | ack   | 0x53 | image CRC-32 (4), next offset (4)                    |
+//!
+//! Multi-byte fields are big endian.
+
+const KIND_START: u8 = 0x51;
+const KIND_CHUNK: u8 = 0x52;
+const KIND_ACK: u8 = 0x53;
+
+/// Largest chunk of data in a frame.
+pub const CHUNK_LEN: usize = 48;
+/// Largest frame.
+pub const MAX_FRAME_LEN: usize = 10 + CHUNK_LEN;
+
+/// CRC-32 (IEEE 802.3, as used by zip and most bootloaders) of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// A transfer frame.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Frame<'a> {
+    /// Image length and CRC-32.
+    Start(u32, u32),
+    /// Image data at the given offset.
+    Chunk(u32, &'a [u8]),
+    /// Image CRC-32 and offset of the next byte expected.
+    Ack(u32, u32),
+}
+
+impl Frame<'_> {
+    /// Encode the frame into `buf`, returns the encoded length.
+    pub fn encode(&self, buf: &mut [u8; MAX_FRAME_LEN]) -> usize {
+        match *self {
+            Frame::Start(len, crc) => {
+                buf[0] = KIND_START;
+                buf[1..5].copy_from_slice(&len.to_be_bytes());
+                buf[5..9].copy_from_slice(&crc.to_be_bytes());
+                9
+            }
+            Frame::Chunk(offset, data) => {
+                let data = &data[..data.len().min(CHUNK_LEN)];
+                buf[0] = KIND_CHUNK;
+                buf[1..5].copy_from_slice(&offset.to_be_bytes());
+                buf[5] = data.len() as u8;
+                buf[6..10].copy_from_slice(&crc32(data).to_be_bytes());
+                buf[10..10 + data.len()].copy_from_slice(data);
+                10 + data.len()
+            }
+            Frame::Ack(crc, next) => {
+                buf[0] = KIND_ACK;
+                buf[1..5].copy_from_slice(&crc.to_be_bytes());
+                buf[5..9].copy_from_slice(&next.to_be_bytes());
+                9
+            }
+        }
+    }
+
+    /// Decode a received frame, `None` if it isn't a valid transfer frame.
+    pub fn decode(frame: &[u8]) -> Option<Frame<'_>> {
+        let be32 = |at: usize| Some(u32::from_be_bytes(frame.get(at..at + 4)?.try_into().ok()?));
+        match *frame.first()? {
+            KIND_START if frame.len() == 9 => Some(Frame::Start(be32(1)?, be32(5)?)),
+            KIND_ACK if frame.len() == 9 => Some(Frame::Ack(be32(1)?, be32(5)?)),
+            KIND_CHUNK if frame.len() >= 10 && frame.len() == 10 + frame[5] as usize => {
+                let data = &frame[10..];
+                match crc32(data) == be32(6)? {
+                    true => Some(Frame::Chunk(be32(1)?, data)),
+                    false => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Sending side of a transfer.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Sender<'a> {
+    image: &'a [u8],
+    crc: u32,
+    window: u32,
+    started: bool,
+    answered: bool,
+    acked: u32,
+    next: u32,
+}
+
+impl<'a> Sender<'a> {
+    /// Send `image`, with at most `window` chunks in flight (at least 1).
+    pub fn new(image: &'a [u8], window: u32) -> Self {
+        Sender {
+            image,
+            crc: crc32(image),
+            window: window.max(1),
+            started: false,
+            answered: false,
+            acked: 0,
+            next: 0,
+        }
+    }
+
+    /// The next frame to transmit, `None` when waiting for acknowledgements (or done).
+    ///
+    /// Transmit frames until `None`, then receive and hand frames to `on_frame` until
+    /// `poll` has something to send again, or call `on_timeout` if nothing comes.
+    pub fn poll(&mut self, buf: &mut [u8; MAX_FRAME_LEN]) -> Option<usize> {
+        if !self.started {
+            // Repeated on timeouts until the receiver answers
+            self.started = true;
+            return Some(Frame::Start(self.image.len() as u32, self.crc).encode(buf));
+        }
+        if !self.answered {
+            return None;
+        }
+        let in_flight = (self.next - self.acked).div_ceil(CHUNK_LEN as u32);
+        if self.is_done() || self.next as usize >= self.image.len() || in_flight >= self.window {
+            return None;
+        }
+        let offset = self.next as usize;
+        let chunk = &self.image[offset..self.image.len().min(offset + CHUNK_LEN)];
+        self.next += chunk.len() as u32;
+        Some(Frame::Chunk(offset as u32, chunk).encode(buf))
+    }
+
+    /// Handle a received frame.
+    pub fn on_frame(&mut self, frame: &[u8]) {
+        let Some(Frame::Ack(crc, next)) = Frame::decode(frame) else {
+            return;
+        };
+        if crc != self.crc || next as usize > self.image.len() {
+            return;
+        }
+        if !self.answered {
+            // Answer to the start frame, possibly resuming
+            self.answered = true;
+            self.acked = next;
+            self.next = next;
+        } else if next > self.acked {
+            self.acked = next;
+            self.next = self.next.max(next);
+        }
+    }
+
+    /// Nothing was received for a while, retransmit from the last acknowledged offset.
+    pub fn on_timeout(&mut self) {
+        self.started = self.answered;
+        self.next = self.acked;
+    }
+
+    /// Bytes acknowledged by the receiver.
+    pub fn acked(&self) -> u32 {
+        self.acked
+    }
+
+    /// Whether the whole image has been acknowledged.
+    pub fn is_done(&self) -> bool {
+        self.answered && self.acked as usize == self.image.len()
+    }
+}
+
+/// Destination of the received image, e.g. the update slot in flash.
+pub trait ChunkSink {
+    type Error;
+
+    /// A new image of `len` bytes is starting from scratch, e.g. erase the update slot.
+    fn begin(&mut self, len: u32, crc: u32) -> Result<(), Self::Error>;
+
+    /// Write `data` at `offset`, chunks are written in order, without gaps.
+    fn write_chunk(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// The whole image has been written, it should be checked against `crc` before use.
+    fn finish(&mut self, len: u32, crc: u32) -> Result<(), Self::Error>;
+}
+
+/// Receiving side of a transfer.
+pub struct Receiver<S> {
+    sink: S,
+    len: u32,
+    crc: u32,
+    next: u32,
+}
+
+impl<S: ChunkSink> Receiver<S> {
+    /// Receive an image into `sink`.
+    pub fn new(sink: S) -> Self {
+        Self::resume(sink, 0, 0)
+    }
+
+    /// Resume the transfer of the image with CRC-32 `crc`, `offset` bytes of which have been
+    /// written already (see `progress`). Any other image starts from scratch.
+    pub fn resume(sink: S, crc: u32, offset: u32) -> Self {
+        Receiver {
+            sink,
+            len: 0,
+            crc,
+            next: offset,
+        }
+    }
+
+    /// Image CRC-32 and bytes written, to be persisted in order to resume after a restart.
+    pub fn progress(&self) -> (u32, u32) {
+        (self.crc, self.next)
+    }
+
+    /// Whether the whole image has been received.
+    pub fn is_done(&self) -> bool {
+        self.len != 0 && self.next == self.len
+    }
+
+    pub fn sink(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Handle a received frame, returns the acknowledgement to transmit, if any.
+    pub fn on_frame(
+        &mut self,
+        frame: &[u8],
+        ack: &mut [u8; MAX_FRAME_LEN],
+    ) -> Result<Option<usize>, S::Error> {
+        match Frame::decode(frame) {
+            Some(Frame::Start(len, crc)) => {
+                if crc != self.crc || self.next > len {
+                    self.sink.begin(len, crc)?;
+                    self.crc = crc;
+                    self.next = 0;
+                }
+                self.len = len;
+                if self.next == len {
+                    // Acknowledgement of the last chunk lost
+                    return Ok(Some(Frame::Ack(self.crc, self.next).encode(ack)));
+                }
+            }
+            Some(Frame::Chunk(offset, data)) if self.len != 0 => {
+                let end = offset + data.len() as u32;
+                if offset == self.next && end <= self.len {
+                    self.sink.write_chunk(offset, data)?;
+                    self.next = end;
+                    if self.next == self.len {
+                        self.sink.finish(self.len, self.crc)?;
+                    }
+                }
+            }
+            _ => return Ok(None),
+        }
+        Ok(Some(Frame::Ack(self.crc, self.next).encode(ack)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ota::*;
+
+    struct Slot {
+        image: [u8; 200],
+        finished: bool,
+    }
+
+    impl ChunkSink for Slot {
+        type Error = ();
+
+        fn begin(&mut self, _len: u32, _crc: u32) -> Result<(), ()> {
+            self.image = [0; 200];
+            Ok(())
+        }
+
+        fn write_chunk(&mut self, offset: u32, data: &[u8]) -> Result<(), ()> {
+            self.image[offset as usize..][..data.len()].copy_from_slice(data);
+            Ok(())
+        }
+
+        fn finish(&mut self, len: u32, crc: u32) -> Result<(), ()> {
+            self.finished = crc32(&self.image[..len as usize]) == crc;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_transfer() {
+        let image: [u8; 200] = core::array::from_fn(|n| (n * 7) as u8);
+        let slot = Slot {
+            image: [0; 200],
+            finished: false,
+        };
+        let mut receiver = Receiver::new(slot);
+        let mut sender = Sender::new(&image, 2);
+        let mut frame = [0u8; MAX_FRAME_LEN];
+        let mut ack = [0u8; MAX_FRAME_LEN];
+        let mut dropped = false;
+
+        while !sender.is_done() {
+            let mut acks = 0;
+            while let Some(len) = sender.poll(&mut frame) {
+                // Lose the second chunk, once
+                if !dropped && frame[0] == KIND_CHUNK && frame[4] == CHUNK_LEN as u8 {
+                    dropped = true;
+                    continue;
+                }
+                if let Some(n) = receiver.on_frame(&frame[..len], &mut ack).unwrap() {
+                    sender.on_frame(&ack[..n]);
+                    acks += 1;
+                }
+            }
+            if acks == 0 {
+                sender.on_timeout();
+            }
+        }
+        assert!(receiver.is_done());
+        assert!(receiver.sink().finished);
+        assert_eq!(receiver.sink().image, image);
+
+        // Resuming a finished transfer only takes the start frame
+        let (crc, offset) = receiver.progress();
+        let mut receiver = Receiver::resume(receiver.sink, crc, offset);
+        let mut sender = Sender::new(&image, 2);
+        let len = sender.poll(&mut frame).unwrap();
+        let n = receiver.on_frame(&frame[..len], &mut ack).unwrap().unwrap();
+        sender.on_frame(&ack[..n]);
+        assert!(sender.is_done());
+    }
+}
@@ -0,0 +1,208 @@
+//! Pairing of a device with a network.
+//!
+//! Both sides switch to the well-known pairing channel and sync word ([`Cc1101::enter_pairing`]).
+//! The network side ([`Coordinator`]) repeatedly transmits an offer carrying the operational
+//! channel, sync word and the address assigned to the device. The device ([`Joiner`]) answers
+//! the first offer it receives with an accept echoing the offer's nonce, and both sides then
+//! switch to the operational settings ([`Cc1101::apply_pairing`]).
+//!
+//! Frames are [`FRAME_LEN`] bytes, the radio must be configured for fixed length packets of
+//! that size (with CRC) while pairing:
+//!
+//! | byte | 0    | 1       | 2       | 3, 4      | 5       | 6     | 7     |
+//! |------|------|---------|---------|-----------|---------|-------|-------|
+//! |      | kind | version | channel | sync word | address | nonce | check |
+//!
+//! `check` is the XOR of the other bytes, it tells pairing frames apart from other traffic
+//! that happens to use the pairing sync word, integrity is left to the CRC.
+
+use crate::lowlevel::registers::*;
+use crate::{AddressFilter, Cc1101, Error, SyncMode};
+use hal::spi::SpiDevice;
+
+/// Channel used while pairing.
+pub const PAIRING_CHANNEL: u8 = 0;
+/// Sync word used while pairing.
+pub const PAIRING_SYNC_WORD: u16 = 0x9A7D;
+/// Length of pairing frames.
+pub const FRAME_LEN: usize = 8;
+
+const VERSION: u8 = 1;
+const KIND_OFFER: u8 = 0xA1;
+const KIND_ACCEPT: u8 = 0xA2;
+
+/// Operational network settings, exchanged while pairing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PairingParams {
+    /// Channel number (CHANNR).
+    pub channel: u8,
+    /// Sync word, matched in full.
+    pub sync_word: u16,
+    /// Address assigned to the device.
+    pub address: u8,
+}
+
+/// A pairing frame.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Frame {
+    /// Network settings offered by the coordinator, and the offer's nonce.
+    Offer(PairingParams, u8),
+    /// Offer accepted by the device, echoing the offer.
+    Accept(PairingParams, u8),
+}
+
+impl Frame {
+    pub fn encode(&self) -> [u8; FRAME_LEN] {
+        let (kind, params, nonce) = match *self {
+            Frame::Offer(params, nonce) => (KIND_OFFER, params, nonce),
+            Frame::Accept(params, nonce) => (KIND_ACCEPT, params, nonce),
+        };
+        let [sync1, sync0] = params.sync_word.to_be_bytes();
+        let mut frame = [kind, VERSION, params.channel, sync1, sync0, params.address, nonce, 0];
+        frame[7] = frame[..7].iter().fold(0, |acc, b| acc ^ b);
+        frame
+    }
+
+    /// Decode a received frame, `None` if it isn't a valid pairing frame.
+    pub fn decode(frame: &[u8]) -> Option<Self> {
+        let frame: &[u8; FRAME_LEN] = frame.try_into().ok()?;
+        if frame[1] != VERSION || frame.iter().fold(0, |acc, b| acc ^ b) != 0 {
+            return None;
+        }
+        let params = PairingParams {
+            channel: frame[2],
+            sync_word: u16::from_be_bytes([frame[3], frame[4]]),
+            address: frame[5],
+        };
+        let nonce = frame[6];
+        match frame[0] {
+            KIND_OFFER => Some(Frame::Offer(params, nonce)),
+            KIND_ACCEPT => Some(Frame::Accept(params, nonce)),
+            _ => None,
+        }
+    }
+}
+
+/// Network side of the pairing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Coordinator {
+    params: PairingParams,
+    nonce: u8,
+    paired: bool,
+}
+
+impl Coordinator {
+    /// Offer `params`, `nonce` should be random so that accepts to stale offers are ignored.
+    pub fn new(params: PairingParams, nonce: u8) -> Self {
+        Coordinator {
+            params,
+            nonce,
+            paired: false,
+        }
+    }
+
+    /// The offer to transmit, until `on_frame` reports the device has paired.
+    pub fn offer(&self) -> [u8; FRAME_LEN] {
+        Frame::Offer(self.params, self.nonce).encode()
+    }
+
+    /// Handle a received frame, returns whether the device accepted the offer.
+    pub fn on_frame(&mut self, frame: &[u8]) -> bool {
+        if let Some(Frame::Accept(params, nonce)) = Frame::decode(frame) {
+            self.paired |= params == self.params && nonce == self.nonce;
+        }
+        self.paired
+    }
+
+    pub fn is_paired(&self) -> bool {
+        self.paired
+    }
+}
+
+/// Device side of the pairing.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Joiner {
+    accepted: Option<PairingParams>,
+}
+
+impl Joiner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle a received frame, returns the accept to transmit when it's an offer.
+    ///
+    /// Once an offer has been accepted, later offers with other settings are ignored, the same
+    /// offer is accepted again in case the first accept was lost.
+    pub fn on_frame(&mut self, frame: &[u8]) -> Option<[u8; FRAME_LEN]> {
+        let Some(Frame::Offer(params, nonce)) = Frame::decode(frame) else {
+            return None;
+        };
+        if self.accepted.is_some_and(|accepted| accepted != params) {
+            return None;
+        }
+        self.accepted = Some(params);
+        Some(Frame::Accept(params, nonce).encode())
+    }
+
+    /// The accepted settings, to apply once the accept has been sent.
+    pub fn params(&self) -> Option<PairingParams> {
+        self.accepted
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Switch to the pairing channel and sync word, without address filtering.
+    pub fn enter_pairing(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.write_register(Config::CHANNR, PAIRING_CHANNEL)?;
+        self.set_sync_mode(SyncMode::MatchFull(PAIRING_SYNC_WORD))?;
+        self.set_address_filter(AddressFilter::Disabled)
+    }
+
+    /// Switch to the operational channel and sync word agreed on while pairing.
+    ///
+    /// The device should then filter on its assigned address, e.g. with
+    /// `set_address_filter(AddressFilter::DeviceLowBroadcast(params.address))`.
+    pub fn apply_pairing(&mut self, params: &PairingParams) -> Result<(), Error<SpiE>> {
+        self.0.write_register(Config::CHANNR, params.channel)?;
+        self.set_sync_mode(SyncMode::MatchFull(params.sync_word))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pairing::*;
+
+    #[test]
+    fn test_pairing() {
+        let params = PairingParams {
+            channel: 7,
+            sync_word: 0x1234,
+            address: 42,
+        };
+        let mut coordinator = Coordinator::new(params, 0x5a);
+        let mut joiner = Joiner::new();
+
+        assert_eq!(joiner.on_frame(&[0; FRAME_LEN]), None);
+        let mut corrupt = coordinator.offer();
+        corrupt[2] ^= 1;
+        assert_eq!(joiner.on_frame(&corrupt), None);
+
+        let accept = joiner.on_frame(&coordinator.offer()).unwrap();
+        assert_eq!(joiner.params(), Some(params));
+        assert!(!coordinator.on_frame(&coordinator.offer()));
+        assert!(coordinator.on_frame(&accept));
+
+        let other = Coordinator::new(
+            PairingParams {
+                channel: 3,
+                ..params
+            },
+            1,
+        );
+        assert_eq!(joiner.on_frame(&other.offer()), None);
+    }
+}
@@ -0,0 +1,262 @@
+//! Packet and bit error rate measurement.
+//!
+//! The transmitter sends numbered packets filled with the PN9 sequence, the receiver counts
+//! missing packets from gaps in the sequence numbers, and bit errors by comparing the payload
+//! against the known sequence. Bit errors are only visible in packets that were not dropped by
+//! the CRC check, so disable CRC (or CRC autoflush) when measuring BER with packets.
+//!
+//! In infinite packet mode, [`BerCounter`] compares a continuous PN9 stream instead.
+
+use crate::{Cc1101, Error};
+use hal::spi::SpiDevice;
+
+/// Length of measurement packets, 2 bytes sequence number followed by PN9 bytes.
+pub const PACKET_LEN: usize = 32;
+
+/// Period of the PN9 byte sequence.
+const PN9_PERIOD: usize = 511;
+
+/// PN9 (x^9 + x^5 + 1) byte sequence generator, the same sequence the CC1101 uses for whitening.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Pn9 {
+    state: u16,
+}
+
+impl Pn9 {
+    pub fn new() -> Self {
+        Pn9 {
+            state: 0x1ff,
+        }
+    }
+
+    pub fn next_byte(&mut self) -> u8 {
+        let byte = self.state as u8;
+        for _ in 0..8 {
+            let bit = (self.state >> 5 ^ self.state) & 1;
+            self.state = self.state >> 1 | bit << 8;
+        }
+        byte
+    }
+}
+
+impl Default for Pn9 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for Pn9 {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        Some(self.next_byte())
+    }
+}
+
+/// Measurement packet with sequence number `seq`.
+pub fn test_packet(seq: u16) -> [u8; PACKET_LEN] {
+    let mut packet = [0u8; PACKET_LEN];
+    packet[..2].copy_from_slice(&seq.to_be_bytes());
+    for (b, pn9) in packet[2..].iter_mut().zip(Pn9::new()) {
+        *b = pn9;
+    }
+    packet
+}
+
+fn bit_errors(received: &[u8], expected: impl Iterator<Item = u8>) -> u32 {
+    received.iter().zip(expected).map(|(r, e)| (r ^ e).count_ones()).sum()
+}
+
+/// Receiver side packet error rate counter.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PerCounter {
+    last: Option<u16>,
+    expected: u32,
+    received: u32,
+    bits: u32,
+    bit_errors: u32,
+}
+
+impl PerCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Account for a received measurement packet.
+    ///
+    /// Duplicates of the previous packet are ignored, packets shorter than 2 bytes are counted
+    /// as received but not checked.
+    pub fn record(&mut self, packet: &[u8]) {
+        if packet.len() < 2 {
+            self.expected += 1;
+            self.received += 1;
+            return;
+        }
+        let seq = u16::from_be_bytes([packet[0], packet[1]]);
+        self.expected += match self.last {
+            None => 1,
+            Some(last) if last == seq => return,
+            Some(last) => seq.wrapping_sub(last) as u32,
+        };
+        self.last = Some(seq);
+        self.received += 1;
+        self.bits += (packet.len() as u32 - 2) * 8;
+        self.bit_errors += bit_errors(&packet[2..], Pn9::new());
+    }
+
+    /// Packets expected from the sequence numbers seen so far.
+    pub fn expected(&self) -> u32 {
+        self.expected
+    }
+
+    /// Packets received.
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    /// Packet error rate, 0 to 1.
+    pub fn per(&self) -> f32 {
+        if self.expected == 0 {
+            return 0.0;
+        }
+        (self.expected - self.received) as f32 / self.expected as f32
+    }
+
+    /// Bit error rate over the payload of received packets, 0 to 1.
+    pub fn ber(&self) -> f32 {
+        if self.bits == 0 {
+            return 0.0;
+        }
+        self.bit_errors as f32 / self.bits as f32
+    }
+}
+
+/// Bit error rate counter for a continuous PN9 stream, e.g. received in infinite packet mode.
+///
+/// The stream has to be byte aligned with the PN9 sequence (as it is after a sync word), the
+/// phase is found from the first 3 bytes.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BerCounter {
+    pn9: Option<Pn9>,
+    head: [u8; 3],
+    head_len: usize,
+    bits: u32,
+    bit_errors: u32,
+}
+
+impl BerCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed received bytes.
+    pub fn feed(&mut self, data: &[u8]) {
+        let mut data = data;
+        while self.pn9.is_none() {
+            let Some((&b, rest)) = data.split_first() else {
+                return;
+            };
+            data = rest;
+            self.head[self.head_len] = b;
+            self.head_len += 1;
+            if self.head_len == self.head.len() {
+                self.synchronize();
+            }
+        }
+        if let Some(pn9) = self.pn9.as_mut() {
+            self.bits += data.len() as u32 * 8;
+            self.bit_errors += bit_errors(data, pn9);
+        }
+    }
+
+    /// Find the PN9 phase matching the first bytes, retrying from the next byte if none matches.
+    fn synchronize(&mut self) {
+        let mut pn9 = Pn9::new();
+        for _ in 0..PN9_PERIOD {
+            let mut candidate = pn9;
+            if self.head.iter().all(|&b| b == candidate.next_byte()) {
+                self.pn9 = Some(candidate);
+                self.bits += self.head.len() as u32 * 8;
+                return;
+            }
+            pn9.next_byte();
+        }
+        self.head.rotate_left(1);
+        self.head_len -= 1;
+    }
+
+    /// Whether the PN9 phase has been found.
+    pub fn is_synchronized(&self) -> bool {
+        self.pn9.is_some()
+    }
+
+    /// Bits compared.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Bit error rate, 0 to 1.
+    pub fn ber(&self) -> f32 {
+        if self.bits == 0 {
+            return 0.0;
+        }
+        self.bit_errors as f32 / self.bits as f32
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Transmit `count` measurement packets, numbered from `first_seq`.
+    ///
+    /// The radio must be configured for fixed `PACKET_LEN` packets.
+    pub fn transmit_per_packets(&mut self, first_seq: u16, count: u16) -> Result<(), Error<SpiE>> {
+        for n in 0..count {
+            self.transmit(&test_packet(first_seq.wrapping_add(n)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::per::*;
+
+    #[test]
+    fn test_pn9() {
+        let mut pn9 = Pn9::new();
+        let bytes: [u8; 8] = core::array::from_fn(|_| pn9.next_byte());
+        assert_eq!(bytes, [0xFF, 0xE1, 0x1D, 0x9A, 0xED, 0x85, 0x33, 0x24]);
+    }
+
+    #[test]
+    fn test_per() {
+        let mut counter = PerCounter::new();
+        counter.record(&test_packet(65534));
+        counter.record(&test_packet(65534));
+        let mut packet = test_packet(1);
+        packet[10] ^= 0b101;
+        counter.record(&packet);
+        assert_eq!(counter.expected(), 4);
+        assert_eq!(counter.received(), 2);
+        assert_eq!(counter.per(), 0.5);
+        assert_eq!(counter.ber(), 2.0 / (2.0 * 30.0 * 8.0));
+    }
+
+    #[test]
+    fn test_ber() {
+        let mut stream = [0u8; 100];
+        for (b, pn9) in stream.iter_mut().zip(Pn9::new().skip(200)) {
+            *b = pn9;
+        }
+        stream[50] ^= 0x01;
+        let mut counter = BerCounter::new();
+        counter.feed(&stream[..2]);
+        assert!(!counter.is_synchronized());
+        counter.feed(&stream[2..]);
+        assert!(counter.is_synchronized());
+        assert_eq!(counter.bits(), 800);
+        assert_eq!(counter.ber(), 1.0 / 800.0);
+    }
+}
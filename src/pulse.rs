@@ -0,0 +1,192 @@
+//! OOK pulse decoding from a timer input capture.
+//!
+//! In asynchronous serial mode the demodulated signal is output on GDO0 as is. Routed to a
+//! timer input-capture channel, the timer measures the pulses precisely (and with DMA, without
+//! an interrupt per edge), no bit-banging needed. The platform implements [`PulseCapture`] on
+//! top of its timer, [`Cc1101::configure_pulse_capture`] sets the radio up, and the pulses
+//! are decoded with [`PwmDecoder`], or turned into one bit per time unit with [`to_units`]
+//! for decoders working on raw captures, e.g. `KeeloqCode::decode`.
+
+use crate::{Cc1101, Error, PacketModeSettings, SerialMode};
+use hal::spi::SpiDevice;
+
+/// A pulse of the demodulated signal.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Pulse {
+    /// Carrier on (`true`) or off.
+    pub high: bool,
+    pub duration_us: u32,
+}
+
+/// Timer input capture of GDO0, implemented by the platform.
+pub trait PulseCapture {
+    /// Next measured pulse, `None` if none is pending.
+    ///
+    /// Pulses are returned in order, the one in progress isn't complete until the next edge.
+    fn next_pulse(&mut self) -> Option<Pulse>;
+}
+
+/// Write `pulses` to `out` as one bit per `unit_us`, MSB first, returns the number of bits.
+///
+/// Durations are rounded to the nearest number of units, at least one. Stops when `out` is
+/// full, bits past the returned count are left as they were.
+pub fn to_units(pulses: &[Pulse], unit_us: u32, out: &mut [u8]) -> usize {
+    let mut n = 0;
+    for pulse in pulses {
+        let units = ((pulse.duration_us + unit_us / 2) / unit_us).max(1) as usize;
+        for _ in 0..units {
+            if n == out.len() * 8 {
+                return n;
+            }
+            match pulse.high {
+                true => out[n / 8] |= 0x80 >> (n % 8),
+                false => out[n / 8] &= !(0x80 >> (n % 8)),
+            }
+            n += 1;
+        }
+    }
+    n
+}
+
+/// A frame decoded by `PwmDecoder`, first bit in the LSB.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PwmFrame {
+    pub bits: u64,
+    pub len: u8,
+}
+
+/// Pulse width modulation decoder: each bit is a high pulse followed by a low one, short high
+/// for 0 and long high for 1. Frames end with a low pulse of at least `gap_us`.
+///
+/// Protocols with the opposite convention get their bits inverted.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PwmDecoder {
+    short_us: u32,
+    long_us: u32,
+    gap_us: u32,
+    bits: u64,
+    len: u8,
+    high_us: Option<u32>,
+}
+
+impl PwmDecoder {
+    /// Short and long pulses, accepted within 25%.
+    pub fn new(short_us: u32, long_us: u32, gap_us: u32) -> Self {
+        PwmDecoder {
+            short_us,
+            long_us,
+            gap_us,
+            bits: 0,
+            len: 0,
+            high_us: None,
+        }
+    }
+
+    /// Decode a pulse, returns a frame when `pulse` is the gap that ends it.
+    ///
+    /// Malformed pulses discard the frame in progress.
+    pub fn push(&mut self, pulse: Pulse) -> Option<PwmFrame> {
+        if pulse.high {
+            self.high_us = Some(pulse.duration_us);
+            return None;
+        }
+        let high_us = self.high_us.take()?;
+        let bit = match (self.width(high_us), self.width(pulse.duration_us)) {
+            (Some(high_long), Some(low_long)) if high_long != low_long => high_long,
+            (Some(high_long), None) if pulse.duration_us >= self.gap_us => high_long,
+            _ => {
+                self.reset();
+                return None;
+            }
+        };
+        if self.len < 64 {
+            self.bits |= (bit as u64) << self.len;
+            self.len += 1;
+        }
+        if pulse.duration_us < self.gap_us {
+            return None;
+        }
+        let frame = PwmFrame {
+            bits: self.bits,
+            len: self.len,
+        };
+        self.reset();
+        Some(frame)
+    }
+
+    /// Drain `capture`, returns the first complete frame.
+    pub fn poll<C: PulseCapture>(&mut self, capture: &mut C) -> Option<PwmFrame> {
+        while let Some(pulse) = capture.next_pulse() {
+            if let Some(frame) = self.push(pulse) {
+                return Some(frame);
+            }
+        }
+        None
+    }
+
+    pub fn reset(&mut self) {
+        self.bits = 0;
+        self.len = 0;
+        self.high_us = None;
+    }
+
+    /// Whether `us` is a long pulse, `None` if it's neither short nor long.
+    fn width(&self, us: u32) -> Option<bool> {
+        let close = |target: u32| us.abs_diff(target) <= target / 4;
+        match (close(self.short_us), close(self.long_us)) {
+            (true, _) => Some(false),
+            (false, true) => Some(true),
+            (false, false) => None,
+        }
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Output the demodulated signal on GDO0 (asynchronous serial mode), for a timer capture.
+    ///
+    /// Packet handling is bypassed, the RX FIFO isn't used. The data rate is still used by the
+    /// demodulator, set it a few times faster than the shortest pulse. Enter RX to start,
+    /// `exit_serial_mode` to go back to packet mode.
+    pub fn configure_pulse_capture(&mut self) -> Result<PacketModeSettings, Error<SpiE>> {
+        self.enter_serial_mode(SerialMode::Asynchronous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pulse::*;
+
+    fn pulse(high: bool, duration_us: u32) -> Pulse {
+        Pulse {
+            high,
+            duration_us,
+        }
+    }
+
+    #[test]
+    fn test_pwm() {
+        let mut decoder = PwmDecoder::new(350, 1050, 5000);
+        // Noise before the frame
+        assert_eq!(decoder.push(pulse(true, 80)), None);
+        assert_eq!(decoder.push(pulse(false, 3000)), None);
+        // 1, 0, 1 with jittery timings
+        assert_eq!(decoder.push(pulse(true, 1000)), None);
+        assert_eq!(decoder.push(pulse(false, 380)), None);
+        assert_eq!(decoder.push(pulse(true, 330)), None);
+        assert_eq!(decoder.push(pulse(false, 1100)), None);
+        assert_eq!(decoder.push(pulse(true, 1070)), None);
+        let frame = decoder.push(pulse(false, 10_000)).unwrap();
+        assert_eq!((frame.bits, frame.len), (0b101, 3));
+    }
+
+    #[test]
+    fn test_to_units() {
+        let pulses = [pulse(true, 340), pulse(false, 1080), pulse(true, 700)];
+        let mut out = [0xffu8; 1];
+        assert_eq!(to_units(&pulses, 350, &mut out), 6);
+        assert_eq!(out[0], 0b1000_1111);
+    }
+}
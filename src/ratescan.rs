@@ -0,0 +1,203 @@
+//! Data rate estimation of an unknown transmitter.
+//!
+//! The raw bitstream is captured at a data rate well above the transmitter's (see
+//! `crate::capture`), so that each transmitted symbol spans several samples. The lengths of
+//! the runs between edges are then multiples of the symbol duration: the shortest frequent one
+//! gives a first estimate, refined over all the runs. Manchester coded data only has runs of
+//! one and two symbols.
+
+use core::ops::RangeInclusive;
+
+use crate::lowlevel::convert::from_drate;
+use crate::lowlevel::registers::*;
+use crate::{Cc1101, Error};
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+/// Longest run counted (in samples), longer ones are silence or long constant data.
+pub const MAX_RUN: usize = 64;
+
+/// Data rates the CC1101 supports (in baud).
+const DATA_RATES: RangeInclusive<u32> = 600..=600_000;
+
+/// Estimated transmitter settings.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RateEstimate {
+    /// Symbol rate on air (in baud).
+    pub baud: u32,
+    /// Only runs of one and two symbols: likely Manchester coded, at half `baud`.
+    pub manchester: bool,
+    /// Share of the runs that are close to a whole number of symbols, from 0 to 1.
+    pub confidence: f32,
+    /// MDMCFG3 DRATE_M for `baud`.
+    pub drate_m: u8,
+    /// MDMCFG4 DRATE_E for `baud`.
+    pub drate_e: u8,
+}
+
+/// Run length statistics of an oversampled bitstream.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EdgeStats {
+    sample_rate: u32,
+    runs: [u32; MAX_RUN + 1],
+    current: usize,
+    last: Option<bool>,
+}
+
+impl EdgeStats {
+    /// Statistics of a bitstream sampled at `sample_rate` (in samples per second).
+    pub fn new(sample_rate: u32) -> Self {
+        EdgeStats {
+            sample_rate,
+            runs: [0; MAX_RUN + 1],
+            current: 0,
+            last: None,
+        }
+    }
+
+    /// Feed captured bytes, MSB first. The first run, cut by the start of the capture, is
+    /// ignored.
+    pub fn feed(&mut self, data: &[u8]) {
+        for &byte in data {
+            for n in (0..8).rev() {
+                let bit = (byte >> n) & 1 != 0;
+                match self.last {
+                    // Runs started before the capture have an unknown length (0)
+                    Some(last) if last == bit && self.current > 0 => self.current += 1,
+                    Some(last) if last != bit => {
+                        if (1..=MAX_RUN).contains(&self.current) {
+                            self.runs[self.current] += 1;
+                        }
+                        self.current = 1;
+                    }
+                    _ => {}
+                }
+                self.last = Some(bit);
+            }
+        }
+    }
+
+    /// Forget the previous bits, e.g. at a gap in the capture.
+    pub fn restart(&mut self) {
+        self.last = None;
+        self.current = 0;
+    }
+
+    /// Estimate the symbol rate, `None` without enough edges, or if it's outside of the data
+    /// rates the CC1101 supports.
+    pub fn estimate(&self) -> Option<RateEstimate> {
+        let total: u32 = self.runs.iter().sum();
+        let peak = *self.runs.iter().max()?;
+        if total < 16 {
+            return None;
+        }
+        // Shortest run length that is frequent, shorter ones are glitches
+        let shortest = (1..=MAX_RUN).find(|&n| self.runs[n] * 4 >= peak)? as f32;
+
+        let (mut samples, mut symbols, mut close, mut double, mut longer) = (0f32, 0f32, 0, 0, 0);
+        for (len, &count) in self.runs.iter().enumerate().skip(1) {
+            let k = (len as f32 / shortest + 0.5) as u32;
+            if k == 0 || k > 8 {
+                continue;
+            }
+            samples += (len * count as usize) as f32;
+            symbols += (k * count) as f32;
+            if (len as f32 - k as f32 * shortest).abs() <= shortest / 4.0 {
+                close += count;
+            }
+            match k {
+                2 => double += count,
+                3.. => longer += count,
+                _ => {}
+            }
+        }
+        let baud = (self.sample_rate as f32 * symbols / samples + 0.5) as u32;
+        if !DATA_RATES.contains(&baud) {
+            return None;
+        }
+        let (drate_m, drate_e) = from_drate(baud as u64);
+        Some(RateEstimate {
+            baud,
+            manchester: double > 0 && longer * 20 < total,
+            confidence: close as f32 / total as f32,
+            drate_m,
+            drate_e,
+        })
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Capture `bytes` bytes at `sample_rate` (in baud), and estimate the data rate from them.
+    ///
+    /// The sample rate should be at least 8 times the expected data rate, and the channel
+    /// bandwidth wide enough for it. The data rate registers are restored afterwards.
+    pub fn estimate_data_rate<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        sample_rate: u32,
+        poll_us: u32,
+        bytes: u32,
+    ) -> Result<Option<RateEstimate>, Error<SpiE>> {
+        let (mantissa, exponent) = from_drate(sample_rate as u64);
+        let mdmcfg4 = self.0.read_register(Config::MDMCFG4)?;
+        let config = [
+            (Config::MDMCFG4, MDMCFG4(mdmcfg4).modify().drate_e(exponent).bits()),
+            (Config::MDMCFG3, MDMCFG3::default().drate_m(mantissa).bits()),
+        ];
+        let mut stats = EdgeStats::new(sample_rate);
+        let mut captured = 0;
+        self.with_config(&config, |cc1101| {
+            cc1101.capture(delay, poll_us, true, |_, data| {
+                stats.feed(data);
+                captured += data.len() as u32;
+                captured < bytes
+            })
+        })?;
+        Ok(stats.estimate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ratescan::*;
+
+    fn stream(pattern: &[usize]) -> [u8; 200] {
+        let mut bits = [0u8; 200];
+        let (mut n, mut level) = (0, 1);
+        for &run in pattern.iter().cycle().take(70) {
+            for _ in 0..run {
+                if n < bits.len() * 8 {
+                    bits[n / 8] |= level << (7 - n % 8);
+                }
+                n += 1;
+            }
+            level ^= 1;
+        }
+        bits
+    }
+
+    #[test]
+    fn test_estimate() {
+        // 10 kbaud sampled at 100 kHz, runs of 10 and 20 samples for Manchester
+        let mut stats = EdgeStats::new(100_000);
+        stats.feed(&stream(&[10, 20, 10, 10, 20, 20, 10, 10, 10, 20]));
+        let estimate = stats.estimate().unwrap();
+        assert_eq!(estimate.baud, 10_000);
+        assert!(estimate.manchester);
+        assert!(estimate.confidence > 0.9);
+
+        let mut stats = EdgeStats::new(100_000);
+        stats.feed(&stream(&[10, 30, 20, 10, 40, 11, 9]));
+        let estimate = stats.estimate().unwrap();
+        assert!((9_900..=10_100).contains(&estimate.baud));
+        assert!(!estimate.manchester);
+
+        // 50 baud, below what the CC1101 supports
+        let mut stats = EdgeStats::new(600);
+        stats.feed(&stream(&[12, 24, 12, 12]));
+        assert_eq!(stats.estimate(), None);
+    }
+}
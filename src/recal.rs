@@ -0,0 +1,187 @@
+//! Scheduled manual recalibration, for radios running with autocalibration disabled.
+//!
+//! Skipping calibration on every RX/TX transition saves ~700 µs, but the synthesizer drifts
+//! out of lock as the temperature changes. [`RecalScheduler`] decides when to recalibrate:
+//! after a temperature change, a number of transitions, or some time. The temperature can come
+//! from the on-chip sensor, routed to GDO0 (see [`Cc1101::enable_temperature_output`]) and read
+//! with an ADC.
+
+use crate::lowlevel::registers::*;
+use crate::{Cc1101, Error, RadioMode};
+use hal::spi::SpiDevice;
+
+/// Temperature sensor output at 0 °C (in millivolts).
+const TEMP_MV_AT_0C: f32 = 747.0;
+/// Temperature sensor slope (in millivolts per °C).
+const TEMP_MV_PER_C: f32 = 2.47;
+
+/// Temperature (in °C) from the voltage of the on-chip sensor (in millivolts).
+///
+/// Typical datasheet values, calibrate at a known temperature for better than ±2 °C.
+pub fn temperature_from_mv(mv: f32) -> f32 {
+    (mv - TEMP_MV_AT_0C) / TEMP_MV_PER_C
+}
+
+/// When to recalibrate, any condition triggers it. `None` disables a condition.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RecalPolicy {
+    /// Temperature change since the last calibration (in °C).
+    pub temp_delta_c: Option<f32>,
+    /// RX/TX transitions since the last calibration.
+    pub transitions: Option<u32>,
+    /// Time since the last calibration (in milliseconds).
+    pub elapsed_ms: Option<u32>,
+}
+
+/// Tracks the conditions of a `RecalPolicy`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RecalScheduler {
+    policy: RecalPolicy,
+    temp_c: Option<f32>,
+    calibrated_temp_c: Option<f32>,
+    transitions: u32,
+    elapsed_ms: u32,
+    fscal: Option<[u8; 3]>,
+}
+
+impl RecalScheduler {
+    /// A scheduler due right away, the first `poll` calibrates.
+    pub fn new(policy: RecalPolicy) -> Self {
+        RecalScheduler {
+            policy,
+            temp_c: None,
+            calibrated_temp_c: None,
+            transitions: 0,
+            elapsed_ms: 0,
+            fscal: None,
+        }
+    }
+
+    /// Record a new temperature reading (in °C).
+    pub fn on_temperature(&mut self, temp_c: f32) {
+        self.temp_c = Some(temp_c);
+    }
+
+    /// Record an RX/TX transition.
+    pub fn on_transition(&mut self) {
+        self.transitions = self.transitions.saturating_add(1);
+    }
+
+    /// Let time pass (in milliseconds).
+    pub fn advance(&mut self, ms: u32) {
+        self.elapsed_ms = self.elapsed_ms.saturating_add(ms);
+    }
+
+    /// Whether a condition of the policy is met.
+    pub fn is_due(&self) -> bool {
+        let drifted = match (self.policy.temp_delta_c, self.temp_c, self.calibrated_temp_c) {
+            (Some(delta), Some(now), Some(then)) => (now - then).abs() >= delta,
+            _ => false,
+        };
+        let transitions = self.policy.transitions.is_some_and(|n| self.transitions >= n);
+        let elapsed = self.policy.elapsed_ms.is_some_and(|ms| self.elapsed_ms >= ms);
+        self.fscal.is_none() || drifted || transitions || elapsed
+    }
+
+    /// FSCAL3, FSCAL2 and FSCAL1 from the last calibration.
+    pub fn fscal(&self) -> Option<[u8; 3]> {
+        self.fscal
+    }
+
+    /// Recalibrate if due, returns whether it did.
+    ///
+    /// The radio goes through IDLE and is put back in RX if it was there.
+    pub fn poll<SPI, SpiE>(&mut self, cc1101: &mut Cc1101<SPI>) -> Result<bool, Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        if !self.is_due() {
+            return Ok(false);
+        }
+        let resume_rx = cc1101.current_mode()? == Some(RadioMode::Receive);
+        self.fscal = Some(cc1101.recalibrate()?);
+        if resume_rx {
+            cc1101.set_radio_mode(RadioMode::Receive)?;
+        }
+        self.calibrated_temp_c = self.temp_c;
+        self.transitions = 0;
+        self.elapsed_ms = 0;
+        Ok(true)
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Calibrate the synthesizer now, returns the resulting FSCAL3, FSCAL2 and FSCAL1.
+    ///
+    /// Leaves the radio in IDLE. The values can be written back with `restore_fscal` to skip
+    /// the calibration when coming back to the same frequency.
+    pub fn recalibrate(&mut self) -> Result<[u8; 3], Error<SpiE>> {
+        self.set_radio_mode(RadioMode::Calibrate)?;
+        let mut fscal = [0u8; 3];
+        self.0.read_config_burst(Config::FSCAL3, &mut fscal)?;
+        Ok(fscal)
+    }
+
+    /// Write back FSCAL3, FSCAL2 and FSCAL1 from a previous calibration.
+    pub fn restore_fscal(&mut self, fscal: &[u8; 3]) -> Result<(), Error<SpiE>> {
+        self.0.write_config_burst(Config::FSCAL3, fscal)?;
+        Ok(())
+    }
+
+    /// Go to IDLE and output the temperature sensor voltage on GDO0, convert the ADC reading
+    /// with `temperature_from_mv`.
+    ///
+    /// The sensor needs PTEST changed from its default, which the datasheet requires written
+    /// back before leaving IDLE: call `disable_temperature_output` with the returned previous
+    /// GDO0 configuration once done.
+    pub fn enable_temperature_output(&mut self) -> Result<u8, Error<SpiE>> {
+        self.set_radio_mode(RadioMode::Idle)?;
+        let iocfg0 = self.0.read_register(Config::IOCFG0)?;
+        let temp_sensor = IOCFG0::default().temp_sensor_enable(1).gdo0_cfg(0).bits();
+        self.0.write_register(Config::IOCFG0, temp_sensor)?;
+        self.0.write_register(Config::PTEST, 0xBF)?;
+        Ok(iocfg0)
+    }
+
+    /// Stop the temperature sensor output, restoring GDO0 to `iocfg0` (as returned by
+    /// `enable_temperature_output`) and PTEST to its default.
+    pub fn disable_temperature_output(&mut self, iocfg0: u8) -> Result<(), Error<SpiE>> {
+        self.0.write_register(Config::IOCFG0, iocfg0)?;
+        self.0.write_register(Config::PTEST, PTEST::default().bits())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::recal::*;
+
+    #[test]
+    fn test_recal_scheduler() {
+        let mut scheduler = RecalScheduler::new(RecalPolicy {
+            temp_delta_c: Some(5.0),
+            transitions: Some(100),
+            elapsed_ms: None,
+        });
+        assert!(scheduler.is_due());
+        // As if `poll` had calibrated
+        scheduler.fscal = Some([0; 3]);
+        scheduler.calibrated_temp_c = Some(20.0);
+        scheduler.transitions = 0;
+        scheduler.elapsed_ms = 0;
+
+        scheduler.on_temperature(24.0);
+        scheduler.advance(1_000_000);
+        assert!(!scheduler.is_due());
+        scheduler.on_temperature(25.5);
+        assert!(scheduler.is_due());
+        scheduler.on_temperature(20.0);
+        (0..100).for_each(|_| scheduler.on_transition());
+        assert!(scheduler.is_due());
+
+        assert!((temperature_from_mv(796.4) - 20.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,234 @@
+//! Transmit templates for common 433 MHz mains-socket remotes.
+//!
+//! Frames are sent as raw OOK: each bit in the FIFO is one time unit of carrier on (1) or off
+//! (0), with no preamble, sync word, CRC or whitening added by the radio.
+
+use crate::lowlevel::registers::*;
+use crate::lowlevel::FIFO_SIZE;
+use crate::{Cc1101, Error, Modulation, PacketLength, SyncMode};
+use hal::spi::SpiDevice;
+
+/// Time unit of PT2262-style fixed-code remotes (in microseconds).
+const FIXED_CODE_UNIT_US: u32 = 350;
+/// Time unit of Intertechno learning-code remotes (in microseconds).
+const LEARNING_CODE_UNIT_US: u32 = 260;
+
+/// A raw OOK frame, one bit per time unit, MSB first.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OokFrame {
+    data: [u8; FIFO_SIZE],
+    len: usize,
+    unit_us: u32,
+}
+
+impl OokFrame {
+    pub(crate) fn new(unit_us: u32) -> Self {
+        OokFrame {
+            data: [0u8; FIFO_SIZE],
+            len: 0,
+            unit_us,
+        }
+    }
+
+    /// Append `units` time units of carrier on (`high`) or off.
+    pub(crate) fn push(&mut self, high: bool, units: usize) {
+        for _ in 0..units {
+            if high {
+                self.data[self.len / 8] |= 0x80 >> (self.len % 8);
+            }
+            self.len += 1;
+        }
+    }
+
+    /// Frame contents, padded with carrier off up to a whole byte.
+    pub fn bytes(&self) -> &[u8] {
+        &self.data[..self.len.div_ceil(8)]
+    }
+
+    /// Length of the frame in time units.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Duration of one time unit (in microseconds).
+    pub fn unit_us(&self) -> u32 {
+        self.unit_us
+    }
+
+    /// Data rate the radio has to be configured for (in bits per second).
+    pub fn data_rate(&self) -> u64 {
+        1_000_000 / self.unit_us as u64
+    }
+}
+
+/// PT2262 tri-state symbol, the remotes below only use 0 and F.
+#[derive(Copy, Clone)]
+enum Trit {
+    Zero,
+    Float,
+}
+
+impl Trit {
+    fn from_bit(bit: bool, set: Trit, clear: Trit) -> Trit {
+        if bit {
+            set
+        } else {
+            clear
+        }
+    }
+}
+
+fn fixed_code(trits: &[Trit; 12]) -> OokFrame {
+    let mut frame = OokFrame::new(FIXED_CODE_UNIT_US);
+    for trit in trits {
+        let second = match trit {
+            Trit::Zero => 1,
+            Trit::Float => 3,
+        };
+        frame.push(true, 1);
+        frame.push(false, 3);
+        frame.push(true, second);
+        frame.push(false, 4 - second);
+    }
+    // Sync
+    frame.push(true, 1);
+    frame.push(false, 31);
+    frame
+}
+
+/// Intertechno fixed-code remote.
+///
+/// - `house`: house code A-P as 0-15
+/// - `unit`: unit 1-16 as 0-15
+pub fn intertechno_fixed(house: u8, unit: u8, on: bool) -> OokFrame {
+    let code = (house & 0x0f) | (unit & 0x0f) << 4;
+    let mut trits = [Trit::Zero; 12];
+    for (n, trit) in trits[..8].iter_mut().enumerate() {
+        *trit = Trit::from_bit(code >> n & 1 == 1, Trit::Float, Trit::Zero);
+    }
+    trits[9] = Trit::Float;
+    trits[10] = Trit::Float;
+    trits[11] = Trit::from_bit(on, Trit::Float, Trit::Zero);
+    fixed_code(&trits)
+}
+
+/// Elro-style fixed-code remote, with 10 DIP switches.
+///
+/// - `house`: the 5 house code switches, switch 1 as MSB, set bit for ON
+/// - `unit`: the 5 unit switches (A-E), switch A as MSB, set bit for ON
+pub fn elro(house: u8, unit: u8, on: bool) -> OokFrame {
+    let code = (house as u16 & 0x1f) << 5 | (unit as u16 & 0x1f);
+    let mut trits = [Trit::Zero; 12];
+    for (n, trit) in trits[..10].iter_mut().enumerate() {
+        *trit = Trit::from_bit(code >> (9 - n) & 1 == 1, Trit::Zero, Trit::Float);
+    }
+    trits[10] = Trit::from_bit(on, Trit::Zero, Trit::Float);
+    trits[11] = Trit::from_bit(on, Trit::Float, Trit::Zero);
+    fixed_code(&trits)
+}
+
+/// Intertechno learning-code (self-learning) remote.
+///
+/// - `address`: 26-bit transmitter address
+/// - `group`: address all units instead of `unit`
+/// - `unit`: unit 0-15
+pub fn intertechno_learning(address: u32, group: bool, unit: u8, on: bool) -> OokFrame {
+    let mut frame = OokFrame::new(LEARNING_CODE_UNIT_US);
+    let push_bit = |frame: &mut OokFrame, bit: bool| {
+        let first = if bit {
+            5
+        } else {
+            1
+        };
+        frame.push(true, 1);
+        frame.push(false, first);
+        frame.push(true, 1);
+        frame.push(false, 6 - first);
+    };
+    // Start
+    frame.push(true, 1);
+    frame.push(false, 10);
+    for n in (0..26).rev() {
+        push_bit(&mut frame, address >> n & 1 == 1);
+    }
+    push_bit(&mut frame, group);
+    push_bit(&mut frame, on);
+    for n in (0..4).rev() {
+        push_bit(&mut frame, unit >> n & 1 == 1);
+    }
+    // Stop
+    frame.push(true, 1);
+    frame.push(false, 40);
+    frame
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Configure the radio for raw OOK transmission of `frame`.
+    ///
+    /// - OOK modulation at the frame data rate
+    /// - No preamble or sync word, no CRC, no whitening, no Manchester encoding
+    /// - PATABLE\[0\] off, PATABLE\[1\] = `power` for the carrier on symbols
+    pub fn configure_raw_ook(&mut self, frame: &OokFrame, power: u8) -> Result<(), Error<SpiE>> {
+        self.set_modulation(Modulation::OnOffKeying)?;
+        self.set_data_rate(frame.data_rate())?;
+        self.set_sync_mode(SyncMode::Disabled)?;
+        self.0.modify_register(Config::MDMCFG2, |r| MDMCFG2(r).modify().manchester_en(0).bits())?;
+        self.0.modify_register(Config::PKTCTRL0, |r| {
+            PKTCTRL0(r).modify().white_data(0).crc_en(0).bits()
+        })?;
+        self.set_pa_ramp(&[0x00, power])
+    }
+
+    /// Transmit `frame` `repeats` times, the radio must be set up with `configure_raw_ook`.
+    ///
+    /// Blocks until each repeat has been sent and the radio left TX (per `set_txoff_mode`), the
+    /// TX FIFO is flushed if back in IDLE.
+    pub fn transmit_raw_ook(&mut self, frame: &OokFrame, repeats: u8) -> Result<(), Error<SpiE>> {
+        self.set_packet_length(PacketLength::Fixed(frame.bytes().len() as u8))?;
+        for _ in 0..repeats {
+            self.write_tx_fifo(frame.bytes())?;
+            self.set_radio_mode(crate::RadioMode::Transmit)?;
+            self.finish_transmit()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remotes::*;
+
+    #[test]
+    fn test_fixed_code() {
+        // A1 on: 0000 0000 0FFF
+        let frame = intertechno_fixed(0, 0, true);
+        assert_eq!(frame.len(), 12 * 8 + 32);
+        assert_eq!(frame.bytes()[..9], [0b1000_1000; 9]);
+        assert_eq!(frame.bytes()[9..12], [0b1000_1110; 3]);
+        // Sync
+        assert_eq!(frame.bytes()[12..], [0b1000_0000, 0, 0, 0]);
+        assert_eq!(frame.data_rate(), 2857);
+
+        // Elro: all house switches on, unit A, off: 00000 0FFFF F0
+        let frame = elro(0b11111, 0b10000, false);
+        assert_eq!(frame.bytes()[..6], [0b1000_1000; 6]);
+        assert_eq!(frame.bytes()[6..11], [0b1000_1110; 5]);
+        assert_eq!(frame.bytes()[11], 0b1000_1000);
+    }
+
+    #[test]
+    fn test_learning_code() {
+        let frame = intertechno_learning(0x3FF_FFFF, false, 0, true);
+        assert_eq!(frame.len(), 11 + 32 * 8 + 41);
+        assert!(frame.bytes().len() <= FIFO_SIZE);
+        // Start, followed by the first address bits (1)
+        assert_eq!(frame.bytes()[..3], [0b1000_0000, 0b0001_0000, 0b0101_0000]);
+    }
+}
@@ -1,11 +1,143 @@
-const RSSI_OFFSET: i16 = 74; // Table 31: Typical RSSI_offset Values
+pub const RSSI_OFFSET: i16 = 74; // Table 31: Typical RSSI_offset Values
 
 pub fn rssi_to_dbm(raw: u8) -> i16 {
+    rssi_to_dbm_with_offset(raw, RSSI_OFFSET)
+}
+
+/// Same as `rssi_to_dbm`, but with a custom RSSI offset, e.g. to account for an external LNA.
+pub fn rssi_to_dbm_with_offset(raw: u8, offset: i16) -> i16 {
     let rssi = raw as i16;
     // According to spec 17.3
     if rssi < 128 {
-        rssi / 2 - RSSI_OFFSET
+        rssi / 2 - offset
     } else {
-        (rssi - 256) / 2 - RSSI_OFFSET
+        (rssi - 256) / 2 - offset
+    }
+}
+
+/// Width of a histogram bucket (in dB).
+pub const HISTOGRAM_BUCKET_DB: i16 = 4;
+/// Lower bound of the first histogram bucket (in dBm), the lowest RSSI the chip can report.
+pub const HISTOGRAM_MIN_DBM: i16 = -128 / 2 - RSSI_OFFSET;
+/// Number of histogram buckets, covering the whole RSSI range.
+pub const HISTOGRAM_BUCKETS: usize = 32;
+
+/// Histogram of RSSI samples, in fixed dBm buckets.
+///
+/// Counts saturate at `u16::MAX`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RssiHistogram {
+    counts: [u16; HISTOGRAM_BUCKETS],
+}
+
+impl RssiHistogram {
+    pub const fn new() -> Self {
+        RssiHistogram {
+            counts: [0; HISTOGRAM_BUCKETS],
+        }
+    }
+
+    /// Count one sample (in dBm), out of range values go to the first or last bucket.
+    pub fn record(&mut self, dbm: i16) {
+        let index = (dbm.saturating_sub(HISTOGRAM_MIN_DBM) / HISTOGRAM_BUCKET_DB)
+            .clamp(0, HISTOGRAM_BUCKETS as i16 - 1) as usize;
+        self.counts[index] = self.counts[index].saturating_add(1);
+    }
+
+    /// Lower bound of bucket `index` (in dBm).
+    pub const fn bucket_dbm(index: usize) -> i16 {
+        HISTOGRAM_MIN_DBM + index as i16 * HISTOGRAM_BUCKET_DB
+    }
+
+    /// Copy of the current counts.
+    pub fn snapshot(&self) -> [u16; HISTOGRAM_BUCKETS] {
+        self.counts
+    }
+
+    /// Reset all counts.
+    pub fn clear(&mut self) {
+        self.counts = [0; HISTOGRAM_BUCKETS];
+    }
+}
+
+impl Default for RssiHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Von Neumann extractor: unbiased bits out of biased, independent ones.
+///
+/// Bits are taken in pairs, `01` gives `0`, `10` gives `1`, equal pairs are dropped.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct Whitener {
+    first: Option<bool>,
+}
+
+impl Whitener {
+    pub(crate) fn push(&mut self, bit: bool) -> Option<bool> {
+        match self.first.take() {
+            None => {
+                self.first = Some(bit);
+                None
+            }
+            Some(first) => (first != bit).then_some(first),
+        }
+    }
+
+    /// Same as `push`, shifting the output bit (if any) into `byte` from the right. Returns
+    /// whether a bit was shifted in.
+    pub(crate) fn shift_into(&mut self, byte: &mut u8, bit: bool) -> bool {
+        match self.push(bit) {
+            Some(out) => {
+                *byte = (*byte << 1) | out as u8;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rssi::*;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = RssiHistogram::new();
+        histogram.record(-138);
+        histogram.record(-135);
+        histogram.record(-134);
+        // Strongest RSSI the chip can report
+        histogram.record(rssi_to_dbm(0x7F));
+        // Out of range
+        histogram.record(-200);
+        histogram.record(0);
+        let counts = histogram.snapshot();
+        assert_eq!((counts[0], counts[1]), (3, 1));
+        assert_eq!(counts[HISTOGRAM_BUCKETS - 1], 2);
+        assert_eq!(RssiHistogram::bucket_dbm(HISTOGRAM_BUCKETS - 1), -14);
+        histogram.clear();
+        assert_eq!(histogram.snapshot(), [0; HISTOGRAM_BUCKETS]);
+    }
+
+    #[test]
+    fn test_whitener() {
+        let mut whitener = Whitener::default();
+        assert_eq!(whitener.push(false), None);
+        assert_eq!(whitener.push(true), Some(false));
+        assert_eq!(whitener.push(true), None);
+        assert_eq!(whitener.push(false), Some(true));
+        // Equal pairs are dropped
+        assert_eq!(whitener.push(false), None);
+        assert_eq!(whitener.push(false), None);
+        assert_eq!(whitener.push(true), None);
+        assert_eq!(whitener.push(true), None);
+
+        // 1, dropped, 0, 1, 1, dropped, 0, 0, 1, 0
+        let bits = [1, 0, 1, 1, 0, 1, 1, 0, 1, 0, 0, 0, 0, 1, 0, 1, 1, 0, 0, 1];
+        let mut byte = 0;
+        let shifted = bits.iter().filter(|&&bit| whitener.shift_into(&mut byte, bit != 0)).count();
+        assert_eq!((shifted, byte), (8, 0b1011_0010));
     }
 }
@@ -0,0 +1,143 @@
+//! Reception with a timeout, explaining what was (not) heard when it expires.
+//!
+//! While waiting, PKTSTATUS and the RSSI are sampled: carrier sense, preamble quality and sync
+//! word detection tell "nothing transmitted" apart from "wrong modulation settings" and
+//! "wrong sync word".
+
+use crate::lowlevel::registers::*;
+use crate::rssi::rssi_to_dbm;
+use crate::{Cc1101, Error, RadioMode};
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+/// What was observed during a receive window.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RxDiagnostics {
+    /// Carrier sense asserted at least once.
+    pub carrier_seen: bool,
+    /// Preamble quality threshold reached at least once.
+    pub preamble_seen: bool,
+    /// Sync word found at least once.
+    pub sync_seen: bool,
+    /// Lowest RSSI sampled (in dBm).
+    pub rssi_min_dbm: i16,
+    /// Highest RSSI sampled (in dBm).
+    pub rssi_max_dbm: i16,
+    /// Number of samples.
+    pub samples: u32,
+}
+
+/// Most likely reason for not receiving a packet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RxVerdict {
+    /// No carrier, nothing transmitted, wrong frequency, or carrier sense threshold too high.
+    NoCarrier,
+    /// Carrier but no preamble: data rate, deviation, modulation or bandwidth mismatch.
+    NoPreamble,
+    /// Preamble but no sync word: wrong sync word or sync mode.
+    NoSync,
+    /// Sync word found but no packet kept: CRC, length or address check failure.
+    PacketDropped,
+}
+
+impl RxDiagnostics {
+    fn new() -> Self {
+        RxDiagnostics {
+            carrier_seen: false,
+            preamble_seen: false,
+            sync_seen: false,
+            rssi_min_dbm: i16::MAX,
+            rssi_max_dbm: i16::MIN,
+            samples: 0,
+        }
+    }
+
+    fn record(&mut self, pktstatus: u8, rssi_dbm: i16) {
+        let pktstatus = PKTSTATUS(pktstatus);
+        self.carrier_seen |= pktstatus.cs() != 0;
+        self.preamble_seen |= pktstatus.pqt_reached() != 0;
+        self.sync_seen |= pktstatus.sfd() != 0;
+        self.rssi_min_dbm = self.rssi_min_dbm.min(rssi_dbm);
+        self.rssi_max_dbm = self.rssi_max_dbm.max(rssi_dbm);
+        self.samples += 1;
+    }
+
+    /// Interpret the observations, short packets can slip between samples, so this is a hint.
+    pub fn verdict(&self) -> RxVerdict {
+        match (self.carrier_seen, self.preamble_seen, self.sync_seen) {
+            (_, _, true) => RxVerdict::PacketDropped,
+            (_, true, false) => RxVerdict::NoSync,
+            (true, false, false) => RxVerdict::NoPreamble,
+            (false, false, false) => RxVerdict::NoCarrier,
+        }
+    }
+}
+
+/// Outcome of `receive_timeout`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Received {
+    /// A packet of the given length was read.
+    Packet(usize),
+    /// No packet before the timeout.
+    Timeout(RxDiagnostics),
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Receive a variable length packet into `buf`, giving up after `timeout_us`.
+    ///
+    /// On timeout the radio is put back in IDLE, and the diagnostics gathered while waiting
+    /// are returned.
+    pub fn receive_timeout<D: DelayNs>(
+        &mut self,
+        buf: &mut [u8],
+        delay: &mut D,
+        timeout_us: u32,
+    ) -> Result<Received, Error<SpiE>> {
+        let mut diag = RxDiagnostics::new();
+        let mut waited_us = 0;
+        self.set_radio_mode(RadioMode::Receive)?;
+        while waited_us < timeout_us {
+            let mut regs = [0u8; 3];
+            self.0.read_status_registers(
+                &[Status::RXBYTES, Status::PKTSTATUS, Status::RSSI],
+                &mut regs,
+            )?;
+            if RXBYTES(regs[0]).rxfifo_overflow() != 0 {
+                return self.recover_rx_overflow();
+            }
+            let pktstatus = PKTSTATUS(regs[1]);
+            diag.record(regs[1], rssi_to_dbm(regs[2]));
+            // Data in the FIFO with no packet in progress: a packet is complete
+            if RXBYTES(regs[0]).num_rxbytes() > 0 && pktstatus.sfd() == 0 {
+                if let Some(packet) = self.receive_filtered(buf, |_| true)? {
+                    return Ok(Received::Packet(packet.len));
+                }
+            }
+            delay.delay_us(self.0.poll_interval_us);
+            waited_us = waited_us.saturating_add(self.0.poll_interval_us);
+        }
+        self.set_radio_mode(RadioMode::Idle)?;
+        Ok(Received::Timeout(diag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lowlevel::registers::PKTSTATUS;
+    use crate::rxdiag::*;
+
+    #[test]
+    fn test_verdict() {
+        let mut diag = RxDiagnostics::new();
+        diag.record(0, -100);
+        assert_eq!(diag.verdict(), RxVerdict::NoCarrier);
+        diag.record(PKTSTATUS::default().cs(1).bits(), -60);
+        assert_eq!(diag.verdict(), RxVerdict::NoPreamble);
+        diag.record(PKTSTATUS::default().pqt_reached(1).bits(), -62);
+        assert_eq!(diag.verdict(), RxVerdict::NoSync);
+        assert_eq!((diag.rssi_min_dbm, diag.rssi_max_dbm, diag.samples), (-100, -60, 3));
+    }
+}
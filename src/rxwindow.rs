@@ -0,0 +1,154 @@
+//! Receive windows synchronized to a periodic beacon.
+//!
+//! In a star network where the gateway beacons at a known interval, a node only needs RX
+//! around the expected beacon times. [`RxWindowTracker`] predicts when to open the window,
+//! correcting for the drift between the two clocks from the actual arrival times, and
+//! [`Cc1101::configure_rx_window`] makes the radio leave RX by itself when no sync word
+//! shows up within the window (MCSM2 RX_TIME), so the node's timer is only needed to open it.
+//!
+//! ```ignore
+//! cc1101.configure_rx_window(window_us)?;
+//! loop {
+//!     sleep_until(tracker.next_open_us(now()));
+//!     cc1101.to_rx()?;
+//!     // On sync word: tracker.on_beacon(sync_time), read the packet
+//! }
+//! ```
+
+use crate::lowlevel::registers::*;
+use crate::lowlevel::FXOSC;
+use crate::{Cc1101, Error, RxTime};
+use hal::spi::SpiDevice;
+
+/// RX timeout for RX_TIME = 0, as a fraction of EVENT0 with WOR_RES = 0 (in millionths).
+pub(crate) const RX_TIME_0_FRACTION: u64 = 36_058;
+
+/// WOR_RES, EVENT0 and resulting RX timeout (in microseconds) for a timeout of at least
+/// `window_us`, with RX_TIME = 0.
+///
+/// The timeout is 750/FXOSC·EVENT0·2^(5·WOR_RES) scaled by 3.6058%/2^WOR_RES (MCSM2 RX_TIME
+/// table). Saturates at the longest timeout.
+pub fn rx_timeout_settings(window_us: u32) -> (u8, u16, u32) {
+    let mut wor_res = 0;
+    loop {
+        let unit = (750 * RX_TIME_0_FRACTION) << (4 * wor_res);
+        let event0 = (window_us as u64 * FXOSC).div_ceil(unit).clamp(1, u16::MAX as u64);
+        if wor_res == 3 || event0 * unit >= window_us as u64 * FXOSC {
+            let timeout_us = (event0 * unit / FXOSC).min(u32::MAX as u64) as u32;
+            return (wor_res, event0 as u16, timeout_us);
+        }
+        wor_res += 1;
+    }
+}
+
+/// Predicts beacon arrival times and tracks the drift of the beacon clock.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RxWindowTracker {
+    period_us: u32,
+    window_us: u32,
+    drift_ppm: i32,
+    last_us: Option<u64>,
+}
+
+impl RxWindowTracker {
+    /// Beacons are nominally `period_us` apart, listen for `window_us` around each.
+    pub fn new(period_us: u32, window_us: u32) -> Self {
+        RxWindowTracker {
+            period_us,
+            window_us,
+            drift_ppm: 0,
+            last_us: None,
+        }
+    }
+
+    /// A beacon arrived at `arrival_us` (local clock), e.g. when the sync word was detected.
+    ///
+    /// The drift estimate is updated from the time since the previous beacon, taking missed
+    /// beacons into account.
+    pub fn on_beacon(&mut self, arrival_us: u64) {
+        if let Some(last_us) = self.last_us {
+            let elapsed = arrival_us.saturating_sub(last_us);
+            let periods = (elapsed + self.period_us as u64 / 2) / self.period_us as u64;
+            if periods > 0 {
+                let error = elapsed as i64 - (periods * self.period_us as u64) as i64;
+                let ppm = (error * 1_000_000 / (periods * self.period_us as u64) as i64) as i32;
+                // Smooth out the jitter of individual arrivals
+                self.drift_ppm += (ppm - self.drift_ppm) / 4;
+            }
+        }
+        self.last_us = Some(arrival_us);
+    }
+
+    /// Beacon period corrected for the estimated drift (in microseconds).
+    pub fn period_us(&self) -> u32 {
+        (self.period_us as i64 + self.period_us as i64 * self.drift_ppm as i64 / 1_000_000) as u32
+    }
+
+    /// Estimated drift of the beacon clock relative to the local clock (in ppm).
+    pub fn drift_ppm(&self) -> i32 {
+        self.drift_ppm
+    }
+
+    /// Time to open the next RX window not starting before `now_us`, centered on the expected
+    /// beacon arrival. `None` until the first beacon has been received.
+    pub fn next_open_us(&self, now_us: u64) -> Option<u64> {
+        let last_us = self.last_us?;
+        let period = self.period_us() as u64;
+        let half_window = self.window_us as u64 / 2;
+        // First expected arrival whose window opens at or after `now_us`
+        let earliest = now_us.saturating_sub(last_us) + half_window;
+        let periods = earliest.div_ceil(period).max(1);
+        Some(last_us + periods * period - half_window)
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Make RX time out after `window_us` when no sync word is found.
+    ///
+    /// Sets MCSM2 RX_TIME, EVENT0 and WOR_RES, which are shared with Wake-on-Radio. Returns the
+    /// actual timeout (in microseconds), which is never shorter than `window_us` unless that's
+    /// beyond the longest possible timeout.
+    pub fn configure_rx_window(&mut self, window_us: u32) -> Result<u32, Error<SpiE>> {
+        let (wor_res, event0, timeout_us) = rx_timeout_settings(window_us);
+        // WOREVT1, WOREVT0, WORCTRL
+        self.0.modify_config_burst(Config::WOREVT1, |[evt1, evt0, worctrl]: &mut [u8; 3]| {
+            [*evt1, *evt0] = event0.to_be_bytes();
+            *worctrl = WORCTRL(*worctrl).modify().wor_res(wor_res).bits();
+        })?;
+        self.set_rx_timeout(RxTime {
+            timeout: Some(0),
+            ..RxTime::default()
+        })?;
+        Ok(timeout_us)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rxwindow::*;
+
+    #[test]
+    fn test_rx_timeout_settings() {
+        assert_eq!(rx_timeout_settings(10_000), (0, 9984, 10_000));
+        let (wor_res, _, timeout) = rx_timeout_settings(500_000);
+        assert_eq!(wor_res, 1);
+        assert!((500_000..500_100).contains(&timeout));
+        assert_eq!(rx_timeout_settings(u32::MAX).1, u16::MAX);
+    }
+
+    #[test]
+    fn test_tracker() {
+        let mut tracker = RxWindowTracker::new(1_000_000, 20_000);
+        assert_eq!(tracker.next_open_us(0), None);
+        tracker.on_beacon(5_000_000);
+        assert_eq!(tracker.next_open_us(5_000_000), Some(5_990_000));
+        // Beacon clock 100 ppm slow, one beacon missed
+        tracker.on_beacon(7_000_200);
+        assert_eq!(tracker.drift_ppm(), 25);
+        assert_eq!(tracker.period_us(), 1_000_025);
+        assert_eq!(tracker.next_open_us(7_500_000), Some(7_990_225));
+    }
+}
@@ -0,0 +1,69 @@
+//! RSSI spectrum scanning.
+//!
+//! [`Cc1101::scan_channels`] steps CHANNR across consecutive channels of the programmed plan,
+//! listening on each for a while and keeping the strongest RSSI seen. Enough to pick a quiet
+//! channel with [`quietest_channel`], or for a crude spectrum analyzer on a microcontroller.
+//!
+//! ```ignore
+//! let mut rssi = [0i16; 16];
+//! cc1101.scan_channels(0, &mut rssi, 10_000, &mut delay)?;
+//! let channel = quietest_channel(&rssi);
+//! ```
+
+use crate::lowlevel::registers::*;
+use crate::{Cc1101, Error, RadioMode};
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+/// Index of the lowest reading in `rssi_dbm`, the first one on ties. `None` if empty.
+pub fn quietest_channel(rssi_dbm: &[i16]) -> Option<usize> {
+    (0..rssi_dbm.len()).min_by_key(|&n| rssi_dbm[n])
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Record the peak RSSI (in dBm) of channels `first_channel` onwards into `rssi_dbm`, one
+    /// per entry.
+    ///
+    /// Each channel is listened to for `dwell_us` (at least the RSSI settle time), sampling
+    /// the RSSI as often as it settles. Channels past 255 are skipped, their entries left as
+    /// they were. The channel is restored and the radio left in IDLE.
+    pub fn scan_channels<D: DelayNs>(
+        &mut self,
+        first_channel: u8,
+        rssi_dbm: &mut [i16],
+        dwell_us: u32,
+        delay: &mut D,
+    ) -> Result<(), Error<SpiE>> {
+        let channr = self.0.read_register(Config::CHANNR)?;
+        let settle_us = self.rssi_settle_time_us()?.max(1);
+        let channels = (first_channel..=u8::MAX).zip(rssi_dbm.iter_mut());
+        for (channel, rssi) in channels {
+            self.set_radio_mode(RadioMode::Idle)?;
+            self.set_channel(channel)?;
+            self.set_radio_mode(RadioMode::Receive)?;
+            *rssi = self.get_rssi_dbm_settled(delay)?;
+            let mut waited_us = settle_us;
+            while waited_us < dwell_us {
+                delay.delay_us(settle_us);
+                waited_us += settle_us;
+                *rssi = (*rssi).max(self.get_rssi_dbm()?);
+            }
+        }
+        self.set_radio_mode(RadioMode::Idle)?;
+        self.set_channel(channr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scan::*;
+
+    #[test]
+    fn test_quietest_channel() {
+        assert_eq!(quietest_channel(&[-80, -102, -95, -102]), Some(1));
+        assert_eq!(quietest_channel(&[]), None);
+    }
+}
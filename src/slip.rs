@@ -0,0 +1,179 @@
+//! SLIP framing (RFC 1055), to carry IP packets over a byte stream.
+//!
+//! With the serial bridge (see `crate::bridge`) on both ends, a host on each side can run an IP
+//! tunnel over the radio link: Linux `slattach` on the serial port, or a tun device pumped with
+//! [`SlipPort`] (with the `std` feature). Lost frames only lose the packets they carried, SLIP
+//! resynchronizes on the next `END`.
+
+const END: u8 = 0xC0;
+const ESC: u8 = 0xDB;
+const ESC_END: u8 = 0xDC;
+const ESC_ESC: u8 = 0xDD;
+
+/// Largest encoding of a `len` bytes packet.
+pub const fn max_encoded_len(len: usize) -> usize {
+    2 * len + 2
+}
+
+/// Encode `packet` into `out`, returns the encoded length, `None` if `out` is too small.
+///
+/// The frame starts with an `END` too, flushing any line noise received by the peer.
+pub fn encode(packet: &[u8], out: &mut [u8]) -> Option<usize> {
+    let mut n = 0;
+    let mut put = |bytes: &[u8]| -> Option<()> {
+        out.get_mut(n..n + bytes.len())?.copy_from_slice(bytes);
+        n += bytes.len();
+        Some(())
+    };
+    put(&[END])?;
+    for &b in packet {
+        match b {
+            END => put(&[ESC, ESC_END])?,
+            ESC => put(&[ESC, ESC_ESC])?,
+            _ => put(&[b])?,
+        }
+    }
+    put(&[END])?;
+    Some(n)
+}
+
+/// Decoder of a SLIP byte stream, for packets of up to `N` bytes.
+#[derive(Clone, Debug)]
+pub struct SlipDecoder<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    escaped: bool,
+    invalid: bool,
+}
+
+impl<const N: usize> SlipDecoder<N> {
+    pub fn new() -> Self {
+        SlipDecoder {
+            buf: [0; N],
+            len: 0,
+            escaped: false,
+            invalid: false,
+        }
+    }
+
+    /// Feed a received byte, returns the packet it completes, if any.
+    ///
+    /// Packets longer than `N` bytes, or with invalid escapes, are dropped.
+    pub fn push(&mut self, byte: u8) -> Option<&[u8]> {
+        if byte == END {
+            let len = core::mem::take(&mut self.len);
+            let valid = !self.invalid && !self.escaped && len > 0;
+            self.invalid = false;
+            self.escaped = false;
+            return valid.then(|| &self.buf[..len]);
+        }
+        let byte = match (core::mem::take(&mut self.escaped), byte) {
+            (false, ESC) => {
+                self.escaped = true;
+                return None;
+            }
+            (false, b) => b,
+            (true, ESC_END) => END,
+            (true, ESC_ESC) => ESC,
+            (true, _) => {
+                self.invalid = true;
+                return None;
+            }
+        };
+        match self.buf.get_mut(self.len) {
+            Some(b) => {
+                *b = byte;
+                self.len += 1;
+            }
+            None => self.invalid = true,
+        }
+        None
+    }
+}
+
+impl<const N: usize> Default for SlipDecoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Host side SLIP port, e.g. over the serial port of the device running the bridge.
+///
+/// Pump packets between it and a tun device to get an IP interface.
+#[cfg(feature = "std")]
+pub struct SlipPort<T> {
+    io: T,
+    decoder: SlipDecoder<MAX_PACKET>,
+    pending: std::collections::VecDeque<u8>,
+}
+
+/// Largest IP packet handled by `SlipPort`, the traditional SLIP MTU.
+#[cfg(feature = "std")]
+pub const MAX_PACKET: usize = 1006;
+
+#[cfg(feature = "std")]
+impl<T: std::io::Read + std::io::Write> SlipPort<T> {
+    pub fn new(io: T) -> Self {
+        SlipPort {
+            io,
+            decoder: SlipDecoder::new(),
+            pending: std::collections::VecDeque::new(),
+        }
+    }
+
+    pub fn release(self) -> T {
+        self.io
+    }
+
+    /// Send a packet.
+    pub fn send(&mut self, packet: &[u8]) -> std::io::Result<()> {
+        let mut frame = std::vec![0u8; max_encoded_len(packet.len())];
+        let n = encode(packet, &mut frame).ok_or(std::io::ErrorKind::InvalidInput)?;
+        self.io.write_all(&frame[..n])?;
+        self.io.flush()
+    }
+
+    /// Receive a packet, blocking until one is complete (or as the underlying reads do).
+    pub fn recv(&mut self) -> std::io::Result<std::vec::Vec<u8>> {
+        loop {
+            let mut chunk = [0u8; 256];
+            while let Some(byte) = self.pending.pop_front() {
+                if let Some(packet) = self.decoder.push(byte) {
+                    return Ok(packet.to_vec());
+                }
+            }
+            let n = self.io.read(&mut chunk)?;
+            if n == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            self.pending.extend(&chunk[..n]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::slip::*;
+
+    #[test]
+    fn test_slip() {
+        let packet = [0x45, END, 0x00, ESC, 0x01];
+        let mut frame = [0u8; max_encoded_len(5)];
+        let n = encode(&packet, &mut frame).unwrap();
+        assert_eq!(&frame[..n], &[END, 0x45, ESC, ESC_END, 0x00, ESC, ESC_ESC, 0x01, END]);
+        assert!(encode(&packet, &mut [0u8; 8]).is_none());
+
+        let mut decoder = SlipDecoder::<8>::new();
+        let mut packets = 0;
+        for &b in frame[..n].iter().chain(&[0x01, ESC, 0x02, END]) {
+            if let Some(decoded) = decoder.push(b) {
+                assert_eq!(decoded, packet);
+                packets += 1;
+            }
+        }
+        assert_eq!(packets, 1);
+
+        let mut small = SlipDecoder::<4>::new();
+        assert!(frame[..n].iter().all(|&b| small.push(b).is_none()));
+    }
+}
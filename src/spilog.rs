@@ -0,0 +1,232 @@
+//! SPI transaction recording and replay.
+//!
+//! [`RecordingSpi`] wraps the platform's SPI device and logs every transaction, what was sent
+//! and what the radio answered, to a sink (a UART, a file, a buffer). Attached to a bug report,
+//! the log can be fed back through [`ReplaySpi`], which stands in for the radio: the driver
+//! runs the same code path without hardware, and any deviation from the recorded accesses is
+//! reported with its position in the log.
+//!
+//! ```ignore
+//! let spi = RecordingSpi::new(spi, |bytes: &[u8]| uart.write_all(bytes).unwrap());
+//! // ... later, in a test
+//! let mut cc1101 = Cc1101::new(ReplaySpi::new(&log))?;
+//! ```
+//!
+//! Each transaction is logged as:
+//!
+//! - the number of operations (u8)
+//! - for each operation: its kind (u8, `OP_*`), the number of bytes sent and received (u16
+//!   little endian each) and the bytes sent, a delay is "sent" as its duration in nanoseconds
+//!   (u32 little endian)
+//! - the outcome (u8): `RESULT_OK` followed by the bytes received by all the operations, in
+//!   order, or `RESULT_FAILED`
+
+use hal::spi::{Error, ErrorKind, ErrorType, Operation, SpiDevice};
+
+pub const OP_READ: u8 = 0;
+pub const OP_WRITE: u8 = 1;
+pub const OP_TRANSFER: u8 = 2;
+pub const OP_TRANSFER_IN_PLACE: u8 = 3;
+pub const OP_DELAY: u8 = 4;
+
+pub const RESULT_OK: u8 = 0;
+pub const RESULT_FAILED: u8 = 1;
+
+/// Kind, bytes sent and number of bytes received of an operation.
+fn describe<'a>(op: &'a Operation<'_, u8>, delay: &'a [u8; 4]) -> (u8, &'a [u8], usize) {
+    match op {
+        Operation::Read(buf) => (OP_READ, &[], buf.len()),
+        Operation::Write(buf) => (OP_WRITE, buf, 0),
+        Operation::Transfer(read, write) => (OP_TRANSFER, write, read.len()),
+        Operation::TransferInPlace(buf) => (OP_TRANSFER_IN_PLACE, buf, buf.len()),
+        Operation::DelayNs(_) => (OP_DELAY, delay, 0),
+    }
+}
+
+/// Bytes received by an operation.
+fn received<'a>(op: &'a mut Operation<'_, u8>) -> &'a mut [u8] {
+    match op {
+        Operation::Read(buf) | Operation::Transfer(buf, _) | Operation::TransferInPlace(buf) => buf,
+        Operation::Write(_) | Operation::DelayNs(_) => &mut [],
+    }
+}
+
+fn delay_bytes(op: &Operation<'_, u8>) -> [u8; 4] {
+    match op {
+        Operation::DelayNs(ns) => ns.to_le_bytes(),
+        _ => [0; 4],
+    }
+}
+
+/// SPI device logging its transactions to `sink`.
+pub struct RecordingSpi<SPI, F> {
+    spi: SPI,
+    sink: F,
+}
+
+impl<SPI, F: FnMut(&[u8])> RecordingSpi<SPI, F> {
+    pub fn new(spi: SPI, sink: F) -> Self {
+        RecordingSpi {
+            spi,
+            sink,
+        }
+    }
+
+    pub fn release(self) -> (SPI, F) {
+        (self.spi, self.sink)
+    }
+}
+
+impl<SPI: ErrorType, F> ErrorType for RecordingSpi<SPI, F> {
+    type Error = SPI::Error;
+}
+
+impl<SPI: SpiDevice<u8>, F: FnMut(&[u8])> SpiDevice<u8> for RecordingSpi<SPI, F> {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        // Sent bytes are logged up front, in place transfers overwrite them
+        (self.sink)(&[operations.len() as u8]);
+        for op in operations.iter() {
+            let delay = delay_bytes(op);
+            let (kind, sent, received) = describe(op, &delay);
+            let [s0, s1] = (sent.len() as u16).to_le_bytes();
+            let [r0, r1] = (received as u16).to_le_bytes();
+            (self.sink)(&[kind, s0, s1, r0, r1]);
+            (self.sink)(sent);
+        }
+        let result = self.spi.transaction(operations);
+        match result {
+            Ok(()) => {
+                (self.sink)(&[RESULT_OK]);
+                for op in operations.iter_mut() {
+                    (self.sink)(received(op));
+                }
+            }
+            Err(_) => (self.sink)(&[RESULT_FAILED]),
+        }
+        result
+    }
+}
+
+/// Replay errors.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ReplayError {
+    /// The transaction doesn't match the one at this offset of the log.
+    Mismatch(usize),
+    /// The log ended.
+    End,
+    /// The recorded transaction failed.
+    Failed,
+}
+
+impl Error for ReplayError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// SPI device answering with the responses of a log made by `RecordingSpi`.
+pub struct ReplaySpi<'a> {
+    log: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ReplaySpi<'a> {
+    pub fn new(log: &'a [u8]) -> Self {
+        ReplaySpi {
+            log,
+            pos: 0,
+        }
+    }
+
+    /// Offset of the next transaction in the log.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Whether all the transactions were replayed.
+    pub fn is_done(&self) -> bool {
+        self.pos == self.log.len()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ReplayError> {
+        let bytes = self.log.get(self.pos..self.pos + n).ok_or(ReplayError::End)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+}
+
+impl ErrorType for ReplaySpi<'_> {
+    type Error = ReplayError;
+}
+
+impl SpiDevice<u8> for ReplaySpi<'_> {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        let start = self.pos;
+        if self.take(1)?[0] as usize != operations.len() {
+            return Err(ReplayError::Mismatch(start));
+        }
+        for op in operations.iter() {
+            let at = self.pos;
+            let header = self.take(5)?;
+            let sent = self.take(u16::from_le_bytes([header[1], header[2]]) as usize)?;
+            let received = u16::from_le_bytes([header[3], header[4]]) as usize;
+            let delay = delay_bytes(op);
+            if describe(op, &delay) != (header[0], sent, received) {
+                return Err(ReplayError::Mismatch(at));
+            }
+        }
+        if self.take(1)?[0] != RESULT_OK {
+            return Err(ReplayError::Failed);
+        }
+        for op in operations.iter_mut() {
+            let buf = received(op);
+            buf.copy_from_slice(self.take(buf.len())?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "spilog"))]
+mod tests {
+    use crate::lowlevel::registers::*;
+    use crate::spilog::*;
+    use core::convert::Infallible;
+
+    /// Answers each byte sent with the next value of a counter.
+    struct Counter(u8);
+
+    impl ErrorType for Counter {
+        type Error = Infallible;
+    }
+
+    impl SpiDevice<u8> for Counter {
+        fn transaction(&mut self, ops: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+            for op in ops {
+                for b in received(op) {
+                    self.0 += 1;
+                    *b = self.0;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_replay() {
+        let mut log = heapless::Vec::<u8, 256>::new();
+        let spi =
+            RecordingSpi::new(Counter(0), |bytes: &[u8]| log.extend_from_slice(bytes).unwrap());
+        let mut cc1101 = crate::lowlevel::Cc1101::new(spi).unwrap();
+        let partnum = cc1101.read_register(Status::PARTNUM).unwrap();
+        let mut fscal = [0u8; 3];
+        cc1101.read_config_burst(Config::FSCAL3, &mut fscal).unwrap();
+        cc1101.write_register(Config::CHANNR, 7).unwrap();
+
+        let mut replay = crate::lowlevel::Cc1101::new(ReplaySpi::new(&log)).unwrap();
+        assert_eq!(replay.read_register(Status::PARTNUM), Ok(partnum));
+        let mut replayed = [0u8; 3];
+        assert_eq!(replay.read_config_burst(Config::FSCAL3, &mut replayed), Ok(()));
+        assert_eq!(replayed, fscal);
+        assert!(matches!(replay.write_register(Config::CHANNR, 8), Err(ReplayError::Mismatch(_))));
+    }
+}
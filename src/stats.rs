@@ -0,0 +1,133 @@
+//! Link statistics, with a compact binary form to embed in a node's own telemetry.
+//!
+//! The driver counts packets and recoveries as they go through the receive, transmit and
+//! watchdog APIs, see [`Cc1101::stats`]. [`Stats::to_bytes`] gives a fixed layout, little
+//! endian snapshot of [`STATS_LEN`] bytes:
+//!
+//! | offset | 0       | 1      | 5      | 9          | 11           | 13            | 15    | 17   | 18  |
+//! |--------|---------|--------|--------|------------|--------------|---------------|-------|------|-----|
+//! | field  | version | rx (4) | tx (4) | CRC errors | RX overflows | TX underflows | stuck | RSSI | LQI |
+//!
+//! The 16-bit counters saturate, the RSSI is in dBm (signed).
+
+use crate::watchdog::Health;
+use crate::Cc1101;
+use hal::spi::SpiDevice;
+
+/// Layout version, first byte of the snapshot.
+pub const STATS_VERSION: u8 = 1;
+/// Length of the snapshot.
+pub const STATS_LEN: usize = 19;
+
+/// Link statistics.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    /// Packets read from the RX FIFO, CRC errors included.
+    pub rx_packets: u32,
+    /// Packets transmitted.
+    pub tx_packets: u32,
+    /// Packets read with a CRC mismatch.
+    pub crc_errors: u16,
+    /// RX FIFO overflows recovered by the watchdog.
+    pub rx_overflows: u16,
+    /// TX FIFO underflows recovered by the watchdog.
+    pub tx_underflows: u16,
+    /// Stuck states recovered by the watchdog.
+    pub stuck: u16,
+    /// RSSI of the last packet (in dBm).
+    pub last_rssi_dbm: i8,
+    /// LQI of the last packet.
+    pub last_lqi: u8,
+}
+
+impl Stats {
+    /// Packets sent and received, a measure of uptime for nodes that communicate regularly.
+    pub fn packets(&self) -> u32 {
+        self.rx_packets.wrapping_add(self.tx_packets)
+    }
+
+    pub fn to_bytes(&self) -> [u8; STATS_LEN] {
+        let mut buf = [0u8; STATS_LEN];
+        buf[0] = STATS_VERSION;
+        buf[1..5].copy_from_slice(&self.rx_packets.to_le_bytes());
+        buf[5..9].copy_from_slice(&self.tx_packets.to_le_bytes());
+        buf[9..11].copy_from_slice(&self.crc_errors.to_le_bytes());
+        buf[11..13].copy_from_slice(&self.rx_overflows.to_le_bytes());
+        buf[13..15].copy_from_slice(&self.tx_underflows.to_le_bytes());
+        buf[15..17].copy_from_slice(&self.stuck.to_le_bytes());
+        buf[17] = self.last_rssi_dbm as u8;
+        buf[18] = self.last_lqi;
+        buf
+    }
+
+    /// Decode a snapshot made by `to_bytes`, host side. `None` for another layout version.
+    #[cfg(feature = "std")]
+    pub fn from_bytes(buf: &[u8]) -> Option<Self> {
+        let buf: &[u8; STATS_LEN] = buf.get(..STATS_LEN)?.try_into().ok()?;
+        if buf[0] != STATS_VERSION {
+            return None;
+        }
+        let u16_at = |n: usize| u16::from_le_bytes([buf[n], buf[n + 1]]);
+        let u32_at = |n: usize| u32::from_le_bytes([buf[n], buf[n + 1], buf[n + 2], buf[n + 3]]);
+        Some(Stats {
+            rx_packets: u32_at(1),
+            tx_packets: u32_at(5),
+            crc_errors: u16_at(9),
+            rx_overflows: u16_at(11),
+            tx_underflows: u16_at(13),
+            stuck: u16_at(15),
+            last_rssi_dbm: buf[17] as i8,
+            last_lqi: buf[18],
+        })
+    }
+
+    pub(crate) fn record_packet(&mut self, rssi_dbm: i16, lqi: u8, crc_ok: bool) {
+        self.rx_packets = self.rx_packets.wrapping_add(1);
+        self.crc_errors = self.crc_errors.saturating_add(!crc_ok as u16);
+        self.last_rssi_dbm = rssi_dbm.clamp(i8::MIN as i16, i8::MAX as i16) as i8;
+        self.last_lqi = lqi;
+    }
+
+    pub(crate) fn record_recovery(&mut self, health: Health) {
+        let counter = match health {
+            Health::Healthy(_) => return,
+            Health::RecoveredRxOverflow => &mut self.rx_overflows,
+            Health::RecoveredTxUnderflow => &mut self.tx_underflows,
+            Health::RecoveredStuck(_) => &mut self.stuck,
+        };
+        *counter = counter.saturating_add(1);
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    pub fn stats(&self) -> Stats {
+        self.0.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.0.stats = Stats::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::*;
+
+    #[test]
+    fn test_stats_bytes() {
+        let mut stats = Stats::default();
+        stats.record_packet(-95, 40, true);
+        stats.record_packet(-200, 12, false);
+        stats.record_recovery(Health::RecoveredRxOverflow);
+        stats.tx_packets = 3;
+        let bytes = stats.to_bytes();
+        assert_eq!(bytes[..5], [STATS_VERSION, 2, 0, 0, 0]);
+        assert_eq!(bytes[17..], [(-128i8) as u8, 12]);
+        assert_eq!(stats.packets(), 5);
+        #[cfg(feature = "std")]
+        assert_eq!(Stats::from_bytes(&bytes), Some(stats));
+    }
+}
@@ -0,0 +1,153 @@
+//! Sync word discovery, for reverse engineering an unknown transmitter.
+//!
+//! Frames are captured raw (sync check disabled, reception triggered by carrier sense, see
+//! `crate::capture`), then the preamble is located in each one, and the 16 bits following it
+//! are counted as a sync word candidate. Across captures, the transmitter's sync word comes out
+//! on top, noise is spread over many candidates.
+//!
+//! The sync word is assumed to follow the alternating preamble, starting with the first bit
+//! that breaks the alternation, or the one before it when the preamble would otherwise end
+//! with a one (`0xAA` preambles end with a zero). A sync word whose leading bits continue
+//! the pattern shows up shifted, by an even number of bits.
+
+use crate::{Cc1101, Error};
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+/// Shortest alternating run taken for a preamble (in bits).
+pub const MIN_PREAMBLE_BITS: u16 = 16;
+/// Number of candidates tracked.
+pub const CANDIDATES: usize = 8;
+/// Bytes kept from the start of each captured frame.
+const FRAME_BYTES: usize = 32;
+
+/// A sync word candidate.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SyncCandidate {
+    pub word: u16,
+    /// Number of frames it was found in.
+    pub count: u16,
+    /// Longest preamble seen before it (in bits), captures can miss the start of a preamble.
+    pub preamble_bits: u16,
+}
+
+/// Sync word statistics over captured frames.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SyncScanner {
+    candidates: [SyncCandidate; CANDIDATES],
+    frames: u16,
+}
+
+impl SyncScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of frames fed.
+    pub fn frames(&self) -> u16 {
+        self.frames
+    }
+
+    /// Look for a preamble and a sync word in a raw captured frame.
+    pub fn feed(&mut self, frame: &[u8]) {
+        self.frames = self.frames.saturating_add(1);
+        let Some((word, preamble_bits)) = find_sync(frame) else {
+            return;
+        };
+        // Frequent items (space saving): the least counted candidate makes room
+        let slot = match self.candidates.iter().position(|c| c.count > 0 && c.word == word) {
+            Some(n) => n,
+            None => {
+                let n = (0..CANDIDATES).min_by_key(|&n| self.candidates[n].count).unwrap();
+                self.candidates[n].word = word;
+                self.candidates[n].preamble_bits = 0;
+                n
+            }
+        };
+        let candidate = &mut self.candidates[slot];
+        candidate.count = candidate.count.saturating_add(1);
+        candidate.preamble_bits = candidate.preamble_bits.max(preamble_bits);
+    }
+
+    /// Candidates, most frequent first.
+    pub fn suggestions(&self) -> [SyncCandidate; CANDIDATES] {
+        let mut candidates = self.candidates;
+        candidates.sort_unstable_by_key(|c| core::cmp::Reverse(c.count));
+        candidates
+    }
+}
+
+/// Sync word following the first preamble in `frame`, and the preamble length (in bits).
+fn find_sync(frame: &[u8]) -> Option<(u16, u16)> {
+    let bits = frame.len() * 8;
+    let bit = |n: usize| (frame[n / 8] >> (7 - n % 8)) & 1;
+    let mut start = 0;
+    for n in 1..bits {
+        if bit(n) != bit(n - 1) {
+            continue;
+        }
+        // Alternating run start..n, the sync word starts after its last zero
+        let end = n - bit(n - 1) as usize;
+        if (end - start) as u16 >= MIN_PREAMBLE_BITS && end + 16 <= bits {
+            let word = (end..end + 16).fold(0u16, |w, n| (w << 1) | bit(n) as u16);
+            return Some((word, (end - start) as u16));
+        }
+        start = n;
+    }
+    None
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Capture `frames` frames from carrier sense triggered reception into `scanner`.
+    ///
+    /// Frames are told apart by gaps in the received data, see `capture` for `poll_us`. The
+    /// carrier sense threshold should be set just above the noise floor.
+    pub fn scan_sync_words<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        poll_us: u32,
+        frames: u16,
+        scanner: &mut SyncScanner,
+    ) -> Result<(), Error<SpiE>> {
+        let mut frame = [0u8; FRAME_BYTES];
+        let mut len = 0;
+        let mut last_us = 0;
+        let mut captured = 0;
+        self.capture(delay, poll_us, true, |now_us, data| {
+            if len > 0 && now_us.wrapping_sub(last_us) > 2 * poll_us {
+                scanner.feed(&frame[..len]);
+                captured += 1;
+                len = 0;
+            }
+            last_us = now_us;
+            let n = data.len().min(FRAME_BYTES - len);
+            frame[len..len + n].copy_from_slice(&data[..n]);
+            len += n;
+            captured < frames
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::syncscan::*;
+
+    #[test]
+    fn test_sync_scanner() {
+        let mut scanner = SyncScanner::new();
+        // Preamble captured from different phases, then 0xD391 and payload
+        scanner.feed(&[0xAA, 0xAA, 0xAA, 0xD3, 0x91, 0x12]);
+        scanner.feed(&[0xAA, 0xAA, 0xAD, 0x39, 0x11, 0x20]);
+        scanner.feed(&[0x0A, 0xAA, 0xAA, 0xD3, 0x91]);
+        scanner.feed(&[0x00, 0xff, 0x12, 0x34]);
+        let best = scanner.suggestions()[0];
+        assert_eq!(best.word, 0xD391);
+        assert_eq!(best.count, 3);
+        assert_eq!(best.preamble_bits, 24);
+        assert_eq!(scanner.frames(), 4);
+    }
+}
@@ -0,0 +1,106 @@
+//! Sync word selection and validation.
+//!
+//! A poor sync word (long runs of equal bits, few transitions, or close to the alternating
+//! preamble pattern) makes the demodulator lock on noise or on the preamble, which shows up as
+//! a loss of sensitivity rather than as an obvious failure.
+
+/// Longest acceptable run of equal bits.
+const MAX_RUN: u32 = 4;
+/// Fewest acceptable bit transitions.
+const MIN_TRANSITIONS: u32 = 5;
+/// Acceptable number of ones, for DC balance.
+const ONES: core::ops::RangeInclusive<u32> = 5..=11;
+/// Minimum Hamming distance to the preamble patterns.
+const MIN_PREAMBLE_DISTANCE: u32 = 5;
+
+/// Why a sync word is poor.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SyncWordIssue {
+    /// Run of the given number of equal bits.
+    LongRun(u32),
+    /// Only the given number of bit transitions.
+    TooFewTransitions(u32),
+    /// Given number of ones, too far from half of the bits.
+    Unbalanced(u32),
+    /// Only the given number of bits away from the alternating preamble pattern.
+    LooksLikePreamble(u32),
+}
+
+fn longest_run(word: u16) -> u32 {
+    let mut longest = 0;
+    let mut bits = word;
+    for _ in 0..2 {
+        // Runs of ones, then runs of zeros
+        let mut x = bits;
+        let mut n = 0;
+        while x != 0 {
+            x &= x << 1;
+            n += 1;
+        }
+        longest = longest.max(n);
+        bits = !word;
+    }
+    longest
+}
+
+/// Check `word` for the patterns that make poor sync words.
+pub fn check_sync_word(word: u16) -> Result<(), SyncWordIssue> {
+    let run = longest_run(word);
+    if run > MAX_RUN {
+        return Err(SyncWordIssue::LongRun(run));
+    }
+    let transitions = ((word ^ (word >> 1)) & 0x7fff).count_ones();
+    if transitions < MIN_TRANSITIONS {
+        return Err(SyncWordIssue::TooFewTransitions(transitions));
+    }
+    let ones = word.count_ones();
+    if !ONES.contains(&ones) {
+        return Err(SyncWordIssue::Unbalanced(ones));
+    }
+    let distance = (word ^ 0xAAAA).count_ones().min((word ^ 0x5555).count_ones());
+    if distance < MIN_PREAMBLE_DISTANCE {
+        return Err(SyncWordIssue::LooksLikePreamble(distance));
+    }
+    Ok(())
+}
+
+/// Map a network ID to a sync word passing `check_sync_word`.
+///
+/// Distinct IDs usually map to distinct words, but not always, the ID should also be checked
+/// in the payload when it matters. The mapping is stable across versions of this crate.
+pub fn sync_word_for_network(id: u16) -> u16 {
+    let mut x = id;
+    loop {
+        // 16-bit xorshift-multiply mixer
+        x ^= x >> 7;
+        x = x.wrapping_mul(0x2f6b);
+        x ^= x >> 9;
+        if check_sync_word(x).is_ok() {
+            return x;
+        }
+        x = x.wrapping_add(0x9e37);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::syncword::*;
+
+    #[test]
+    fn test_check_sync_word() {
+        assert_eq!(check_sync_word(0xD391), Ok(()));
+        assert_eq!(check_sync_word(0xF0F0), Err(SyncWordIssue::TooFewTransitions(3)));
+        assert_eq!(check_sync_word(0xF855), Err(SyncWordIssue::LongRun(5)));
+        assert_eq!(check_sync_word(0xAAAB), Err(SyncWordIssue::LooksLikePreamble(1)));
+        assert_eq!(check_sync_word(0xEEEE), Err(SyncWordIssue::Unbalanced(12)));
+    }
+
+    #[test]
+    fn test_sync_word_for_network() {
+        let words: [u16; 16] = core::array::from_fn(|id| sync_word_for_network(id as u16));
+        for (n, &word) in words.iter().enumerate() {
+            assert_eq!(check_sync_word(word), Ok(()));
+            assert!(!words[..n].contains(&word));
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Temperature compensation of the crystal frequency drift.
+//!
+//! The carrier is derived from the crystal, so the crystal drift over temperature (in ppm) moves
+//! the carrier by the same relative amount. Given the temperature and the drift curve of the
+//! crystal, [`Cc1101::trim_frequency`] sets the synthesizer frequency offset (FSCTRL0) to pull the
+//! carrier back. Call it periodically, e.g. whenever a new temperature reading is available.
+//!
+//! FSCTRL0 is also the register the frequency offset compensation loop results are usually
+//! written back to (see [`Cc1101::apply_afc`]), don't use both.
+
+use crate::lowlevel::registers::*;
+use crate::{Cc1101, Error};
+use hal::spi::SpiDevice;
+
+/// Crystal frequency error (in ppm) as a function of temperature (in °C).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DriftCurve<'a> {
+    /// `ppm_per_c` ppm per °C away from `reference_c`, where the error is zero.
+    Linear {
+        reference_c: f32,
+        ppm_per_c: f32,
+    },
+    /// (°C, ppm) points sorted by temperature, linearly interpolated and held constant outside
+    /// of the covered range.
+    Table(&'a [(f32, f32)]),
+}
+
+impl DriftCurve<'_> {
+    /// Crystal frequency error (in ppm) at `temp_c`.
+    pub fn ppm_at(&self, temp_c: f32) -> f32 {
+        match *self {
+            DriftCurve::Linear {
+                reference_c,
+                ppm_per_c,
+            } => (temp_c - reference_c) * ppm_per_c,
+            DriftCurve::Table(points) => {
+                let Some(&(first_c, first_ppm)) = points.first() else {
+                    return 0.0;
+                };
+                if temp_c <= first_c {
+                    return first_ppm;
+                }
+                for w in points.windows(2) {
+                    let ((t0, p0), (t1, p1)) = (w[0], w[1]);
+                    if temp_c <= t1 {
+                        return p0 + (p1 - p0) * (temp_c - t0) / (t1 - t0);
+                    }
+                }
+                points[points.len() - 1].1
+            }
+        }
+    }
+}
+
+/// FREQOFF value compensating a crystal error of `ppm`, for the frequency control word `freq`.
+///
+/// The offset step is FXOSC/2^14 and the carrier FXOSC·`freq`/2^16, so the offset is
+/// -`freq`·`ppm`/(4·10^6) steps, independent of the crystal frequency. Saturates at the limits
+/// of the register.
+pub fn freqoff_for(freq: u32, ppm: f32) -> i8 {
+    let steps = -(freq as f32) * ppm / 4e6;
+    // Round half away from zero, `f32::round` isn't available in core
+    let steps = if steps < 0.0 {
+        steps - 0.5
+    } else {
+        steps + 0.5
+    };
+    steps.clamp(i8::MIN as f32, i8::MAX as f32) as i8
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Adjust the frequency offset (FSCTRL0) for the crystal drift at `temp_c`.
+    ///
+    /// Returns the applied offset, in steps of FXOSC/2^14 (~1.6 kHz). The register is only
+    /// written when the offset changes. Takes effect on the next calibration.
+    pub fn trim_frequency(&mut self, temp_c: f32, curve: &DriftCurve) -> Result<i8, Error<SpiE>> {
+        let mut offset = 0;
+        self.0.modify_config_burst(
+            Config::FSCTRL0,
+            |[freqoff, freq2, freq1, freq0]: &mut [u8; 4]| {
+                let freq = u32::from_be_bytes([0, *freq2, *freq1, *freq0]);
+                offset = freqoff_for(freq, curve.ppm_at(temp_c));
+                *freqoff = offset as u8;
+            },
+        )?;
+        Ok(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lowlevel::convert::from_frequency;
+    use crate::tempcomp::*;
+
+    #[test]
+    fn test_ppm_at() {
+        let linear = DriftCurve::Linear {
+            reference_c: 25.0,
+            ppm_per_c: -0.5,
+        };
+        assert_eq!(linear.ppm_at(45.0), -10.0);
+
+        let table = DriftCurve::Table(&[(-40.0, -20.0), (25.0, 0.0), (85.0, -12.0)]);
+        assert_eq!(table.ppm_at(-50.0), -20.0);
+        assert_eq!(table.ppm_at(55.0), -6.0);
+        assert_eq!(table.ppm_at(100.0), -12.0);
+        assert_eq!(DriftCurve::Table(&[]).ppm_at(0.0), 0.0);
+    }
+
+    #[test]
+    fn test_freqoff_for() {
+        let (freq0, freq1, freq2) = from_frequency(868_000_000);
+        let freq = u32::from_be_bytes([0, freq2, freq1, freq0]);
+        // 868 MHz * 10 ppm = 8680 Hz, / (27 MHz / 2^14) = 5.27 steps
+        assert_eq!(freqoff_for(freq, 10.0), -5);
+        assert_eq!(freqoff_for(freq, -10.0), 5);
+        assert_eq!(freqoff_for(freq, 1000.0), i8::MIN);
+    }
+}
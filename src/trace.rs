@@ -0,0 +1,131 @@
+//! Timeline of the last driver events, to find out how a node in the field got stuck.
+//!
+//! With the `trace` feature, strobes, mode changes, MARCSTATE changes seen while polling,
+//! recoveries and errors are recorded in a ring buffer of the last [`TRACE_LEN`] events,
+//! timestamped with a user supplied clock. Without it, nothing is recorded, at no cost.
+
+use crate::lowlevel::registers::Command;
+use crate::watchdog::Health;
+use crate::RadioMode;
+
+/// Number of events kept.
+pub const TRACE_LEN: usize = 32;
+
+/// A driver event.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TraceEvent {
+    /// Command strobe sent.
+    Strobe(Command),
+    /// Mode change requested.
+    Mode(RadioMode),
+    /// MARCSTATE read, only recorded when it differs from the previous reading.
+    State(u8),
+    /// Watchdog recovery.
+    Recovery(Health),
+    /// Packet didn't fit in the TX FIFO.
+    TxOverflow,
+    /// Operation refused in the given MARCSTATE.
+    InvalidState(u8),
+    /// Received packet length didn't match the configuration or the buffer.
+    PacketLengthMismatch,
+    /// RX FIFO overflowed, it was flushed and the radio put back in RX.
+    RxOverflow,
+    /// TX FIFO underflowed, it was flushed and the radio put in IDLE.
+    TxUnderflow,
+}
+
+/// A timestamped event.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TraceEntry {
+    /// Time of the event, from the clock set with `set_trace_clock`, 0 without one.
+    pub timestamp: u32,
+    pub event: TraceEvent,
+}
+
+/// Ring buffer of the last events.
+#[cfg(feature = "trace")]
+#[derive(Clone, Debug)]
+pub struct Trace {
+    entries: [Option<TraceEntry>; TRACE_LEN],
+    next: usize,
+    clock: Option<fn() -> u32>,
+    last_state: Option<u8>,
+}
+
+#[cfg(feature = "trace")]
+impl Trace {
+    pub(crate) const fn new() -> Self {
+        Trace {
+            entries: [None; TRACE_LEN],
+            next: 0,
+            clock: None,
+            last_state: None,
+        }
+    }
+
+    pub(crate) fn record(&mut self, event: TraceEvent) {
+        if let TraceEvent::State(state) = event {
+            if self.last_state.replace(state) == Some(state) {
+                return;
+            }
+        }
+        let timestamp = self.clock.map_or(0, |clock| clock());
+        self.entries[self.next] = Some(TraceEntry {
+            timestamp,
+            event,
+        });
+        self.next = (self.next + 1) % TRACE_LEN;
+    }
+
+    /// The recorded events, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = TraceEntry> + '_ {
+        let (newer, older) = self.entries.split_at(self.next);
+        older.iter().chain(newer).flatten().copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries = [None; TRACE_LEN];
+        self.next = 0;
+        self.last_state = None;
+    }
+}
+
+#[cfg(feature = "trace")]
+impl<SPI, SpiE> crate::Cc1101<SPI>
+where
+    SPI: hal::spi::SpiDevice<u8, Error = SpiE>,
+{
+    /// Timestamp events with `clock`, e.g. a millisecond tick counter.
+    pub fn set_trace_clock(&mut self, clock: fn() -> u32) {
+        self.0.trace.clock = Some(clock);
+    }
+
+    /// The event timeline, to dump when something went wrong.
+    pub fn trace(&self) -> &Trace {
+        &self.0.trace
+    }
+
+    pub fn clear_trace(&mut self) {
+        self.0.trace.clear();
+    }
+}
+
+#[cfg(all(test, feature = "trace"))]
+mod tests {
+    use crate::trace::*;
+
+    #[test]
+    fn test_trace() {
+        let mut trace = Trace::new();
+        trace.record(TraceEvent::State(1));
+        trace.record(TraceEvent::State(1));
+        trace.record(TraceEvent::Strobe(Command::SRX));
+        assert_eq!(trace.iter().count(), 2);
+        for n in 0..TRACE_LEN as u8 {
+            trace.record(TraceEvent::InvalidState(n));
+        }
+        let mut events = trace.iter().map(|entry| entry.event);
+        assert_eq!(events.next(), Some(TraceEvent::InvalidState(0)));
+        assert_eq!(events.last(), Some(TraceEvent::InvalidState(TRACE_LEN as u8 - 1)));
+    }
+}
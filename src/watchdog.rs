@@ -0,0 +1,75 @@
+//! State machine watchdog, detecting and recovering from wedged radio states.
+
+use crate::lowlevel::registers::*;
+use crate::lowlevel::types::*;
+use crate::{Cc1101, Error};
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+/// Outcome of a health check.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Health {
+    /// Radio is in the given, stable, state. Nothing was done.
+    Healthy(MachineState),
+    /// RX FIFO overflowed, it was flushed and the radio is in IDLE.
+    RecoveredRxOverflow,
+    /// TX FIFO underflowed, it was flushed and the radio is in IDLE.
+    RecoveredTxUnderflow,
+    /// Radio was stuck in the given state (`None` for an invalid MARCSTATE value), it was
+    /// forced to IDLE and recalibrated.
+    RecoveredStuck(Option<MachineState>),
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Check that the radio is not wedged, and perform the documented recovery if it is.
+    ///
+    /// - RX FIFO overflow: flush the RX FIFO (SFRX)
+    /// - TX FIFO underflow: flush the TX FIFO (SFTX)
+    /// - Calibration, settling or RX/TX switching for longer than `settle_timeout_us`: force
+    ///   IDLE (SIDLE), then recalibrate (SCAL)
+    ///
+    /// After a recovery the radio is left in IDLE, it's up to the caller to resume RX/TX.
+    pub fn ensure_healthy<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        settle_timeout_us: u32,
+    ) -> Result<Health, Error<SpiE>> {
+        let mut waited_us = 0;
+        let state = loop {
+            let state = MachineState::from_value(self.get_marc_state()?);
+            match state {
+                Some(s) if s.is_transitional() && waited_us < settle_timeout_us => {
+                    delay.delay_us(self.0.poll_interval_us);
+                    waited_us = waited_us.saturating_add(self.0.poll_interval_us);
+                }
+                _ => break state,
+            }
+        };
+
+        let health = match state {
+            Some(MachineState::RXFIFO_OVERFLOW) => {
+                self.flush_rx()?;
+                self.await_machine_state(MachineState::IDLE)?;
+                Health::RecoveredRxOverflow
+            }
+            Some(MachineState::TXFIFO_UNDERFLOW) => {
+                self.flush_tx()?;
+                self.await_machine_state(MachineState::IDLE)?;
+                Health::RecoveredTxUnderflow
+            }
+            Some(s) if !s.is_transitional() => return Ok(Health::Healthy(s)),
+            stuck => {
+                self.0.write_strobe(Command::SIDLE)?;
+                self.await_machine_state(MachineState::IDLE)?;
+                self.0.write_strobe(Command::SCAL)?;
+                self.await_machine_state(MachineState::IDLE)?;
+                Health::RecoveredStuck(stuck)
+            }
+        };
+        self.notify_recovery(health);
+        Ok(health)
+    }
+}
@@ -0,0 +1,187 @@
+//! Wake-on-Radio.
+//!
+//! A WOR receiver sleeps, and only wakes up to listen for a preamble every event 0 period. A
+//! packet reaches it only if its preamble is on air when it wakes up, so the transmitter has
+//! to send a preamble at least one period long, see [`Cc1101::transmit_wor`].
+//!
+//! On the receiver, [`WorSettings`] derives the event 0 period and RX timeout from a polling
+//! interval and RX duty cycle, [`Cc1101::configure_wor`] programs them and
+//! [`Cc1101::enter_wor`] starts polling. Configure a GDO pin (e.g. `GdoCfg::SYNC_WORD`) to
+//! wake the MCU on a packet, which stays in the RX FIFO until read. With RXOFF_MODE set to
+//! IDLE, the radio stops polling after a packet: read it, then call `enter_wor` again.
+//!
+//! ```ignore
+//! cc1101.configure_wor(&WorSettings::new(1_000_000, 10_000))?;
+//! cc1101.enter_wor()?;
+//! // GDO interrupt
+//! cc1101.exit_wor()?;
+//! ```
+//!
+//! The radio is in SLEEP between events, where FSTEST to TEST0 (e.g. PTEST, changed by
+//! `enable_temperature_output`) and the PATABLE past its first entry are lost. They're back to
+//! their reset values after `exit_wor`, write them again if they were changed, e.g. before
+//! transmitting with a power ramp. A `SleepSnapshot` taken
+//! with `Cc1101::sleep` has the values to write back.
+
+use crate::lowlevel::registers::*;
+use crate::lowlevel::{FIFO_SIZE, FXOSC};
+use crate::rxwindow::RX_TIME_0_FRACTION;
+use crate::trace::TraceEvent;
+use crate::{Cc1101, Error, RadioMode};
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+/// Event 0 period and RX timeout of the WOR receiver.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct WorSettings {
+    /// WORCTRL WOR_RES, the event 0 resolution.
+    pub wor_res: u8,
+    pub event0: u16,
+    /// MCSM2 RX_TIME, the RX timeout as a fraction of the event 0 period.
+    pub rx_time: u8,
+}
+
+impl WorSettings {
+    /// Settings polling every `interval_us`, as close as the resolution allows, listening for
+    /// at least `rx_duty_ppm` millionths of the interval (or the longest timeout, ~3.6%).
+    pub fn new(interval_us: u32, rx_duty_ppm: u32) -> Self {
+        let mut wor_res = 0;
+        let event0 = loop {
+            let event0 =
+                (interval_us as u64 * FXOSC + Self::unit(wor_res) / 2) / Self::unit(wor_res);
+            if event0 <= u16::MAX as u64 || wor_res == 3 {
+                break event0.clamp(1, u16::MAX as u64) as u16;
+            }
+            wor_res += 1;
+        };
+        let rx_time = (0..=6)
+            .rev()
+            .find(|&rx_time| Self::fraction(wor_res, rx_time) >= rx_duty_ppm as u64)
+            .unwrap_or(0);
+        WorSettings {
+            wor_res,
+            event0,
+            rx_time,
+        }
+    }
+
+    /// Event 0 period (in microseconds).
+    pub fn interval_us(&self) -> u32 {
+        (self.event0 as u64 * Self::unit(self.wor_res) / FXOSC) as u32
+    }
+
+    /// RX timeout (in microseconds).
+    pub fn rx_timeout_us(&self) -> u32 {
+        (self.interval_us() as u64 * Self::fraction(self.wor_res, self.rx_time) / 1_000_000) as u32
+    }
+
+    /// Event 0 period per EVENT0 step, 750/FXOSC·2^(5·WOR_RES), times FXOSC (in microseconds).
+    fn unit(wor_res: u8) -> u64 {
+        750_000_000 << (5 * wor_res)
+    }
+
+    /// RX timeout as a fraction of the event 0 period (in millionths).
+    fn fraction(wor_res: u8, rx_time: u8) -> u64 {
+        RX_TIME_0_FRACTION >> (wor_res + rx_time)
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Transmit `packet` (written to the TX FIFO as is) to a receiver polling every
+    /// `wakeup_period_us`.
+    ///
+    /// The radio enters TX with an empty TX FIFO, in which case it sends preamble until data
+    /// is written, waits for `wakeup_period_us`, then writes the packet. The preamble setting
+    /// doesn't have to be changed, there is nothing to restore. Anything left in the TX FIFO
+    /// is dropped first. Blocks until the packet is sent, see `set_txoff_mode`.
+    pub fn transmit_wor<D: DelayNs>(
+        &mut self,
+        packet: &[u8],
+        wakeup_period_us: u32,
+        delay: &mut D,
+    ) -> Result<(), Error<SpiE>> {
+        if packet.len() > FIFO_SIZE {
+            self.0.trace_event(TraceEvent::TxOverflow);
+            return Err(Error::TxOverflow);
+        }
+        self.abort_transmit()?;
+        self.set_radio_mode(RadioMode::Transmit)?;
+        delay.delay_us(wakeup_period_us);
+        self.write_tx_fifo(packet)?;
+        self.finish_transmit()
+    }
+
+    /// Program the WOR timing of `settings`, with automatic RC oscillator calibration.
+    ///
+    /// EVENT0, WOR_RES and RX_TIME are shared with `configure_rx_window`. The RC oscillator,
+    /// which times the sleep, is calibrated whenever the crystal runs, so on each event 0.
+    /// Calibrating the synthesizer on every wake up is expensive, consider
+    /// `AutoCalibration::ToIdleEvery4th`.
+    pub fn configure_wor(&mut self, settings: &WorSettings) -> Result<(), Error<SpiE>> {
+        // WOREVT1, WOREVT0, WORCTRL
+        self.0.modify_config_burst(Config::WOREVT1, |[evt1, evt0, worctrl]: &mut [u8; 3]| {
+            [*evt1, *evt0] = settings.event0.to_be_bytes();
+            *worctrl =
+                WORCTRL(*worctrl).modify().rc_pd(0).rc_cal(1).wor_res(settings.wor_res).bits();
+        })?;
+        self.0.modify_register(Config::MCSM2, |r| {
+            MCSM2(r).modify().rx_time(settings.rx_time).bits()
+        })?;
+        Ok(())
+    }
+
+    /// Start polling for packets, as set up by `configure_wor`.
+    ///
+    /// Goes to IDLE and flushes the RX FIFO first, the first event 0 comes a full period later.
+    pub fn enter_wor(&mut self) -> Result<(), Error<SpiE>> {
+        self.set_radio_mode(RadioMode::Idle)?;
+        self.flush_rx()?;
+        self.0.write_strobe(Command::SWORRST)?;
+        self.0.write_strobe(Command::SWOR)?;
+        Ok(())
+    }
+
+    /// Stop polling, waking the radio up if it's asleep. Blocks until it's in IDLE.
+    ///
+    /// A packet received before is left in the RX FIFO.
+    pub fn exit_wor(&mut self) -> Result<(), Error<SpiE>> {
+        self.wake_up_wait()?;
+        self.set_radio_mode(RadioMode::Idle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wor::*;
+
+    #[test]
+    fn test_wor_settings() {
+        let settings = WorSettings::new(1_000_000, 10_000);
+        assert_eq!(
+            settings,
+            WorSettings {
+                wor_res: 0,
+                event0: 36_000,
+                rx_time: 1
+            }
+        );
+        assert_eq!((settings.interval_us(), settings.rx_timeout_us()), (1_000_000, 18_029));
+
+        let settings = WorSettings::new(10_000_000, 1_000);
+        assert_eq!(
+            settings,
+            WorSettings {
+                wor_res: 1,
+                event0: 11_250,
+                rx_time: 4
+            }
+        );
+        assert_eq!(settings.interval_us(), 10_000_000);
+
+        // Longer than the longest timeout
+        assert_eq!(WorSettings::new(1_000_000, 100_000).rx_time, 0);
+    }
+}